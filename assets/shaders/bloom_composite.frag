@@ -0,0 +1,13 @@
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screenTexture;
+uniform sampler2D bloomTexture;
+uniform float intensity;
+
+void main() {
+  vec3 color = texture(screenTexture, TexCoords).rgb;
+  color += texture(bloomTexture, TexCoords).rgb * intensity;
+  FragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,12 @@
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screenTexture;
+uniform float threshold;
+
+void main() {
+  vec3 color = texture(screenTexture, TexCoords).rgb;
+  float brightness = dot(color, vec3(0.2126, 0.7152, 0.0722));
+  FragColor = brightness > threshold ? vec4(color, 1.0) : vec4(0.0, 0.0, 0.0, 1.0);
+}
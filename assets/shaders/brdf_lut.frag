@@ -0,0 +1,67 @@
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+const uint NUM_SAMPLES = 1024u;
+
+// Same as in prefilter.frag
+vec2 hammersley(uint i, uint n) {
+  uint bits = i;
+  bits = (bits << 16u) | (bits >> 16u);
+  bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+  bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+  bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+  bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+  float radical_inverse = float(bits) * 2.3283064365386963e-10;
+  return vec2(float(i) / float(n), radical_inverse);
+}
+
+vec3 importance_sample_ggx(vec2 Xi, vec3 N, float roughness) {
+  float a = roughness * roughness;
+  float phi = 2.0 * PBR_PI * Xi.x;
+  float cos_theta = sqrt((1.0 - Xi.y) / (1.0 + (a * a - 1.0) * Xi.y));
+  float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+  vec3 H = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+  vec3 up = abs(N.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+  vec3 tangent = normalize(cross(up, N));
+  vec3 bitangent = cross(N, tangent);
+  return normalize(tangent * H.x + bitangent * H.y + N * H.z);
+}
+
+// Image-based lighting remaps k differently than direct lighting does
+float geometry_smith_ibl(float n_dot_v, float n_dot_l, float roughness) {
+  float k = (roughness * roughness) / 2.0;
+  float ggx_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+  float ggx_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+  return ggx_v * ggx_l;
+}
+
+// Integrate the specular BRDF over the hemisphere, split into a scale (x) and bias (y)
+// to F0, for a view angle (u) and roughness (v)
+void main() {
+  float n_dot_v = TexCoords.x;
+  float roughness = TexCoords.y;
+  vec3 V = vec3(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+  vec3 N = vec3(0.0, 0.0, 1.0);
+
+  float scale = 0.0;
+  float bias = 0.0;
+  for (uint i = 0u; i < NUM_SAMPLES; i++) {
+    vec3 H = importance_sample_ggx(hammersley(i, NUM_SAMPLES), N, roughness);
+    vec3 L = normalize(2.0 * dot(V, H) * H - V);
+
+    float n_dot_l = max(L.z, 0.0);
+    float n_dot_h = max(H.z, 0.0);
+    float v_dot_h = max(dot(V, H), 0.0);
+    if (n_dot_l > 0.0) {
+      float G = geometry_smith_ibl(n_dot_v, n_dot_l, roughness);
+      float G_vis = (G * v_dot_h) / (n_dot_h * n_dot_v);
+      float Fc = pow(1.0 - v_dot_h, 5.0);
+      scale += (1.0 - Fc) * G_vis;
+      bias += Fc * G_vis;
+    }
+  }
+
+  FragColor = vec4(scale / float(NUM_SAMPLES), bias / float(NUM_SAMPLES), 0.0, 1.0);
+}
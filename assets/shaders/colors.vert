@@ -4,7 +4,7 @@ layout (location = 2) in vec2 aTexCoords;
 
 uniform mat4 model;
 
-#ifdef WASM
+#ifdef GLES
 out vec3 Normal;
 out vec3 FragPos;
 out vec2 TexCoords;
@@ -18,7 +18,7 @@ out VS_OUT {
 
 void main()
 {
-  #ifdef WASM
+  #ifdef GLES
   Normal = mat3(transpose(inverse(model))) * aNormal;
   FragPos = vec3(model * vec4(aPos, 1.0));
   TexCoords = aTexCoords;
@@ -0,0 +1,12 @@
+layout (location = 0) in vec3 aPos;
+
+out vec3 local_pos;
+
+// Prefixed to not clash with the view and projection of the built-in CameraBlock
+uniform mat4 cubemap_projection;
+uniform mat4 cubemap_view;
+
+void main() {
+  local_pos = aPos;
+  gl_Position = cubemap_projection * cubemap_view * vec4(aPos, 1.0);
+}
@@ -0,0 +1,12 @@
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aColor;
+
+uniform mat4 view_projection;
+
+out vec3 color;
+
+void main()
+{
+  color = aColor;
+  gl_Position = view_projection * vec4(aPos, 1.0);
+}
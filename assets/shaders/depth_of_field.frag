@@ -0,0 +1,47 @@
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screenTexture;
+uniform sampler2D depthTexture;
+uniform vec2 inverse_screen_size;
+uniform float focal_distance;
+uniform float focal_range;
+uniform float aperture;
+uniform float near;
+uniform float far;
+
+const int NUM_SAMPLES = 32;
+const float GOLDEN_ANGLE = 2.39996323;
+
+// Convert a depth buffer value back into distance from the camera
+float linear_depth(vec2 uv) {
+  return linearize_depth(texture(depthTexture, uv).r, near, far);
+}
+
+// Circle of confusion: how blurry a pixel is, from 0 (in focus) to 1
+float circle_of_confusion(vec2 uv) {
+  return clamp(abs(linear_depth(uv) - focal_distance) / focal_range, 0.0, 1.0);
+}
+
+void main() {
+  vec3 sharp = texture(screenTexture, TexCoords).rgb;
+  float coc = circle_of_confusion(TexCoords);
+
+  // Gather samples in a spiral filling a disc the size of the circle of confusion.
+  // Samples are weighted by their own blurriness so in-focus objects don't bleed outward.
+  vec3 blurred = sharp;
+  float total_weight = 1.0;
+  for (int i = 1; i < NUM_SAMPLES; i++) {
+    float radius = sqrt(float(i) / float(NUM_SAMPLES)) * coc * aperture;
+    float theta = float(i) * GOLDEN_ANGLE;
+    vec2 uv = TexCoords + vec2(cos(theta), sin(theta)) * radius * inverse_screen_size;
+
+    float weight = circle_of_confusion(uv);
+    blurred += texture(screenTexture, uv).rgb * weight;
+    total_weight += weight;
+  }
+  blurred /= total_weight;
+
+  FragColor = vec4(mix(sharp, blurred, coc), 1.0);
+}
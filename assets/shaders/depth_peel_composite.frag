@@ -0,0 +1,11 @@
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screenTexture;
+
+// One peeled layer, blended over everything behind it. Pixels the layer didn't cover were
+// cleared to zero alpha, so they leave the destination untouched.
+void main() {
+  FragColor = texture(screenTexture, TexCoords);
+}
@@ -0,0 +1,19 @@
+out vec4 FragColor;
+
+in vec3 local_pos;
+
+uniform sampler2D equirectangular_map;
+
+// 1 / (2 pi), 1 / pi
+const vec2 INV_ATAN = vec2(0.1591, 0.3183);
+
+// Longitude and latitude of a direction, mapped into [0, 1]
+vec2 sample_spherical_map(vec3 v) {
+  vec2 uv = vec2(atan(v.z, v.x), asin(v.y));
+  return uv * INV_ATAN + 0.5;
+}
+
+void main() {
+  vec2 uv = sample_spherical_map(normalize(local_pos));
+  FragColor = vec4(texture(equirectangular_map, uv).rgb, 1.0);
+}
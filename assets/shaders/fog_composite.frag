@@ -0,0 +1,10 @@
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screenTexture;
+
+// Fog color with its opacity as alpha, so blending mixes it into the scene below
+void main() {
+  FragColor = texture(screenTexture, TexCoords);
+}
@@ -0,0 +1,143 @@
+// FXAA 3.11 (quality variant), after Timothy Lottes' reference implementation.
+// FXAA_QUALITY__PRESET picks how far along an edge to search for its end.
+
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screenTexture;
+uniform vec2 inverse_screen_size;
+uniform float edge_threshold;
+uniform float edge_threshold_min;
+uniform float subpixel;
+
+#if FXAA_QUALITY__PRESET == 10
+const int NUM_STEPS = 4;
+const float STEP_SIZES[4] = float[](1.0, 1.5, 2.0, 4.0);
+#elif FXAA_QUALITY__PRESET == 20
+const int NUM_STEPS = 8;
+const float STEP_SIZES[8] = float[](1.0, 1.5, 2.0, 2.0, 2.0, 2.0, 4.0, 8.0);
+#else
+const int NUM_STEPS = 12;
+const float STEP_SIZES[12] = float[](1.0, 1.0, 1.0, 1.0, 1.0, 1.5, 2.0, 2.0, 2.0, 2.0, 4.0, 8.0);
+#endif
+
+float luma(vec3 color) {
+  return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+float luma_at(vec2 uv) {
+  return luma(texture(screenTexture, uv).rgb);
+}
+
+float luma_offset(vec2 offset) {
+  return luma_at(TexCoords + offset * inverse_screen_size);
+}
+
+void main() {
+  vec3 color = texture(screenTexture, TexCoords).rgb;
+  float luma_m = luma(color);
+  float luma_n = luma_offset(vec2(0.0, 1.0));
+  float luma_s = luma_offset(vec2(0.0, -1.0));
+  float luma_e = luma_offset(vec2(1.0, 0.0));
+  float luma_w = luma_offset(vec2(-1.0, 0.0));
+
+  float luma_min = min(luma_m, min(min(luma_n, luma_s), min(luma_e, luma_w)));
+  float luma_max = max(luma_m, max(max(luma_n, luma_s), max(luma_e, luma_w)));
+  float range = luma_max - luma_min;
+
+  // Skip pixels that aren't on an edge
+  if (range < max(edge_threshold_min, luma_max * edge_threshold)) {
+    FragColor = vec4(color, 1.0);
+    return;
+  }
+
+  float luma_ne = luma_offset(vec2(1.0, 1.0));
+  float luma_nw = luma_offset(vec2(-1.0, 1.0));
+  float luma_se = luma_offset(vec2(1.0, -1.0));
+  float luma_sw = luma_offset(vec2(-1.0, -1.0));
+
+  // Subpixel aliasing: how much the pixel differs from its neighborhood's average
+  float average = (2.0 * (luma_n + luma_s + luma_e + luma_w) + luma_ne + luma_nw + luma_se + luma_sw) / 12.0;
+  float subpixel_blend = smoothstep(0.0, 1.0, clamp(abs(average - luma_m) / range, 0.0, 1.0));
+  subpixel_blend = subpixel_blend * subpixel_blend * subpixel;
+
+  // Decide whether the edge runs horizontally or vertically
+  float horizontal_contrast =
+    abs(luma_n + luma_s - 2.0 * luma_m) * 2.0 +
+    abs(luma_ne + luma_se - 2.0 * luma_e) +
+    abs(luma_nw + luma_sw - 2.0 * luma_w);
+  float vertical_contrast =
+    abs(luma_e + luma_w - 2.0 * luma_m) * 2.0 +
+    abs(luma_ne + luma_nw - 2.0 * luma_n) +
+    abs(luma_se + luma_sw - 2.0 * luma_s);
+  bool is_horizontal = horizontal_contrast >= vertical_contrast;
+
+  // Pick which side of the pixel the edge is on
+  float luma_positive = is_horizontal ? luma_n : luma_e;
+  float luma_negative = is_horizontal ? luma_s : luma_w;
+  float gradient_positive = abs(luma_positive - luma_m);
+  float gradient_negative = abs(luma_negative - luma_m);
+  bool positive_side = gradient_positive >= gradient_negative;
+
+  float step_length = is_horizontal ? inverse_screen_size.y : inverse_screen_size.x;
+  float edge_luma = 0.5 * (luma_m + (positive_side ? luma_positive : luma_negative));
+  float gradient = max(gradient_positive, gradient_negative);
+  if (!positive_side) {
+    step_length = -step_length;
+  }
+
+  // Walk along the edge in both directions until reaching its ends
+  vec2 edge_uv = TexCoords;
+  vec2 edge_step;
+  if (is_horizontal) {
+    edge_uv.y += step_length * 0.5;
+    edge_step = vec2(inverse_screen_size.x, 0.0);
+  } else {
+    edge_uv.x += step_length * 0.5;
+    edge_step = vec2(0.0, inverse_screen_size.y);
+  }
+
+  float gradient_threshold = gradient * 0.25;
+  vec2 uv_positive = edge_uv;
+  vec2 uv_negative = edge_uv;
+  float delta_positive = 0.0;
+  float delta_negative = 0.0;
+  bool done_positive = false;
+  bool done_negative = false;
+  for (int i = 0; i < NUM_STEPS; i++) {
+    if (!done_positive) {
+      uv_positive += edge_step * STEP_SIZES[i];
+      delta_positive = luma_at(uv_positive) - edge_luma;
+      done_positive = abs(delta_positive) >= gradient_threshold;
+    }
+    if (!done_negative) {
+      uv_negative -= edge_step * STEP_SIZES[i];
+      delta_negative = luma_at(uv_negative) - edge_luma;
+      done_negative = abs(delta_negative) >= gradient_threshold;
+    }
+    if (done_positive && done_negative) {
+      break;
+    }
+  }
+
+  float distance_positive = is_horizontal ? uv_positive.x - TexCoords.x : uv_positive.y - TexCoords.y;
+  float distance_negative = is_horizontal ? TexCoords.x - uv_negative.x : TexCoords.y - uv_negative.y;
+
+  // Only blend if the pixel is on the side of the edge end that's heading away from it
+  float shortest = min(distance_positive, distance_negative);
+  float delta_sign = distance_positive <= distance_negative ? delta_positive : delta_negative;
+  float edge_blend = (delta_sign >= 0.0) != (luma_m - edge_luma >= 0.0)
+    ? 0.5 - shortest / (distance_positive + distance_negative)
+    : 0.0;
+
+  float blend = max(edge_blend, subpixel_blend);
+  vec2 uv = TexCoords;
+  if (is_horizontal) {
+    uv.y += step_length * blend;
+  } else {
+    uv.x += step_length * blend;
+  }
+
+  FragColor = vec4(texture(screenTexture, uv).rgb, 1.0);
+}
@@ -0,0 +1,21 @@
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screenTexture;
+uniform bool horizontal;
+
+// Weights of a 9-tap Gaussian kernel, from the center outward
+const float weight[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+void main() {
+  vec2 texel_size = 1.0 / vec2(textureSize(screenTexture, 0));
+  vec2 direction = horizontal ? vec2(texel_size.x, 0.0) : vec2(0.0, texel_size.y);
+
+  vec3 result = texture(screenTexture, TexCoords).rgb * weight[0];
+  for (int i = 1; i < 5; i++) {
+    result += texture(screenTexture, TexCoords + direction * float(i)).rgb * weight[i];
+    result += texture(screenTexture, TexCoords - direction * float(i)).rgb * weight[i];
+  }
+  FragColor = vec4(result, 1.0);
+}
@@ -0,0 +1,29 @@
+out vec4 FragColor;
+
+in vec3 local_pos;
+
+uniform samplerCube environment;
+
+// Average the light arriving over the hemisphere around the normal, weighted by cos(theta)
+void main() {
+  vec3 normal = normalize(local_pos);
+  vec3 up = abs(normal.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+  vec3 right = normalize(cross(up, normal));
+  up = cross(normal, right);
+
+  const float sample_delta = 0.025;
+  vec3 irradiance = vec3(0.0);
+  float num_samples = 0.0;
+  for (float phi = 0.0; phi < 2.0 * PBR_PI; phi += sample_delta) {
+    for (float theta = 0.0; theta < 0.5 * PBR_PI; theta += sample_delta) {
+      // Spherical to cartesian, in tangent space and then world space
+      vec3 tangent_sample = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+      vec3 sample_vec = tangent_sample.x * right + tangent_sample.y * up + tangent_sample.z * normal;
+
+      irradiance += texture(environment, sample_vec).rgb * cos(theta) * sin(theta);
+      num_samples++;
+    }
+  }
+
+  FragColor = vec4(PBR_PI * irradiance / num_samples, 1.0);
+}
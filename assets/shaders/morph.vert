@@ -0,0 +1,81 @@
+// Blend shapes: each morph target adds its deltas, scaled by its weight, to the base
+// vertex. Define MAX_MORPH_TARGETS to match Mesh::with_max_morph_targets if it was lowered.
+// Locations 3 to 5 are left for skinning and tangents, so at most 5 targets fit in the 16
+// vertex attributes every GL supports.
+#ifndef MAX_MORPH_TARGETS
+#define MAX_MORPH_TARGETS 5
+#endif
+
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+layout (location = 2) in vec2 aTexCoords;
+#if MAX_MORPH_TARGETS > 0
+layout (location = 6) in vec3 morph_position_0;
+layout (location = 7) in vec3 morph_normal_0;
+#endif
+#if MAX_MORPH_TARGETS > 1
+layout (location = 8) in vec3 morph_position_1;
+layout (location = 9) in vec3 morph_normal_1;
+#endif
+#if MAX_MORPH_TARGETS > 2
+layout (location = 10) in vec3 morph_position_2;
+layout (location = 11) in vec3 morph_normal_2;
+#endif
+#if MAX_MORPH_TARGETS > 3
+layout (location = 12) in vec3 morph_position_3;
+layout (location = 13) in vec3 morph_normal_3;
+#endif
+#if MAX_MORPH_TARGETS > 4
+layout (location = 14) in vec3 morph_position_4;
+layout (location = 15) in vec3 morph_normal_4;
+#endif
+
+uniform mat4 model;
+uniform float morph_weights[MAX_MORPH_TARGETS];
+uniform int morph_weights_len;
+
+out vec3 Normal;
+out vec3 FragPos;
+out vec2 TexCoords;
+
+void main()
+{
+  vec3 position = aPos;
+  vec3 normal = aNormal;
+  #if MAX_MORPH_TARGETS > 0
+  if (morph_weights_len > 0) {
+    position += morph_weights[0] * morph_position_0;
+    normal += morph_weights[0] * morph_normal_0;
+  }
+  #endif
+  #if MAX_MORPH_TARGETS > 1
+  if (morph_weights_len > 1) {
+    position += morph_weights[1] * morph_position_1;
+    normal += morph_weights[1] * morph_normal_1;
+  }
+  #endif
+  #if MAX_MORPH_TARGETS > 2
+  if (morph_weights_len > 2) {
+    position += morph_weights[2] * morph_position_2;
+    normal += morph_weights[2] * morph_normal_2;
+  }
+  #endif
+  #if MAX_MORPH_TARGETS > 3
+  if (morph_weights_len > 3) {
+    position += morph_weights[3] * morph_position_3;
+    normal += morph_weights[3] * morph_normal_3;
+  }
+  #endif
+  #if MAX_MORPH_TARGETS > 4
+  if (morph_weights_len > 4) {
+    position += morph_weights[4] * morph_position_4;
+    normal += morph_weights[4] * morph_normal_4;
+  }
+  #endif
+
+  Normal = mat3(transpose(inverse(model))) * normalize(normal);
+  FragPos = vec3(model * vec4(position, 1.0));
+  TexCoords = aTexCoords;
+
+  gl_Position = projection * view * model * vec4(position, 1.0);
+}
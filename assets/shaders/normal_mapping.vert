@@ -0,0 +1,27 @@
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+layout (location = 2) in vec2 aTexCoords;
+layout (location = 5) in vec4 aTangent;
+
+uniform mat4 model;
+
+out vec3 FragPos;
+out vec2 TexCoords;
+// Columns take tangent-space normals from the normal map to world space
+out mat3 TBN;
+
+void main()
+{
+  mat3 normal_matrix = mat3(transpose(inverse(model)));
+  vec3 N = normalize(normal_matrix * aNormal);
+  vec3 T = normalize(mat3(model) * aTangent.xyz);
+  // Re-orthogonalize, since non-uniform scaling can skew the tangent off the normal
+  T = normalize(T - dot(T, N) * N);
+  vec3 B = cross(N, T) * aTangent.w;
+  TBN = mat3(T, B, N);
+
+  FragPos = vec3(model * vec4(aPos, 1.0));
+  TexCoords = aTexCoords;
+
+  gl_Position = projection * view * model * vec4(aPos, 1.0);
+}
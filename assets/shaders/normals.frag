@@ -0,0 +1,8 @@
+out vec4 FragColor;
+
+uniform vec4 color;
+
+void main()
+{
+  FragColor = color;
+}
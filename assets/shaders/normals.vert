@@ -0,0 +1,17 @@
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+
+uniform mat4 model;
+uniform mat4 camera_view;
+
+out VS_OUT {
+  vec3 normal;
+} vs_out;
+
+void main()
+{
+  // Do everything in view space, and let the geometry shader apply the projection
+  mat4 model_view = camera_view * model;
+  gl_Position = model_view * vec4(aPos, 1.0);
+  vs_out.normal = normalize(mat3(transpose(inverse(model_view))) * aNormal);
+}
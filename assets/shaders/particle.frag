@@ -0,0 +1,13 @@
+in vec4 f_color;
+in vec2 f_corner;
+
+out vec4 out_color;
+
+void main() {
+    // Round particles with a soft edge
+    float alpha = 1.0 - smoothstep(0.4, 0.5, length(f_corner));
+    if (alpha <= 0.0) {
+        discard;
+    }
+    out_color = f_color * vec4(1.0, 1.0, 1.0, alpha);
+}
@@ -0,0 +1,27 @@
+layout(location = 0) in vec3 center;
+layout(location = 1) in vec4 color;
+layout(location = 2) in float size;
+
+out vec4 f_color;
+out vec2 f_corner;
+
+uniform mat4 view_projection;
+uniform vec3 camera_right;
+uniform vec3 camera_up;
+
+// generate a camera-facing quad based on vertex ID
+void main() {
+    vec2 corner = vec2(0.0);
+    switch (gl_VertexID) {
+        case 0: corner = vec2(-0.5, 0.5); break;
+        case 1: corner = vec2(0.5, 0.5); break;
+        case 2: corner = vec2(-0.5, -0.5); break;
+        case 3: corner = vec2(0.5, -0.5); break;
+    }
+
+    vec3 pos = center + (camera_right * corner.x + camera_up * corner.y) * size;
+
+    f_color = color;
+    f_corner = corner;
+    gl_Position = view_projection * vec4(pos, 1.0);
+}
@@ -0,0 +1,21 @@
+layout (location = 0) out vec3 gPosition;
+layout (location = 1) out vec3 gNormal;
+layout (location = 2) out vec4 gAlbedoSpec;
+layout (location = 3) out vec4 gMaterial;
+
+in vec3 Normal;
+in vec3 FragPos;
+in vec2 TexCoords;
+
+uniform PbrMaterial material;
+
+void main() {
+  vec3 albedo;
+  float metallic, roughness, ao;
+  pbr_sample(material, TexCoords, albedo, metallic, roughness, ao);
+
+  gPosition = FragPos;
+  gNormal = normalize(Normal);
+  gAlbedoSpec = vec4(albedo, 1.0);
+  gMaterial = vec4(metallic, roughness, ao, 1.0);
+}
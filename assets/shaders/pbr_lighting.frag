@@ -0,0 +1,59 @@
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+// G-buffer positions and normals are in world space
+uniform sampler2D gPosition;
+uniform sampler2D gNormal;
+uniform sampler2D gAlbedoSpec;
+uniform sampler2D gMaterial;
+uniform sampler2D ssao;
+
+uniform IblMaps ibl;
+uniform samplerCube skybox;
+uniform mat4 inverse_view_projection;
+
+uniform DirLight dir_lights[4];
+uniform int dir_lights_len;
+uniform PointLight point_lights[16];
+uniform int point_lights_len;
+
+void main() {
+  vec3 N = texture(gNormal, TexCoords).xyz;
+
+  // Nothing was drawn here, so show the environment behind the scene
+  if (N == vec3(0.0)) {
+    vec4 far = inverse_view_projection * vec4(TexCoords * 2.0 - 1.0, 1.0, 1.0);
+    vec3 direction = far.xyz / far.w - view_pos;
+    FragColor = vec4(texture(skybox, direction).rgb, 1.0);
+    return;
+  }
+
+  vec3 position = texture(gPosition, TexCoords).xyz;
+  vec3 albedo = texture(gAlbedoSpec, TexCoords).rgb;
+  vec3 params = texture(gMaterial, TexCoords).rgb;
+  float metallic = params.r;
+  float roughness = params.g;
+  float ao = params.b * texture(ssao, TexCoords).r;
+
+  N = normalize(N);
+  vec3 V = normalize(view_pos - position);
+
+  // The lights' diffuse color is used as their radiance
+  vec3 color = vec3(0.0);
+  for (int i = 0; i < dir_lights_len; i++) {
+    vec3 L = normalize(-dir_lights[i].direction);
+    color += cook_torrance(N, V, L, dir_lights[i].diffuse, albedo, metallic, roughness);
+  }
+  for (int i = 0; i < point_lights_len; i++) {
+    PointLight light = point_lights[i];
+    vec3 to_light = light.position - position;
+    float d = length(to_light);
+    float attenuation = 1.0 / (light.constant + light.linear * d + light.quadratic * d * d);
+    color += cook_torrance(
+      N, V, to_light / d, light.diffuse * attenuation, albedo, metallic, roughness);
+  }
+
+  color += ibl_ambient(ibl, N, V, albedo, metallic, roughness, ao);
+  FragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,62 @@
+out vec4 FragColor;
+
+in vec3 local_pos;
+
+uniform samplerCube environment;
+uniform float environment_size;
+uniform float roughness;
+
+const uint NUM_SAMPLES = 1024u;
+
+// Low-discrepancy sequence, more evenly spread than random samples
+vec2 hammersley(uint i, uint n) {
+  uint bits = i;
+  bits = (bits << 16u) | (bits >> 16u);
+  bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+  bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+  bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+  bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+  float radical_inverse = float(bits) * 2.3283064365386963e-10;
+  return vec2(float(i) / float(n), radical_inverse);
+}
+
+// Sample a halfway vector around N, distributed like GGX microfacets
+vec3 importance_sample_ggx(vec2 Xi, vec3 N, float roughness) {
+  float a = roughness * roughness;
+  float phi = 2.0 * PBR_PI * Xi.x;
+  float cos_theta = sqrt((1.0 - Xi.y) / (1.0 + (a * a - 1.0) * Xi.y));
+  float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+  vec3 H = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+  vec3 up = abs(N.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+  vec3 tangent = normalize(cross(up, N));
+  vec3 bitangent = cross(N, tangent);
+  return normalize(tangent * H.x + bitangent * H.y + N * H.z);
+}
+
+void main() {
+  // Assume the view direction equals the reflection direction equals the normal
+  vec3 N = normalize(local_pos);
+  vec3 V = N;
+
+  vec3 color = vec3(0.0);
+  float total_weight = 0.0;
+  for (uint i = 0u; i < NUM_SAMPLES; i++) {
+    vec3 H = importance_sample_ggx(hammersley(i, NUM_SAMPLES), N, roughness);
+    vec3 L = normalize(2.0 * dot(V, H) * H - V);
+    float n_dot_l = max(dot(N, L), 0.0);
+    if (n_dot_l > 0.0) {
+      // Sample a blurrier mip for unlikely directions, so bright spots don't alias
+      float n_dot_h = max(dot(N, H), 0.0);
+      float pdf = distribution_ggx(N, H, roughness) * n_dot_h / (4.0 * n_dot_h) + 0.0001;
+      float sa_texel = 4.0 * PBR_PI / (6.0 * environment_size * environment_size);
+      float sa_sample = 1.0 / (float(NUM_SAMPLES) * pdf + 0.0001);
+      float mip = roughness == 0.0 ? 0.0 : 0.5 * log2(sa_sample / sa_texel);
+
+      color += textureLod(environment, L, mip).rgb * n_dot_l;
+      total_weight += n_dot_l;
+    }
+  }
+
+  FragColor = vec4(color / total_weight, 1.0);
+}
@@ -4,6 +4,7 @@ in vec2 TexCoords;
 
 uniform sampler2D screenTexture;
 uniform int effect;
+uniform float gamma;
 
 vec4 invert() {
   vec3 pixel = vec3(texture(screenTexture, TexCoords));
@@ -82,4 +83,8 @@ void main() {
     );
     FragColor = convolve(kernel);
   }
+
+  // Encode linear color for the display (gamma is 1 when correction is off). The sharpen
+  // and edge kernels can go negative, where pow is undefined, so clamp first.
+  FragColor.rgb = pow(max(FragColor.rgb, vec3(0.0)), vec3(1.0 / gamma));
 }
\ No newline at end of file
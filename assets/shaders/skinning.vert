@@ -0,0 +1,30 @@
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+layout (location = 2) in vec2 aTexCoords;
+layout (location = 3) in ivec4 bone_indices;
+layout (location = 4) in vec4 bone_weights;
+
+uniform mat4 model;
+
+out vec3 Normal;
+out vec3 FragPos;
+out vec2 TexCoords;
+
+void main()
+{
+  // Linear blend skinning: each vertex follows a weighted average of its bones' transforms
+  mat4 skin =
+    bone_weights.x * bones[bone_indices.x] +
+    bone_weights.y * bones[bone_indices.y] +
+    bone_weights.z * bones[bone_indices.z] +
+    bone_weights.w * bones[bone_indices.w];
+
+  vec4 skinned_pos = skin * vec4(aPos, 1.0);
+  vec3 skinned_normal = mat3(skin) * aNormal;
+
+  Normal = mat3(transpose(inverse(model))) * skinned_normal;
+  FragPos = vec3(model * skinned_pos);
+  TexCoords = aTexCoords;
+
+  gl_Position = projection * view * model * skinned_pos;
+}
@@ -0,0 +1,45 @@
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+// G-buffer positions and normals are in world space
+uniform sampler2D gPosition;
+uniform sampler2D gNormal;
+uniform sampler2D texNoise;
+
+uniform vec3 samples[64];
+uniform int kernel_size;
+uniform float radius;
+uniform float bias;
+
+// Prefixed to not clash with the view and projection of the built-in CameraBlock
+uniform mat4 ssao_view;
+uniform mat4 ssao_projection;
+uniform vec2 noise_scale;
+
+void main() {
+  vec3 frag_pos = vec3(ssao_view * vec4(texture(gPosition, TexCoords).xyz, 1.0));
+  vec3 normal = normalize(mat3(ssao_view) * texture(gNormal, TexCoords).xyz);
+  vec3 random_vec = normalize(texture(texNoise, TexCoords * noise_scale).xyz);
+
+  // Gram-Schmidt to build a tangent frame around the normal, randomly rotated
+  vec3 tangent = normalize(random_vec - normal * dot(random_vec, normal));
+  vec3 bitangent = cross(normal, tangent);
+  mat3 TBN = mat3(tangent, bitangent, normal);
+
+  float occlusion = 0.0;
+  for (int i = 0; i < kernel_size; i++) {
+    vec3 sample_pos = frag_pos + TBN * samples[i] * radius;
+
+    // Project the sample to find the geometry it lines up with on screen
+    vec4 offset = ssao_projection * vec4(sample_pos, 1.0);
+    offset.xy = (offset.xy / offset.w) * 0.5 + 0.5;
+    float sample_depth = (ssao_view * vec4(texture(gPosition, offset.xy).xyz, 1.0)).z;
+
+    // Ignore geometry far outside the radius, e.g. a background behind an edge
+    float range_check = smoothstep(0.0, 1.0, radius / abs(frag_pos.z - sample_depth));
+    occlusion += (sample_depth >= sample_pos.z + bias ? 1.0 : 0.0) * range_check;
+  }
+
+  FragColor = vec4(vec3(1.0 - occlusion / float(kernel_size)), 1.0);
+}
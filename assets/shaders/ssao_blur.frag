@@ -0,0 +1,18 @@
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screenTexture;
+
+// Box blur matching the 4x4 noise texture, which cancels out the noise pattern
+void main() {
+  vec2 texel_size = 1.0 / vec2(textureSize(screenTexture, 0));
+  float result = 0.0;
+  for (int x = -2; x < 2; x++) {
+    for (int y = -2; y < 2; y++) {
+      vec2 offset = vec2(float(x), float(y)) * texel_size;
+      result += texture(screenTexture, TexCoords + offset).r;
+    }
+  }
+  FragColor = vec4(vec3(result / 16.0), 1.0);
+}
@@ -0,0 +1,61 @@
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screenTexture;
+// G-buffer positions and normals are in world space
+uniform sampler2D gPosition;
+uniform sampler2D gNormal;
+uniform samplerCube environment;
+
+// Prefixed to not clash with the view and projection of the built-in CameraBlock
+uniform mat4 ssr_view;
+uniform mat4 inverse_view;
+uniform mat4 ssr_projection;
+uniform int max_steps;
+uniform float step_size;
+uniform float thickness;
+
+vec2 project(vec3 view_pos) {
+  vec4 clip = ssr_projection * vec4(view_pos, 1.0);
+  return clip.xy / clip.w * 0.5 + 0.5;
+}
+
+void main() {
+  vec3 world_normal = texture(gNormal, TexCoords).xyz;
+  // Background pixels have no geometry to reflect from
+  if (world_normal == vec3(0.0)) {
+    FragColor = vec4(0.0);
+    return;
+  }
+
+  vec3 view_pos = vec3(ssr_view * vec4(texture(gPosition, TexCoords).xyz, 1.0));
+  vec3 view_normal = normalize(mat3(ssr_view) * world_normal);
+  vec3 ray = normalize(reflect(normalize(view_pos), view_normal));
+
+  vec3 environment_color = texture(environment, mat3(inverse_view) * ray).rgb;
+
+  vec3 ray_pos = view_pos;
+  for (int i = 0; i < max_steps; i++) {
+    ray_pos += ray * step_size;
+
+    vec2 uv = project(ray_pos);
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+      break;
+    }
+
+    // View space looks down -Z, so a ray behind the surface has a smaller z
+    float scene_z = (ssr_view * vec4(texture(gPosition, uv).xyz, 1.0)).z;
+    float delta = scene_z - ray_pos.z;
+    if (delta > 0.0 && delta < thickness) {
+      // Fade out hits near the edge of the screen, where the reflection would be cut off
+      vec2 edge = smoothstep(0.0, 0.1, uv) * (1.0 - smoothstep(0.9, 1.0, uv));
+      float confidence = edge.x * edge.y;
+      vec3 hit_color = texture(screenTexture, uv).rgb;
+      FragColor = vec4(mix(environment_color, hit_color, confidence), confidence);
+      return;
+    }
+  }
+
+  FragColor = vec4(environment_color, 0.0);
+}
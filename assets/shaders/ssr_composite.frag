@@ -0,0 +1,15 @@
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screenTexture;
+uniform sampler2D reflections;
+// Specular intensity in alpha
+uniform sampler2D gAlbedoSpec;
+
+void main() {
+  vec3 color = texture(screenTexture, TexCoords).rgb;
+  float specular = texture(gAlbedoSpec, TexCoords).a;
+  color += texture(reflections, TexCoords).rgb * specular;
+  FragColor = vec4(color, 1.0);
+}
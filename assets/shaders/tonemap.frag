@@ -0,0 +1,46 @@
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screenTexture;
+uniform float exposure;
+uniform float gamma;
+
+#if defined(TONEMAP_ACES)
+// Narkowicz's fit of the ACES filmic curve
+vec3 tonemap(vec3 x) {
+  const float a = 2.51;
+  const float b = 0.03;
+  const float c = 2.43;
+  const float d = 0.59;
+  const float e = 0.14;
+  return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
+}
+#elif defined(TONEMAP_UNCHARTED2)
+// John Hable's filmic curve from Uncharted 2
+vec3 hable(vec3 x) {
+  const float A = 0.15;
+  const float B = 0.50;
+  const float C = 0.10;
+  const float D = 0.20;
+  const float E = 0.02;
+  const float F = 0.30;
+  return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+}
+
+vec3 tonemap(vec3 x) {
+  const float white_point = 11.2;
+  return hable(x * 2.0) / hable(vec3(white_point));
+}
+#else
+vec3 tonemap(vec3 x) {
+  return x / (x + vec3(1.0));
+}
+#endif
+
+void main() {
+  vec3 hdr = texture(screenTexture, TexCoords).rgb;
+  vec3 ldr = max(tonemap(hdr * exposure), vec3(0.0));
+  // gamma is 1 unless this pass encodes for the display with GammaCorrection::Manual
+  FragColor = vec4(pow(ldr, vec3(1.0 / gamma)), 1.0);
+}
@@ -0,0 +1,47 @@
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D depth_texture;
+uniform mat4 inverse_view_projection;
+uniform vec3 camera_pos;
+
+uniform vec3 fog_color;
+uniform float density;
+uniform int steps;
+uniform float max_distance;
+uniform float base_height;
+uniform float height_falloff;
+
+// Fog thins out exponentially above base_height
+float fog_density(vec3 p) {
+  return density * exp(-height_falloff * max(p.y - base_height, 0.0));
+}
+
+// Per-pixel offset for the first step, which turns banding into less noticeable noise
+float jitter(vec2 p) {
+  return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+void main() {
+  float depth = texture(depth_texture, TexCoords).r;
+  vec4 world = inverse_view_projection * vec4(vec3(TexCoords, depth) * 2.0 - 1.0, 1.0);
+  vec3 to_frag = world.xyz / world.w - camera_pos;
+
+  // Stop at the first surface, or at max_distance for the sky
+  float dist = min(length(to_frag), max_distance);
+  vec3 dir = normalize(to_frag);
+  float step_size = dist / float(steps);
+
+  // March from the camera, accumulating how much light the fog blocks (optical depth)
+  float optical_depth = 0.0;
+  float t = step_size * jitter(gl_FragCoord.xy);
+  for (int i = 0; i < steps; i++) {
+    optical_depth += fog_density(camera_pos + dir * t) * step_size;
+    t += step_size;
+  }
+
+  // Beer-Lambert: the fraction of the scene hidden by fog, replaced by the fog color
+  float opacity = 1.0 - exp(-optical_depth);
+  FragColor = vec4(fog_color, opacity);
+}
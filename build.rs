@@ -0,0 +1,17 @@
+// With the spirv-reflect feature, generate uniform structs from the SPIR-V shaders in
+// assets/shaders/spirv. See src/build_support.rs.
+#[cfg(feature = "spirv-reflect")]
+#[path = "src/build_support.rs"]
+mod build_support;
+
+fn main() {
+  #[cfg(feature = "spirv-reflect")]
+  {
+    let out_dir = std::path::PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
+    build_support::generate_uniform_structs(
+      std::path::Path::new("assets/shaders/spirv"),
+      &out_dir.join("spirv_uniforms.rs"),
+    )
+    .unwrap();
+  }
+}
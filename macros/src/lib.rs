@@ -25,17 +25,32 @@ pub fn bind_uniform_derive(input: TokenStream) -> TokenStream {
     .map(|field| {
       let ident = field.ident.as_ref().unwrap();
       let ident_str = ident.to_string();
+
+      // Optional fields also tell the shader whether they're present via a has_ flag
+      let is_option = match &field.ty {
+        syn::Type::Path(path) => path.path.segments[0].ident == "Option",
+        _ => false,
+      };
+      let has_flag = if is_option {
+        quote! {
+          shader.bind_uniform(gl, &format!("{}.has_{}", name, #ident_str), &self.#ident.is_some());
+        }
+      } else {
+        quote! {}
+      };
+
       quote! {
         self
           .#ident
           .bind_uniform(gl, shader, &format!("{}.{}", name, #ident_str));
+        #has_flag
       }
     })
     .collect::<Vec<_>>();
 
   let imp = quote! {
     impl crate::shader::BindUniform for #ident {
-      unsafe fn bind_uniform(&self, gl: &glow::Context, shader: &mut crate::shader::ActiveShader, name: &str) {
+      unsafe fn bind_uniform(&self, gl: &crate::context::Context, shader: &mut crate::shader::ActiveShader, name: &str) {
         #(#calls)*
       }
     }
@@ -80,13 +95,17 @@ pub fn bind_shader_type_def(input: TokenStream) -> TokenStream {
 
       let primitive_type = |ident: &syn::Ident| match ident.to_string().as_str() {
         "f32" => "float",
+        "i32" => "int",
+        "u32" => "uint",
+        "bool" => "bool",
         "Vec4" => "vec4",
-        "Vec3" => "vec3",
+        "Vec3" | "Color" => "vec3",
         "Vec2" => "vec2",
+        "Mat3" => "mat3",
         "Mat4" => "mat4",
         "T2d" | "Texture" => "sampler2D",
         "TCubemap" => "samplerCube",
-        id => unimplemented!("primtive {}", id),
+        id => unimplemented!("primitive {}", id),
       };
 
       match &ty.arguments {
@@ -109,6 +128,14 @@ pub fn bind_shader_type_def(input: TokenStream) -> TokenStream {
             "Texture" => {
               format!("{} {};", primitive_type(&arg.ident), ident_str)
             }
+            "Option" => {
+              format!(
+                "{} {}; bool has_{};",
+                primitive_type(&arg.ident),
+                ident_str,
+                ident_str
+              )
+            }
             _ => unimplemented!(),
           }
         }
@@ -164,6 +191,12 @@ pub fn bind_shader_block_def(input: TokenStream) -> TokenStream {
       } else {
         unimplemented!()
       };
+      // std140::boolean is the only type not named after its GLSL type
+      let ty = if ty == "boolean" {
+        "bool".to_string()
+      } else {
+        ty
+      };
 
       format!("{} {};", ty, ident.to_string())
     })
@@ -0,0 +1,226 @@
+use crate::{
+  prelude::*,
+  shader::{ShaderBlockDef, UniformBlock},
+  transform::Transform,
+};
+use std140::ReprStd140;
+
+// Must match the array size in BoneBlock::BLOCK_DEF
+pub const MAX_BONES: usize = 128;
+
+// A hierarchy of bones posed by forward kinematics. Bones are stored so that every parent
+// comes before its children, which lets one pass over the bones compute all world matrices.
+pub struct Skeleton {
+  parents: Vec<Option<usize>>,
+  local_transforms: Vec<Mat4>,
+
+  // Bone-to-model transforms for the current pose
+  pub bones: Vec<Mat4>,
+
+  // Model-to-bone transforms for the bind pose, i.e. the pose the mesh was modeled in
+  pub inverse_bind_matrices: Vec<Mat4>,
+}
+
+impl Skeleton {
+  // The bind pose is taken as the initial pose, so every skinning matrix starts as identity
+  pub fn new(parents: Vec<Option<usize>>, inverse_bind_matrices: Vec<Mat4>) -> Result<Self> {
+    if parents.len() != inverse_bind_matrices.len() {
+      bail!(
+        "{} parents but {} inverse bind matrices",
+        parents.len(),
+        inverse_bind_matrices.len()
+      );
+    }
+    if parents.len() > MAX_BONES {
+      bail!(
+        "Skeleton has {} bones, more than MAX_BONES ({})",
+        parents.len(),
+        MAX_BONES
+      );
+    }
+    for (i, parent) in parents.iter().enumerate() {
+      if let Some(parent) = *parent {
+        if parent >= i {
+          bail!("Bone {} comes before its parent {}", i, parent);
+        }
+      }
+    }
+
+    let bones = inverse_bind_matrices
+      .iter()
+      .map(|m| m.try_inverse().unwrap_or_else(Mat4::identity))
+      .collect::<Vec<_>>();
+    let local_transforms = parents
+      .iter()
+      .zip(&bones)
+      .map(|(parent, bone)| match parent {
+        Some(parent) => bones[*parent].try_inverse().unwrap_or_else(Mat4::identity) * bone,
+        None => *bone,
+      })
+      .collect();
+
+    Ok(Skeleton {
+      parents,
+      local_transforms,
+      bones,
+      inverse_bind_matrices,
+    })
+  }
+
+  pub fn len(&self) -> usize {
+    self.bones.len()
+  }
+
+  // Set a bone's transform relative to its parent, and recompute the world matrices of it
+  // and everything after it (which includes all of its descendants)
+  pub fn update_bone(&mut self, index: usize, local_transform: Mat4) {
+    self.local_transforms[index] = local_transform;
+    for i in index..self.bones.len() {
+      let parent = self.parents[i].map_or_else(Mat4::identity, |p| self.bones[p]);
+      self.bones[i] = parent * self.local_transforms[i];
+    }
+  }
+
+  // Set every bone's transform relative to its parent at once, e.g. from Animation::sample
+  pub fn set_pose(&mut self, local_transforms: &[Mat4]) {
+    for (local, new_local) in self.local_transforms.iter_mut().zip(local_transforms) {
+      *local = *new_local;
+    }
+    for i in 0..self.bones.len() {
+      let parent = self.parents[i].map_or_else(Mat4::identity, |p| self.bones[p]);
+      self.bones[i] = parent * self.local_transforms[i];
+    }
+  }
+
+  // Matrices taking bind-pose vertices to their posed positions
+  pub fn skinning_matrices(&self) -> impl Iterator<Item = Mat4> + '_ {
+    self
+      .bones
+      .iter()
+      .zip(&self.inverse_bind_matrices)
+      .map(|(bone, inverse_bind)| bone * inverse_bind)
+  }
+
+  pub fn uniform_block(&self) -> BoneBlock {
+    let mut block = BoneBlock {
+      bones: [[0.; 16]; MAX_BONES],
+    };
+    for (dst, matrix) in block.bones.iter_mut().zip(self.skinning_matrices()) {
+      dst.copy_from_slice(matrix.as_slice());
+    }
+    block
+  }
+
+  pub unsafe fn upload(&self, gl: &Context, ubo: &UniformBlock<BoneBlock>) {
+    ubo.upload(gl, &self.uniform_block());
+  }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Interpolation {
+  Step,
+  Linear,
+}
+
+// Values of one property over time. Times are in seconds and increasing.
+#[derive(Clone)]
+pub struct Keyframes<T> {
+  pub times: Vec<f32>,
+  pub values: Vec<T>,
+  pub interpolation: Interpolation,
+}
+
+impl<T: Copy> Keyframes<T> {
+  // Times outside the keyframes hold the first or last value
+  fn sample(&self, time: f32, lerp: impl Fn(&T, &T, f32) -> T) -> Option<T> {
+    let last = self.times.len().checked_sub(1)?;
+    let next = self
+      .times
+      .iter()
+      .position(|t| *t > time)
+      .unwrap_or(last + 1);
+    if next == 0 {
+      return Some(self.values[0]);
+    } else if next > last {
+      return Some(self.values[last]);
+    }
+
+    let (prev_time, next_time) = (self.times[next - 1], self.times[next]);
+    let (prev, next) = (&self.values[next - 1], &self.values[next]);
+    Some(match self.interpolation {
+      Interpolation::Step => *prev,
+      Interpolation::Linear => lerp(prev, next, (time - prev_time) / (next_time - prev_time)),
+    })
+  }
+}
+
+// The animated properties of one bone. Properties without keyframes stay at their rest value.
+#[derive(Clone, Default)]
+pub struct BoneTrack {
+  pub translations: Option<Keyframes<Vec3>>,
+  pub rotations: Option<Keyframes<glm::Quat>>,
+  pub scales: Option<Keyframes<Vec3>>,
+}
+
+// A keyframed clip posing a skeleton, with one track (possibly empty) per bone
+pub struct Animation {
+  pub name: Option<String>,
+  pub duration: f32,
+  pub rest_pose: Vec<Transform>,
+  pub tracks: Vec<BoneTrack>,
+}
+
+impl Animation {
+  // Local bone transforms at the given time, looping after the duration. Pass the result
+  // to Skeleton::set_pose.
+  pub fn sample(&self, time: f32) -> Vec<Mat4> {
+    let time = if self.duration > 0. {
+      time.rem_euclid(self.duration)
+    } else {
+      0.
+    };
+
+    self
+      .rest_pose
+      .iter()
+      .zip(&self.tracks)
+      .map(|(rest, track)| {
+        let sample_vec3 = |keyframes: &Option<Keyframes<Vec3>>, rest: Vec3| {
+          keyframes
+            .as_ref()
+            .and_then(|keyframes| keyframes.sample(time, glm::lerp))
+            .unwrap_or(rest)
+        };
+        Transform {
+          position: sample_vec3(&track.translations, rest.position),
+          rotation: track
+            .rotations
+            .as_ref()
+            .and_then(|keyframes| keyframes.sample(time, glm::quat_slerp))
+            .unwrap_or(rest.rotation),
+          scale: sample_vec3(&track.scales, rest.scale),
+        }
+        .matrix(None)
+      })
+      .collect()
+  }
+}
+
+// std140 lays out an array of mat4 as tightly packed column-major matrices, which is just
+// what a Rust array of column-major matrices looks like, so no padding is needed. At 8KB
+// the block isn't built in to every shader: build skinned shaders with
+// ShaderBuilder::with_block_def::<BoneBlock>().
+#[repr(C)]
+pub struct BoneBlock {
+  bones: [[f32; 16]; MAX_BONES],
+}
+
+unsafe impl ReprStd140 for BoneBlock {}
+
+impl ShaderBlockDef for BoneBlock {
+  const BLOCK_DEF: &'static str = r#"
+layout (std140) uniform BoneBlock {
+  mat4 bones[128];
+};
+"#;
+}
@@ -0,0 +1,90 @@
+use crate::{
+  framebuffer::{Framebuffer, PingPong},
+  post_process::PostProcess,
+  prelude::*,
+  texture::Texture,
+};
+
+// Makes bright parts of an HDR image glow by blurring them and adding them back on top.
+// Works at half resolution, since the result is blurry anyway.
+pub struct Bloom {
+  bright: Framebuffer,
+  ping_pong: PingPong,
+  threshold_pass: PostProcess,
+  blur_pass: PostProcess,
+  composite_pass: PostProcess,
+  width: u32,
+  height: u32,
+
+  // How strongly the bloom is added back onto the scene
+  pub intensity: f32,
+}
+
+impl Bloom {
+  pub async unsafe fn new(gl: &Context, width: u32, height: u32) -> Result<Self> {
+    let (width, height) = ((width / 2).max(1), (height / 2).max(1));
+    Ok(Bloom {
+      bright: Framebuffer::with_color_format(gl, width, height, glow::RGBA16F)?,
+      ping_pong: PingPong::with_color_format(gl, width, height, glow::RGBA16F)?,
+      threshold_pass: PostProcess::new(gl, "assets/shaders/bloom_threshold.frag").await?,
+      blur_pass: PostProcess::new(gl, "assets/shaders/gaussian_blur.frag").await?,
+      composite_pass: PostProcess::new(gl, "assets/shaders/bloom_composite.frag").await?,
+      width,
+      height,
+      intensity: 1.,
+    })
+  }
+
+  // Extract the pixels of hdr_texture brighter than threshold, then blur them. Each
+  // iteration is one horizontal and one vertical Gaussian blur pass. The caller is
+  // responsible for restoring the viewport afterwards.
+  pub unsafe fn render(
+    &mut self,
+    gl: &Context,
+    hdr_texture: &Texture,
+    threshold: f32,
+    iterations: u32,
+  ) {
+    gl.viewport(0, 0, self.width as i32, self.height as i32);
+
+    self.bright.bind(gl);
+    self.threshold_pass.draw(gl, hdr_texture, |gl, shader| {
+      shader.bind_uniform(gl, "threshold", &threshold);
+    });
+
+    for i in 0..iterations * 2 {
+      let (_, previous) = self.ping_pong.next(gl);
+      let input = if i == 0 {
+        self.bright.color.as_ref().unwrap()
+      } else {
+        previous
+      };
+      self.blur_pass.draw(gl, input, |gl, shader| {
+        shader.bind_uniform(gl, "horizontal", &(i % 2 == 0));
+      });
+    }
+
+    self.bright.unbind(gl);
+  }
+
+  // The blurred bright pixels from the last render
+  pub fn result(&self) -> &Texture {
+    self.ping_pong.result()
+  }
+
+  // Draw scene with the bloom added on top into the currently bound framebuffer
+  pub unsafe fn composite(&self, gl: &Context, scene: &Texture) {
+    self.composite_pass.draw(gl, scene, |gl, shader| {
+      shader.bind_uniform(gl, "bloomTexture", self.result());
+      shader.bind_uniform(gl, "intensity", &self.intensity);
+    });
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.bright.dispose(gl);
+    self.ping_pong.dispose(gl);
+    self.threshold_pass.dispose(gl);
+    self.blur_pass.dispose(gl);
+    self.composite_pass.dispose(gl);
+  }
+}
@@ -0,0 +1,230 @@
+// Build script support for generating uniform structs from SPIR-V shaders, enabled by the
+// spirv-reflect feature. This is compiled into build.rs rather than the crate, so it can
+// only use build dependencies.
+//
+// Each uniform block of every *.spv file in a directory becomes a #[repr_std140] Rust struct
+// with a field per member, deriving ShaderBlockDef like CameraBlock. Block members have no
+// uniform locations of their own, so the struct is uploaded through a UniformBlock. So with
+// `layout (std140) uniform Material { vec4 diffuse; float shininess; };` in a shader:
+//
+//   let block = UniformBlock::<Material>::new(gl, binding)?;
+//   block.upload(gl, &Material { diffuse: color.to_std140(), shininess: std140::float(32.) });
+//   shader.bind_uniform(gl, "Material", &block);
+
+use anyhow::{bail, Context, Result};
+use spirv_cross::{glsl, spirv};
+use std::{
+  collections::HashMap,
+  fmt::Write,
+  fs,
+  path::{Path, PathBuf},
+};
+
+// std140 crate type for each GLSL type, with the size and alignment the std140 crate gives
+// it in Rust. Blocks with members of any other type are skipped.
+const TYPES: &[(&str, &str, u32, u32)] = &[
+  ("float", "std140::float", 4, 4),
+  ("int", "std140::int", 4, 4),
+  ("uint", "std140::uint", 4, 4),
+  ("bool", "std140::boolean", 4, 4),
+  ("vec2", "std140::vec2", 8, 8),
+  ("vec3", "std140::vec3", 16, 16),
+  ("vec4", "std140::vec4", 16, 16),
+  ("mat3", "std140::mat3x3", 48, 16),
+  ("mat4", "std140::mat4x4", 64, 16),
+];
+
+// A uniform block found in a shader, with the GLSL type of each member
+struct UniformStruct {
+  name: String,
+  shader: PathBuf,
+  fields: Vec<(String, &'static str)>,
+}
+
+// Write the structs for every SPIR-V module in shader_dir to out_path, which the crate
+// include!s. Call from build.rs.
+pub fn generate_uniform_structs(shader_dir: &Path, out_path: &Path) -> Result<()> {
+  println!("cargo:rerun-if-changed={}", shader_dir.display());
+
+  let mut paths = match fs::read_dir(shader_dir) {
+    Ok(entries) => entries
+      .map(|entry| entry.map(|entry| entry.path()))
+      .collect::<Result<Vec<_>, _>>()?,
+    // No SPIR-V shaders yet, so no structs
+    Err(_) => vec![],
+  };
+  paths.retain(|path| path.extension().map_or(false, |ext| ext == "spv"));
+  paths.sort();
+
+  // Stages of one program often share a block, so each struct is only generated once
+  let mut structs: HashMap<String, UniformStruct> = HashMap::new();
+  for path in &paths {
+    println!("cargo:rerun-if-changed={}", path.display());
+    let bytes = fs::read(path)?;
+    for uniform_struct in uniform_variables(path, &bytes)? {
+      match structs.get(&uniform_struct.name) {
+        Some(existing) if existing.fields != uniform_struct.fields => bail!(
+          "Uniform block {} is declared differently in {} and {}",
+          uniform_struct.name,
+          existing.shader.display(),
+          path.display()
+        ),
+        Some(_) => {}
+        None => {
+          structs.insert(uniform_struct.name.clone(), uniform_struct);
+        }
+      }
+    }
+  }
+
+  let mut structs = structs.into_iter().map(|(_, s)| s).collect::<Vec<_>>();
+  structs.sort_by(|a, b| a.name.cmp(&b.name));
+  let source = structs
+    .iter()
+    .map(rust_struct)
+    .collect::<Vec<_>>()
+    .join("\n");
+  fs::write(out_path, source)?;
+  Ok(())
+}
+
+// Reflect the uniform blocks of a SPIR-V module
+fn uniform_variables(path: &Path, bytes: &[u8]) -> Result<Vec<UniformStruct>> {
+  if bytes.len() % 4 != 0 {
+    bail!(
+      "{} is not SPIR-V, its length isn't a whole number of words",
+      path.display()
+    );
+  }
+  let words = bytes
+    .chunks_exact(4)
+    .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+    .collect::<Vec<_>>();
+
+  let module = spirv::Module::from_words(&words);
+  let mut ast = spirv::Ast::<glsl::Target>::parse(&module)
+    .with_context(|| format!("Failed to parse {}", path.display()))?;
+  let resources = ast.get_shader_resources()?;
+
+  let mut structs = vec![];
+  for block in &resources.uniform_buffers {
+    let member_types = match ast.get_type(block.base_type_id)? {
+      spirv::Type::Struct { member_types, .. } => member_types,
+      _ => continue,
+    };
+
+    let name = ast.get_name(block.base_type_id)?;
+    match block_fields(&ast, block.base_type_id, &member_types) {
+      Ok(fields) => structs.push(UniformStruct {
+        name,
+        shader: path.to_owned(),
+        fields,
+      }),
+      // Leaving out a member would shift the ones after it, so skip the whole block
+      Err(reason) => println!(
+        "cargo:warning=Skipping uniform block {} in {}: {}",
+        name,
+        path.display(),
+        reason
+      ),
+    }
+  }
+  Ok(structs)
+}
+
+// The members of a block, checking that the std140 crate lays each one out at the offset
+// the shader expects. It pads vec3 to 16 bytes, so e.g. a float right after a vec3 won't
+// line up, and the block needs reordering.
+fn block_fields(
+  ast: &spirv::Ast<glsl::Target>,
+  block_type: u32,
+  member_types: &[u32],
+) -> Result<Vec<(String, &'static str)>> {
+  let mut fields = vec![];
+  let mut rust_offset = 0;
+  for (index, member_type) in member_types.iter().enumerate() {
+    let index = index as u32;
+    let member = ast.get_member_name(block_type, index)?;
+    let (glsl, size, align) = match glsl_type(&ast.get_type(*member_type)?) {
+      Some(glsl) => {
+        let (_, _, size, align) = TYPES.iter().find(|(ty, ..)| *ty == glsl).unwrap();
+        (glsl, *size, *align)
+      }
+      None => bail!("{} has a type with no std140 equivalent", member),
+    };
+
+    let offset = ast.get_member_decoration(block_type, index, spirv::Decoration::Offset)?;
+    rust_offset = (rust_offset + align - 1) / align * align;
+    if offset != rust_offset {
+      bail!(
+        "{} is at offset {} in the shader but would be at {} in Rust",
+        member,
+        offset,
+        rust_offset
+      );
+    }
+    rust_offset += size;
+
+    fields.push((member, glsl));
+  }
+  Ok(fields)
+}
+
+// GLSL name of a reflected type, if it's one in TYPES. Arrays aren't supported.
+fn glsl_type(ty: &spirv::Type) -> Option<&'static str> {
+  let (scalar, vecsize, columns, array) = match ty {
+    spirv::Type::Float {
+      vecsize,
+      columns,
+      array,
+    } => ("float", *vecsize, *columns, array),
+    spirv::Type::Int {
+      vecsize,
+      columns,
+      array,
+    } => ("int", *vecsize, *columns, array),
+    spirv::Type::UInt {
+      vecsize,
+      columns,
+      array,
+    } => ("uint", *vecsize, *columns, array),
+    spirv::Type::Boolean {
+      vecsize,
+      columns,
+      array,
+    } => ("bool", *vecsize, *columns, array),
+    _ => return None,
+  };
+  if !array.is_empty() {
+    return None;
+  }
+
+  match (scalar, vecsize, columns) {
+    (_, 1, 1) => Some(scalar),
+    ("float", 2, 1) => Some("vec2"),
+    ("float", 3, 1) => Some("vec3"),
+    ("float", 4, 1) => Some("vec4"),
+    ("float", 3, 3) => Some("mat3"),
+    ("float", 4, 4) => Some("mat4"),
+    _ => None,
+  }
+}
+
+fn rust_struct(uniform_struct: &UniformStruct) -> String {
+  let mut source = String::new();
+  writeln!(
+    source,
+    "// Generated from {}",
+    uniform_struct.shader.display()
+  )
+  .unwrap();
+  writeln!(source, "#[std140::repr_std140]").unwrap();
+  writeln!(source, "#[derive(ShaderBlockDef)]").unwrap();
+  writeln!(source, "pub struct {} {{", uniform_struct.name).unwrap();
+  for (name, glsl) in &uniform_struct.fields {
+    let rust = TYPES.iter().find(|(ty, ..)| ty == glsl).unwrap().1;
+    writeln!(source, "  pub {}: {},", name, rust).unwrap();
+  }
+  writeln!(source, "}}").unwrap();
+  source
+}
@@ -1,6 +1,14 @@
-use crate::{prelude::*, user_inputs::UserInputs};
+use crate::{math::Ray, prelude::*, user_inputs::UserInputs};
 use winit::event::VirtualKeyCode as Key;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMovement {
+  Forward,
+  Backward,
+  Left,
+  Right,
+}
+
 pub struct Camera {
   pub pos: Vec3,
   pub up: Vec3,
@@ -8,11 +16,21 @@ pub struct Camera {
   pub sensitivity: f32,
   pub pitch: f32,
   pub yaw: f32,
-  pub projection: Mat4,
+
+  // Perspective projection parameters. fovy is the vertical field of view in radians.
+  fovy: f32,
+  aspect: f32,
+  near: f32,
+  far: f32,
+
+  // When set, the camera's orientation comes from this quaternion instead of yaw/pitch,
+  // which avoids gimbal lock when looking straight up or down
+  pub orientation: Option<glm::Quat>,
 }
 
 impl Camera {
-  pub fn new(pos: Vec3, projection: Mat4, look_at: Vec3) -> Self {
+  // Perspective camera with clip planes at 0.1 and 100, see with_clip_planes to change them
+  pub fn new(pos: Vec3, look_at: Vec3, fovy: f32, aspect: f32) -> Self {
     let look_dir = glm::normalize(&(pos - look_at));
 
     Camera {
@@ -22,11 +40,66 @@ impl Camera {
       sensitivity: 0.25,
       yaw: f32::atan2(look_dir.x, look_dir.z).to_degrees(),
       pitch: f32::asin(-look_dir.y).to_degrees(),
-      projection,
+      fovy,
+      aspect,
+      near: 0.1,
+      far: 100.,
+      orientation: None,
+    }
+  }
+
+  // Switch to quaternion orientation, starting from the current yaw/pitch
+  pub fn with_quat_orientation(mut self) -> Self {
+    self.orientation = Some(self.orientation());
+    self
+  }
+
+  pub fn orientation(&self) -> glm::Quat {
+    match self.orientation {
+      Some(orientation) => orientation,
+      None => Self::look_rotation(&self.front(), &self.up),
+    }
+  }
+
+  // Rotation that turns the camera's local -Z towards direction. glm::quat_look_at gives
+  // the view rotation, which goes the other way (direction to -Z), so it's inverted.
+  fn look_rotation(direction: &Vec3, up: &Vec3) -> glm::Quat {
+    glm::quat_inverse(&glm::quat_look_at(direction, up))
+  }
+
+  // Rotate the camera by angle (in radians) around a world-space axis
+  pub fn rotate(&mut self, axis: &Vec3, angle: f32) {
+    let rotation = glm::quat_angle_axis(angle, &glm::normalize(axis));
+    self.orientation = Some(glm::quat_normalize(&(rotation * self.orientation())));
+  }
+
+  // Point the camera at target, keeping the world up direction up
+  pub fn look_at(&mut self, target: &Vec3) {
+    let direction = glm::normalize(&(target - self.pos));
+    self.orientation = Some(Self::look_rotation(&direction, &self.up));
+  }
+
+  // The camera's local up direction, which differs from world up when pitched
+  pub fn camera_up(&self) -> Vec3 {
+    match self.orientation {
+      Some(orientation) => glm::quat_rotate_vec3(&orientation, &glm::vec3(0., 1., 0.)),
+
+      // Tilt world up back by the pitch so it stays perpendicular to front. Right comes from
+      // world up here since right() itself depends on camera_up.
+      None => {
+        let front = self.front();
+        let right = glm::normalize(&glm::cross(&front, &self.up));
+        glm::cross(&right, &front)
+      }
     }
   }
 
   pub fn front(&self) -> Vec3 {
+    // Cameras look down -Z in their local space
+    if let Some(orientation) = self.orientation {
+      return glm::quat_rotate_vec3(&orientation, &glm::vec3(0., 0., -1.));
+    }
+
     // TODO: why is -90 needed?
     let yaw = (self.yaw - 90.).to_radians();
     let pitch = self.pitch.to_radians();
@@ -40,34 +113,117 @@ impl Camera {
   pub fn view_matrix(&self) -> Mat4 {
     // Creates a LookAt matrix defined by rotation^T * -translation
     // where fwd = (pos - target), right = up x fwd, rotation = (right, up, fwd), and translation = pos
-    glm::look_at(&self.pos, &(self.pos + self.front()), &self.up)
+    glm::look_at(&self.pos, &(self.pos + self.front()), &self.camera_up())
   }
 
   pub fn right(&self) -> Vec3 {
-    glm::cross(&self.front(), &self.up)
+    glm::cross(&self.front(), &self.camera_up())
+  }
+
+  pub fn projection(&self) -> Mat4 {
+    glm::perspective(self.aspect, self.fovy, self.near, self.far)
+  }
+
+  // Change the aspect ratio of the projection, e.g. when the camera renders into a
+  // viewport that's a different shape than the window
+  pub fn set_aspect_ratio(&mut self, aspect: f32) {
+    self.aspect = aspect;
+  }
+
+  // (fovy, aspect, near, far) of the projection
+  pub fn perspective_params(&self) -> (f32, f32, f32, f32) {
+    (self.fovy, self.aspect, self.near, self.far)
+  }
+
+  // Distance to the near clip plane, e.g. for linearizing depth
+  pub fn near(&self) -> f32 {
+    self.near
+  }
+
+  // Distance to the far clip plane
+  pub fn far(&self) -> f32 {
+    self.far
+  }
+
+  // Move the clip planes, keeping the field of view and aspect ratio. A larger near plane
+  // gives more depth precision to distant objects.
+  pub fn set_clip_planes(&mut self, near: f32, far: f32) {
+    self.near = near;
+    self.far = far;
+  }
+
+  pub fn with_clip_planes(mut self, near: f32, far: f32) -> Self {
+    self.set_clip_planes(near, far);
+    self
   }
 
   pub fn speed(&self, dt: f32) -> f32 {
     self.speed * dt
   }
 
+  // Move the camera at `speed` units per second, so movement doesn't depend on frame rate
+  pub fn process_movement(&mut self, direction: CameraMovement, dt: f32) {
+    let speed = self.speed(dt);
+    self.pos += speed
+      * match direction {
+        CameraMovement::Forward => self.front(),
+        CameraMovement::Backward => -self.front(),
+        CameraMovement::Left => -self.right(),
+        CameraMovement::Right => self.right(),
+      };
+  }
+
+  // Turn the camera by a mouse delta in pixels, scaled by `sensitivity` degrees per pixel
+  pub fn process_mouse(&mut self, dx: f32, dy: f32) {
+    if self.orientation.is_some() {
+      // Yaw around the world up axis and pitch around the camera's right axis
+      let right = self.right();
+      self.rotate(&self.up.clone(), (-dx * self.sensitivity).to_radians());
+      self.rotate(&right, (-dy * self.sensitivity).to_radians());
+    } else {
+      self.yaw += dx * self.sensitivity;
+      self.pitch = (self.pitch - dy * self.sensitivity).clamp(-89., 89.);
+    }
+  }
+
   pub fn update(&mut self, dt: f32, user_inputs: &UserInputs) {
     let (dx, dy) = user_inputs.mouse_delta;
-    self.yaw += (dx as f32) * self.sensitivity;
-    self.pitch = (self.pitch + (-dy as f32) * self.sensitivity).clamp(-89., 89.);
+    self.process_mouse(dx, dy);
 
-    let speed = self.speed(dt);
-    if user_inputs.pressed(Key::W) {
-      self.pos += speed * self.front();
+    let bindings = [
+      (Key::W, CameraMovement::Forward),
+      (Key::S, CameraMovement::Backward),
+      (Key::A, CameraMovement::Left),
+      (Key::D, CameraMovement::Right),
+    ];
+    for (key, direction) in bindings.iter() {
+      if user_inputs.pressed(*key) {
+        self.process_movement(*direction, dt);
+      }
     }
-    if user_inputs.pressed(Key::S) {
-      self.pos += speed * -self.front();
-    }
-    if user_inputs.pressed(Key::A) {
-      self.pos += speed * -self.right();
-    }
-    if user_inputs.pressed(Key::D) {
-      self.pos += speed * self.right();
+  }
+
+  // World-space ray through a point on screen in normalized device coordinates, e.g. for
+  // picking the object under the mouse
+  pub fn screen_ray(&self, mouse_ndc: Vec2) -> Ray {
+    let inverse = glm::inverse(&(self.projection() * self.view_matrix()));
+    let unproject = |z: f32| {
+      let point = inverse * glm::vec4(mouse_ndc.x, mouse_ndc.y, z, 1.);
+      point.xyz() / point.w
+    };
+
+    // Unproject the point on the near and far planes and draw a ray between them
+    let near = unproject(-1.);
+    let far = unproject(1.);
+    Ray::new(near, far - near)
+  }
+
+  // What post-processing passes need to turn the depth buffer back into view space
+  pub fn depth_params(&self) -> DepthParams {
+    DepthParams {
+      near: self.near(),
+      far: self.far(),
+      inverse_projection: glm::inverse(&self.projection()),
     }
   }
 
@@ -75,7 +231,7 @@ impl Camera {
     CameraBlock {
       view_pos: self.pos.to_std140(),
       view: self.view_matrix().to_std140(),
-      projection: self.projection.to_std140(),
+      projection: self.projection().to_std140(),
     }
   }
 }
@@ -87,3 +243,104 @@ pub struct CameraBlock {
   view: std140::mat4x4,
   projection: std140::mat4x4,
 }
+
+// Bind with camera.depth_params() to use DepthParams::DEPTH_DEF's helpers with the
+// camera's clip planes, e.g. as depth_params.near
+#[derive(BindUniform, ShaderTypeDef, Clone)]
+pub struct DepthParams {
+  pub near: f32,
+  pub far: f32,
+  pub inverse_projection: Mat4,
+}
+
+impl DepthParams {
+  // GLSL for undoing a perspective projection, added with ShaderBuilder::with_def. depth
+  // is a depth buffer value in [0, 1] and uv a screen position in [0, 1]. linearize_depth
+  // returns the distance from the camera along its view direction, and
+  // reconstruct_view_pos the view-space position, which looks down -Z.
+  pub const DEPTH_DEF: &'static str = r#"
+float linearize_depth(float depth, float near, float far) {
+  float z = depth * 2.0 - 1.0;
+  return (2.0 * near * far) / (far + near - z * (far - near));
+}
+
+vec3 reconstruct_view_pos(vec2 uv, float depth, mat4 inv_proj) {
+  vec4 view = inv_proj * vec4(vec3(uv, depth) * 2.0 - 1.0, 1.0);
+  return view.xyz / view.w;
+}
+"#;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn camera() -> Camera {
+    Camera::new(
+      glm::vec3(0., 0., 3.),
+      glm::vec3(0., 0., 0.),
+      45f32.to_radians(),
+      1.,
+    )
+  }
+
+  #[test]
+  fn look_at_faces_target() {
+    let mut camera = camera();
+    let target = glm::vec3(2., 1., -4.);
+    camera.look_at(&target);
+    let expected = glm::normalize(&(target - camera.pos));
+    assert!(glm::distance(&camera.front(), &expected) < 1e-5);
+  }
+
+  #[test]
+  fn quat_orientation_keeps_direction() {
+    let camera = camera();
+    let front = camera.front();
+    let camera = camera.with_quat_orientation();
+    assert!(glm::distance(&camera.front(), &front) < 1e-5);
+  }
+
+  #[test]
+  fn screen_ray_through_center_follows_front() {
+    let camera = camera();
+    let ray = camera.screen_ray(glm::vec2(0., 0.));
+    assert!(glm::distance(&ray.direction, &camera.front()) < 1e-5);
+    // The ray starts on the near plane, 0.1 in front of the camera
+    assert!(glm::distance(&ray.origin, &glm::vec3(0., 0., 2.9)) < 1e-4);
+  }
+
+  #[test]
+  fn screen_ray_through_corner_spreads_by_fov() {
+    let camera = camera();
+    let ray = camera.screen_ray(glm::vec2(0., 1.));
+    // The top of the screen is half the vertical field of view above the view direction
+    let angle = glm::angle(&ray.direction, &camera.front());
+    assert!((angle - 22.5f32.to_radians()).abs() < 1e-4);
+    assert!(ray.direction.y > 0.);
+  }
+
+  #[test]
+  fn euler_camera_up_is_perpendicular_to_front() {
+    let mut camera = camera();
+    camera.pitch = 30.;
+    let up = camera.camera_up();
+    assert!(glm::dot(&up, &camera.front()).abs() < 1e-5);
+    assert!((glm::length(&up) - 1.).abs() < 1e-5);
+    assert!(up.y > 0.);
+  }
+
+  #[test]
+  fn clip_planes_feed_the_projection() {
+    let camera = camera().with_clip_planes(0.5, 50.);
+    assert_eq!((camera.near(), camera.far()), (0.5, 50.));
+
+    // The near plane maps to -1 and the far plane to 1 in NDC
+    let depth = |z: f32| {
+      let clip = camera.projection() * glm::vec4(0., 0., -z, 1.);
+      clip.z / clip.w
+    };
+    assert!((depth(0.5) + 1.).abs() < 1e-4);
+    assert!((depth(50.) - 1.).abs() < 1e-4);
+  }
+}
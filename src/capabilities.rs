@@ -0,0 +1,105 @@
+use crate::prelude::*;
+use std::collections::HashSet;
+
+// Which flavor of GL the context implements, which decides the GLSL dialect shaders are
+// compiled as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlVariant {
+  // Desktop OpenGL 3.3 core
+  Desktop330,
+  // Native OpenGL ES 3.0, e.g. on Android or embedded devices
+  Es300,
+  // WebGL2 in the browser, which is ES 3.0 with a few web restrictions
+  WebGl2,
+}
+
+impl GlVariant {
+  // GL_VERSION looks like "4.6.0 NVIDIA 470.57", "OpenGL ES 3.2 Mesa 21.0" or
+  // "WebGL 2.0 (OpenGL ES 3.0 Chromium)"
+  pub unsafe fn detect(gl: &glow::Context) -> Self {
+    let version = gl.get_parameter_string(glow::VERSION);
+    if version.starts_with("WebGL") {
+      GlVariant::WebGl2
+    } else if version.starts_with("OpenGL ES") {
+      GlVariant::Es300
+    } else {
+      GlVariant::Desktop330
+    }
+  }
+
+  pub fn is_es(&self) -> bool {
+    *self != GlVariant::Desktop330
+  }
+}
+
+// Optional GL features that the crate's constructors depend on. Desktop GL 3.3 has most
+// of them built in, but WebGL2 only has some through extensions. Constructors needing a
+// missing feature return an error instead of failing inside the driver. They are detected
+// once when the Context is created, see Context::capabilities.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capabilities {
+  pub variant: GlVariant,
+  // TEXTURE_MAX_ANISOTROPY_EXT, for sharper textures at grazing angles
+  pub has_anisotropic_filtering: bool,
+  pub max_anisotropy: f32,
+  // Half and single precision float textures that can be sampled
+  pub has_float_textures: bool,
+  // Rendering into float textures, e.g. for HDR framebuffers
+  pub has_color_buffer_float: bool,
+  // Depth textures that can be sampled, e.g. for shadow maps
+  pub has_depth_texture: bool,
+  // Shaders given as SPIR-V binaries (GL_ARB_gl_spirv, core in GL 4.6). glow 0.7 has no
+  // bindings for glShaderBinary or glSpecializeShader yet, so shaders are always compiled
+  // from GLSL for now.
+  pub has_gl_spirv: bool,
+}
+
+// WebGL extensions have to be enabled before they can be used, so these are the ones the
+// context enables, see Context::from_webgl2_context
+pub const WEB_EXTENSIONS: &[&str] = &["EXT_color_buffer_float", "EXT_texture_filter_anisotropic"];
+
+impl Capabilities {
+  // `extensions` are the names of the extensions the context supports (and on WebGL, has
+  // enabled)
+  pub unsafe fn detect(gl: &glow::Context, extensions: &HashSet<String>) -> Self {
+    let variant = GlVariant::detect(gl);
+    let has = |names: &[&str]| names.iter().any(|name| extensions.contains(*name));
+
+    let has_anisotropic_filtering = has(&[
+      "EXT_texture_filter_anisotropic",
+      "GL_EXT_texture_filter_anisotropic",
+      "GL_ARB_texture_filter_anisotropic",
+    ]);
+    let max_anisotropy = if has_anisotropic_filtering {
+      gl.get_parameter_i32(glow::MAX_TEXTURE_MAX_ANISOTROPY_EXT) as f32
+    } else {
+      1.
+    };
+
+    let has_gl_spirv = has(&["GL_ARB_gl_spirv"]);
+
+    // ES 3.0 and WebGL2 can sample float and depth textures, but rendering into float
+    // textures is an extension
+    if variant.is_es() {
+      Capabilities {
+        variant,
+        has_anisotropic_filtering,
+        max_anisotropy,
+        has_float_textures: true,
+        has_color_buffer_float: has(&["EXT_color_buffer_float", "GL_EXT_color_buffer_float"]),
+        has_depth_texture: true,
+        has_gl_spirv,
+      }
+    } else {
+      Capabilities {
+        variant,
+        has_anisotropic_filtering,
+        max_anisotropy,
+        has_float_textures: true,
+        has_color_buffer_float: true,
+        has_depth_texture: true,
+        has_gl_spirv,
+      }
+    }
+  }
+}
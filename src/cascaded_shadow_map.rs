@@ -0,0 +1,156 @@
+use crate::{camera::Camera, light::DirLight, prelude::*, shadow_map::ShadowMap, texture::Texture};
+
+// Must match the array size the ShaderTypeDef derive gives Vec fields
+pub const MAX_CASCADES: usize = 4;
+
+// Shadows for a directional light over a large view. The camera frustum is split by
+// distance into cascades, each with its own shadow map, so nearby shadows get more texels
+// than distant ones. Call update whenever the camera or light moves, then render.
+pub struct CascadedShadowMap {
+  cascades: Vec<ShadowMap>,
+  light_spaces: Vec<Mat4>,
+  split_distances: Vec<f32>,
+
+  // Blend between uniform (0) and logarithmic (1) split distances. Logarithmic splits match
+  // how perspective shrinks distant objects, but leave the far cascades very long.
+  pub split_lambda: f32,
+}
+
+impl CascadedShadowMap {
+  pub unsafe fn new(gl: &Context, num_cascades: usize, resolution: u32) -> Result<Self> {
+    if num_cascades == 0 || num_cascades > MAX_CASCADES {
+      bail!(
+        "CascadedShadowMap needs between 1 and {} cascades, got {}",
+        MAX_CASCADES,
+        num_cascades
+      );
+    }
+
+    let cascades = (0..num_cascades)
+      .map(|_| ShadowMap::new(gl, resolution))
+      .collect::<Result<Vec<_>>>()?;
+
+    Ok(CascadedShadowMap {
+      cascades,
+      light_spaces: vec![glm::identity(); num_cascades],
+      split_distances: vec![0.; num_cascades],
+      split_lambda: 0.75,
+    })
+  }
+
+  // Recompute the cascade splits and each cascade's light-space matrix
+  pub fn update(&mut self, light: &DirLight, camera: &Camera) {
+    let (fovy, aspect, near, far) = camera.perspective_params();
+    let view = camera.view_matrix();
+    let n = self.cascades.len();
+
+    let mut cascade_near = near;
+    for i in 0..n {
+      let t = (i + 1) as f32 / n as f32;
+      let log_split = near * (far / near).powf(t);
+      let uniform_split = near + (far - near) * t;
+      let cascade_far = glm::lerp_scalar(uniform_split, log_split, self.split_lambda);
+
+      // Corners of this slice of the frustum in world space, found by unprojecting the
+      // corners of clip space with a projection covering just this slice
+      let projection = glm::perspective(aspect, fovy, cascade_near, cascade_far);
+      let inverse = glm::inverse(&(projection * view));
+      let mut corners = vec![];
+      for &x in &[-1., 1.] {
+        for &y in &[-1., 1.] {
+          for &z in &[-1., 1.] {
+            let corner = inverse * glm::vec4(x, y, z, 1.);
+            corners.push(corner.xyz() / corner.w);
+          }
+        }
+      }
+
+      // Fit a sphere rather than a box so the shadow map doesn't change size (and shimmer)
+      // as the camera turns
+      let center = corners.iter().fold(glm::zero::<Vec3>(), |sum, c| sum + c) / 8.;
+      let radius = corners
+        .iter()
+        .map(|c| glm::distance(c, &center))
+        .fold(0., f32::max);
+
+      // Leave room behind the cascade for objects outside the view that cast shadows into it
+      self.light_spaces[i] = light.shadow_view_proj(center, radius, 0., radius * 4.);
+      self.split_distances[i] = cascade_far;
+      cascade_near = cascade_far;
+    }
+  }
+
+  // Render every cascade by calling draw with each cascade's light-space matrix. Restores
+  // the default framebuffer afterwards, but the caller is responsible for restoring the
+  // viewport.
+  pub unsafe fn render(&self, gl: &Context, draw: impl Fn(&Context, &Mat4)) {
+    for (cascade, light_space) in self.cascades.iter().zip(&self.light_spaces) {
+      cascade.bind(gl);
+      gl.clear(glow::DEPTH_BUFFER_BIT);
+      draw(gl, light_space);
+      cascade.unbind(gl);
+    }
+  }
+
+  pub fn params(&self) -> CascadeParams {
+    let first = &self.cascades[0];
+    CascadeParams {
+      maps: self.cascades.iter().map(|c| c.texture().clone()).collect(),
+      light_spaces: self.light_spaces.clone(),
+      split_distances: self.split_distances.clone(),
+      bias: first.bias,
+      pcf_kernel_size: first.pcf_kernel_size as i32,
+    }
+  }
+
+  pub fn cascades_mut(&mut self) -> &mut [ShadowMap] {
+    &mut self.cascades
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    for cascade in self.cascades {
+      cascade.dispose(gl);
+    }
+  }
+}
+
+#[derive(BindUniform, ShaderTypeDef, Clone)]
+pub struct CascadeParams {
+  pub maps: Vec<Texture>,
+  pub light_spaces: Vec<Mat4>,
+  // Distance from the camera where each cascade ends
+  pub split_distances: Vec<f32>,
+  pub bias: f32,
+  pub pcf_kernel_size: i32,
+}
+
+impl CascadeParams {
+  // GLSL to pick the cascade covering a fragment and look up its shadow. view_depth is the
+  // fragment's distance along the camera's view direction. Sampler arrays can only be
+  // indexed by constants, hence the unrolled branches. Add it with ShaderBuilder::with_def
+  // after ShadowParams::SHADOW_FACTOR_DEF, whose pcf_shadow it calls.
+  pub const CASCADE_SHADOW_FACTOR_DEF: &'static str = r#"
+float cascade_shadow_factor(
+  CascadeParams csm, vec3 world_pos, float view_depth, vec3 normal, vec3 light_dir
+) {
+  int cascade = csm.maps_len - 1;
+  for (int i = 0; i < csm.maps_len; i++) {
+    if (view_depth < csm.split_distances[i]) {
+      cascade = i;
+      break;
+    }
+  }
+
+  mat4 light_space = csm.light_spaces[cascade];
+  if (cascade == 0) {
+    return pcf_shadow(csm.maps[0], light_space, csm.bias, csm.pcf_kernel_size, world_pos, normal, light_dir);
+  } else if (cascade == 1) {
+    return pcf_shadow(csm.maps[1], light_space, csm.bias, csm.pcf_kernel_size, world_pos, normal, light_dir);
+  } else if (cascade == 2) {
+    return pcf_shadow(csm.maps[2], light_space, csm.bias, csm.pcf_kernel_size, world_pos, normal, light_dir);
+  } else {
+    return pcf_shadow(csm.maps[3], light_space, csm.bias, csm.pcf_kernel_size, world_pos, normal, light_dir);
+  }
+}
+"#;
+}
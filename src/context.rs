@@ -0,0 +1,60 @@
+use crate::capabilities::Capabilities;
+#[cfg(target_arch = "wasm32")]
+use crate::capabilities::WEB_EXTENSIONS;
+#[cfg(not(target_arch = "wasm32"))]
+use glow::HasContext;
+use std::{collections::HashSet, ops::Deref};
+
+// A glow context along with what it supports, detected once when it's created. Every GL
+// call goes straight through to glow, since this derefs to glow::Context.
+pub struct Context {
+  gl: glow::Context,
+  capabilities: Capabilities,
+}
+
+impl Context {
+  // Wrap a native context, which has to be current so its capabilities can be queried
+  #[cfg(not(target_arch = "wasm32"))]
+  pub unsafe fn new(gl: glow::Context) -> Self {
+    let extensions = (0..gl.get_parameter_i32(glow::NUM_EXTENSIONS) as u32)
+      .map(|index| gl.get_parameter_indexed_string(glow::EXTENSIONS, index))
+      .collect();
+    Self::with_extensions(gl, &extensions)
+  }
+
+  // glow can't list a WebGL context's extensions, so they're read from the browser's
+  // context before it's wrapped. Extensions the crate uses are enabled here, since WebGL
+  // ignores them until they are.
+  #[cfg(target_arch = "wasm32")]
+  pub fn from_webgl2_context(context: web_sys::WebGl2RenderingContext) -> Self {
+    let supported = context
+      .get_supported_extensions()
+      .map(|names| names.iter().filter_map(|name| name.as_string()).collect())
+      .unwrap_or_else(HashSet::new);
+    let extensions = WEB_EXTENSIONS
+      .iter()
+      .filter(|name| supported.contains(**name))
+      .filter(|name| matches!(context.get_extension(name), Ok(Some(_))))
+      .map(|name| name.to_string())
+      .collect();
+    let gl = glow::Context::from_webgl2_context(context);
+    unsafe { Self::with_extensions(gl, &extensions) }
+  }
+
+  unsafe fn with_extensions(gl: glow::Context, extensions: &HashSet<String>) -> Self {
+    let capabilities = Capabilities::detect(&gl, extensions);
+    Context { gl, capabilities }
+  }
+
+  pub fn capabilities(&self) -> Capabilities {
+    self.capabilities
+  }
+}
+
+impl Deref for Context {
+  type Target = glow::Context;
+
+  fn deref(&self) -> &glow::Context {
+    &self.gl
+  }
+}
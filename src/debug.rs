@@ -0,0 +1,84 @@
+use crate::prelude::*;
+
+// Ask the driver to report errors and performance warnings as they happen. Needs
+// GL 4.3 or KHR_debug, which WebGL doesn't expose, so this is native-only.
+//
+// The callback receives (source, type, id, severity, message).
+#[cfg(not(target_arch = "wasm32"))]
+pub unsafe fn enable_debug_output(
+  gl: &Context,
+  callback: impl Fn(u32, u32, u32, u32, &str) + 'static,
+) {
+  gl.enable(glow::DEBUG_OUTPUT);
+
+  // Synchronous output means the callback runs inside the offending GL call, so a
+  // breakpoint in the callback has a useful stack trace
+  gl.enable(glow::DEBUG_OUTPUT_SYNCHRONOUS);
+
+  gl.debug_message_callback(move |source, ty, id, severity, message| {
+    callback(source, ty, id, severity, message)
+  });
+}
+
+// Forward every debug message to the log crate at a level matching its severity
+#[cfg(not(target_arch = "wasm32"))]
+pub unsafe fn log_debug_output(gl: &Context) {
+  enable_debug_output(gl, |_source, _ty, id, severity, message| {
+    let level = match severity {
+      glow::DEBUG_SEVERITY_HIGH => log::Level::Error,
+      glow::DEBUG_SEVERITY_MEDIUM | glow::DEBUG_SEVERITY_LOW => log::Level::Warn,
+      _ => log::Level::Info,
+    };
+    log::log!(level, "GL debug message ({}): {}", id, message);
+  });
+}
+
+pub fn error_name(error: u32) -> &'static str {
+  match error {
+    glow::INVALID_ENUM => "GL_INVALID_ENUM",
+    glow::INVALID_VALUE => "GL_INVALID_VALUE",
+    glow::INVALID_OPERATION => "GL_INVALID_OPERATION",
+    glow::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+    glow::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+    glow::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
+    glow::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
+    _ => "unknown GL error",
+  }
+}
+
+// Log any errors raised by GL calls since the last check, along with the caller's
+// location, and return whether there were any. GL keeps one flag per kind of error, so
+// it's polled until it reports none. Works everywhere, unlike enable_debug_output, but
+// only says where an error was noticed rather than which call caused it.
+#[track_caller]
+pub unsafe fn check_error(gl: &Context) -> bool {
+  let location = std::panic::Location::caller();
+  let mut found = false;
+  loop {
+    let error = gl.get_error();
+    if error == glow::NO_ERROR {
+      break;
+    }
+    log::error!(
+      "GL error at {}: {} ({:#x})",
+      location,
+      error_name(error),
+      error
+    );
+    found = true;
+  }
+  found
+}
+
+// Evaluate a GL call and, in debug builds, check for errors right after it, e.g.
+// `gl_check!(gl, gl.draw_arrays(glow::TRIANGLES, 0, 3))`. Release builds skip the check,
+// since glGetError stalls the pipeline.
+#[macro_export]
+macro_rules! gl_check {
+  ($gl:expr, $call:expr) => {{
+    let result = $call;
+    #[cfg(debug_assertions)]
+    $crate::debug::check_error($gl);
+    result
+  }};
+}
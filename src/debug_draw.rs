@@ -0,0 +1,173 @@
+use crate::{camera::Camera, math::Aabb, prelude::*, shader::Shader};
+use std::{f32::consts::PI, mem::size_of};
+
+#[repr(C)]
+#[derive(Clone, Debug)]
+struct LineVertex {
+  position: Vec3,
+  color: Vec3,
+}
+
+// Immediate-mode wireframe shapes for debugging. Shapes are queued as line segments during
+// the frame, then drawn all at once by flush with a single unindexed GL_LINES draw.
+pub struct DebugDraw {
+  vertices: Vec<LineVertex>,
+  shader: Shader,
+  vertex_array: GlVertexArray,
+  vertex_buffer: GlBuffer,
+}
+
+impl DebugDraw {
+  pub unsafe fn new(gl: &Context) -> Result<Self> {
+    let shader = crate::shader!(
+      gl,
+      "../assets/shaders/debug_draw.vert",
+      "../assets/shaders/debug_draw.frag"
+    )?;
+
+    let vertex_array = gl.create_vertex_array().map_err(Error::msg)?;
+    gl.bind_vertex_array(Some(vertex_array));
+
+    let vertex_buffer = gl.create_buffer().map_err(Error::msg)?;
+    gl.bind_buffer(glow::ARRAY_BUFFER, Some(vertex_buffer));
+
+    let size_f32 = size_of::<f32>() as i32;
+    let stride = size_of::<LineVertex>() as i32;
+    for (i, offset) in [0, 3].iter().enumerate() {
+      gl.enable_vertex_attrib_array(i as u32);
+      gl.vertex_attrib_pointer_f32(i as u32, 3, glow::FLOAT, false, stride, offset * size_f32);
+    }
+
+    gl.bind_vertex_array(None);
+
+    Ok(DebugDraw {
+      vertices: vec![],
+      shader,
+      vertex_array,
+      vertex_buffer,
+    })
+  }
+
+  pub fn line(&mut self, start: Vec3, end: Vec3, color: Vec3) {
+    self.vertices.push(LineVertex {
+      position: start,
+      color,
+    });
+    self.vertices.push(LineVertex {
+      position: end,
+      color,
+    });
+  }
+
+  // The twelve edges of the box
+  pub fn aabb(&mut self, aabb: &Aabb, color: Vec3) {
+    let corner = |i: usize| {
+      glm::vec3(
+        if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+        if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+        if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+      )
+    };
+
+    // Corners are numbered by which axes are at their max, so each edge joins two corners
+    // differing in exactly one bit
+    for i in 0..8 {
+      for bit in &[1, 2, 4] {
+        if i & bit == 0 {
+          self.line(corner(i), corner(i | bit), color);
+        }
+      }
+    }
+  }
+
+  // Three circles, one around each axis
+  pub fn sphere(&mut self, center: Vec3, radius: f32, subdivisions: u32, color: Vec3) {
+    let subdivisions = subdivisions.max(3);
+    let point = |axis: usize, i: u32| {
+      let angle = 2. * PI * i as f32 / subdivisions as f32;
+      let (sin, cos) = angle.sin_cos();
+      let offset = match axis {
+        0 => glm::vec3(0., cos, sin),
+        1 => glm::vec3(cos, 0., sin),
+        _ => glm::vec3(cos, sin, 0.),
+      };
+      center + offset * radius
+    };
+
+    for axis in 0..3 {
+      for i in 0..subdivisions {
+        self.line(point(axis, i), point(axis, i + 1), color);
+      }
+    }
+  }
+
+  pub fn ray(&mut self, origin: Vec3, direction: Vec3, length: f32, color: Vec3) {
+    self.line(origin, origin + glm::normalize(&direction) * length, color);
+  }
+
+  // X, Y and Z axes from origin in red, green and blue
+  pub fn axes(&mut self, origin: Vec3, length: f32) {
+    for i in 0..3 {
+      let mut axis = Vec3::zeros();
+      axis[i] = 1.;
+      self.line(origin, origin + axis * length, axis);
+    }
+  }
+
+  // Draw the world axes at the origin right away, along with anything already queued
+  pub unsafe fn draw_axes(&mut self, gl: &Context, camera: &Camera, length: f32) {
+    self.axes(Vec3::zeros(), length);
+    self.flush(gl, camera);
+  }
+
+  // Lines along the cell edges of a Geometry::Grid with the same size and subdivisions,
+  // to show the ground and its scale
+  pub fn grid(&mut self, size: f32, subdivisions: u32, color: Vec3) {
+    let subdivisions = subdivisions.max(1);
+    let half = size / 2.;
+    for i in 0..=subdivisions {
+      let offset = i as f32 / subdivisions as f32 * size - half;
+      self.line(
+        glm::vec3(offset, 0., -half),
+        glm::vec3(offset, 0., half),
+        color,
+      );
+      self.line(
+        glm::vec3(-half, 0., offset),
+        glm::vec3(half, 0., offset),
+        color,
+      );
+    }
+  }
+
+  // Draw everything queued since the last flush, then clear the queue
+  pub unsafe fn flush(&mut self, gl: &Context, camera: &Camera) {
+    if self.vertices.is_empty() {
+      return;
+    }
+
+    gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vertex_buffer));
+    let (_, bytes, _) = self.vertices.align_to::<u8>();
+    gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, bytes, glow::STREAM_DRAW);
+    gl.bind_buffer(glow::ARRAY_BUFFER, None);
+
+    let mut shader = self.shader.activate(gl);
+    shader.bind_uniform(
+      gl,
+      "view_projection",
+      &(camera.projection() * camera.view_matrix()),
+    );
+
+    gl.bind_vertex_array(Some(self.vertex_array));
+    gl.draw_arrays(glow::LINES, 0, self.vertices.len() as i32);
+    gl.bind_vertex_array(None);
+
+    self.vertices.clear();
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.shader.dispose(gl);
+    gl.delete_vertex_array(self.vertex_array);
+    gl.delete_buffer(self.vertex_buffer);
+  }
+}
@@ -0,0 +1,71 @@
+use crate::{
+  camera::{Camera, DepthParams},
+  post_process::PostProcess,
+  prelude::*,
+  texture::Texture,
+};
+
+// Blurs the scene based on each pixel's distance from the focal plane, like a camera lens.
+// Needs the scene's depth as a texture, e.g. Framebuffer::with_color_and_depth's.
+pub struct DepthOfField {
+  pass: PostProcess,
+
+  // Distance from the camera that's perfectly sharp
+  pub focal_distance: f32,
+  // How far from the focal plane the blur reaches its maximum
+  pub focal_range: f32,
+  // Maximum blur radius in pixels, like a lens's aperture size
+  pub aperture: f32,
+
+  // Clip planes of the camera's projection, needed to turn depth back into distance
+  pub near: f32,
+  pub far: f32,
+}
+
+impl DepthOfField {
+  pub async unsafe fn new(gl: &Context, near: f32, far: f32) -> Result<Self> {
+    Ok(DepthOfField {
+      pass: PostProcess::with_defs(
+        gl,
+        "assets/shaders/depth_of_field.frag",
+        &[DepthParams::DEPTH_DEF],
+      )
+      .await?,
+      focal_distance: 5.,
+      focal_range: 3.,
+      aperture: 8.,
+      near,
+      far,
+    })
+  }
+
+  // Pick up the camera's clip planes, e.g. after Camera::set_clip_planes
+  pub fn match_camera(&mut self, camera: &Camera) {
+    self.near = camera.near();
+    self.far = camera.far();
+  }
+
+  pub unsafe fn draw(
+    &self,
+    gl: &Context,
+    color: &Texture,
+    depth: &Texture,
+    width: u32,
+    height: u32,
+  ) {
+    self.pass.draw(gl, color, |gl, shader| {
+      let inverse_size = glm::vec2(1. / width as f32, 1. / height as f32);
+      shader.bind_uniform(gl, "depthTexture", depth);
+      shader.bind_uniform(gl, "inverse_screen_size", &inverse_size);
+      shader.bind_uniform(gl, "focal_distance", &self.focal_distance);
+      shader.bind_uniform(gl, "focal_range", &self.focal_range);
+      shader.bind_uniform(gl, "aperture", &self.aperture);
+      shader.bind_uniform(gl, "near", &self.near);
+      shader.bind_uniform(gl, "far", &self.far);
+    });
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.pass.dispose(gl);
+  }
+}
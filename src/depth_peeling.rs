@@ -0,0 +1,136 @@
+use crate::{
+  camera::Camera,
+  framebuffer::Framebuffer,
+  post_process::PostProcess,
+  prelude::*,
+  render_state::RenderState,
+  texture::{Texture, TextureBuilder},
+};
+
+// Order-independent transparency by depth peeling. The transparent geometry is drawn once
+// per layer, and each pass keeps only the nearest fragment behind the previous pass's
+// depth. The layers are then blended over the screen from back to front, so objects don't
+// need to be sorted. Passes alternate between two depth textures: one is written by the
+// current pass while the other holds the depth being peeled away.
+pub struct DepthPeeling {
+  layers: Vec<Framebuffer>,
+  depths: [Texture; 2],
+  composite: PostProcess,
+  width: u32,
+  height: u32,
+}
+
+impl DepthPeeling {
+  pub async unsafe fn new(gl: &Context, width: u32, height: u32, layers: u32) -> Result<Self> {
+    if layers == 0 {
+      bail!("Depth peeling needs at least one layer");
+    }
+
+    let depth_texture = || {
+      TextureBuilder::new(gl)
+        .with_format(glow::DEPTH_COMPONENT)
+        .with_tex_parameter(glow::TEXTURE_MIN_FILTER, glow::NEAREST)
+        .with_tex_parameter(glow::TEXTURE_MAG_FILTER, glow::NEAREST)
+        .with_tex_parameter(glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE)
+        .with_tex_parameter(glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE)
+        .render_texture(width, height)
+    };
+    let depths = [depth_texture()?, depth_texture()?];
+
+    // Each layer keeps its own color so they can be composited in reverse order, and
+    // borrows whichever depth texture its pass writes into
+    let layers = (0..layers as usize)
+      .map(|i| {
+        let framebuffer = Framebuffer::with_color(gl, width, height)?;
+        framebuffer.bind(gl);
+        framebuffer.attach_texture(gl, glow::DEPTH_ATTACHMENT, &depths[i % 2]);
+        framebuffer.check_completeness(gl)?;
+        framebuffer.unbind(gl);
+        Ok(framebuffer)
+      })
+      .collect::<Result<Vec<_>>>()?;
+
+    Ok(DepthPeeling {
+      layers,
+      depths,
+      composite: PostProcess::new(gl, "assets/shaders/depth_peel_composite.frag").await?,
+      width,
+      height,
+    })
+  }
+
+  pub fn layers(&self) -> usize {
+    self.layers.len()
+  }
+
+  // Peel the geometry drawn by draw_fn, then blend the layers into the default framebuffer.
+  // draw_fn is called once per layer. Its shaders should bind the given PeelParams as
+  // `peel`, and discard fragments where depth_peeled(peel, gl_FragCoord) is true. Opaque
+  // geometry isn't peeled, so draw it to the screen beforehand.
+  pub unsafe fn render(
+    &self,
+    gl: &Context,
+    mut draw_fn: impl FnMut(&Context, &Camera, &PeelParams),
+    camera: &Camera,
+  ) {
+    gl.viewport(0, 0, self.width as i32, self.height as i32);
+
+    // Each pass needs the nearest remaining fragment as-is, and back faces count as layers
+    RenderState {
+      blend: None,
+      cull_face: None,
+      ..Default::default()
+    }
+    .apply(gl);
+    for (i, layer) in self.layers.iter().enumerate() {
+      layer.bind(gl);
+      gl.clear_color(0., 0., 0., 0.);
+      gl.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
+
+      let params = PeelParams {
+        prev_depth: self.depths[(i + 1) % 2].clone(),
+        first_layer: i == 0,
+      };
+      draw_fn(gl, camera, &params);
+    }
+    self.layers[0].unbind(gl);
+    RenderState::default().apply(gl);
+
+    for layer in self.layers.iter().rev() {
+      self
+        .composite
+        .draw(gl, layer.color.as_ref().unwrap(), |_, _| {});
+    }
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    for layer in self.layers {
+      layer.dispose(gl);
+    }
+    let [a, b] = self.depths;
+    a.dispose(gl);
+    b.dispose(gl);
+    self.composite.dispose(gl);
+  }
+}
+
+#[derive(BindUniform, ShaderTypeDef, Clone)]
+pub struct PeelParams {
+  pub prev_depth: Texture,
+  pub first_layer: bool,
+}
+
+impl PeelParams {
+  // GLSL for the peel test, added with ShaderBuilder::with_def. Fragments at or in front
+  // of the previous layer were already peeled, so the shader discards them. The
+  // definitions also go into vertex shaders, so gl_FragCoord is passed in.
+  pub const PEEL_DEF: &'static str = r#"
+bool depth_peeled(PeelParams peel, vec4 frag_coord) {
+  if (peel.first_layer) {
+    return false;
+  }
+  float prev_depth = texelFetch(peel.prev_depth, ivec2(frag_coord.xy), 0).r;
+  return frag_coord.z <= prev_depth;
+}
+"#;
+}
@@ -0,0 +1,440 @@
+use crate::{
+  prelude::*,
+  texture::{self, Texture, TextureBuilder},
+};
+use image::{imageops, RgbaImage};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+// An offscreen render target. Anything drawn while the framebuffer is bound ends up in
+// its attachments, which can then be sampled as regular textures in a later pass.
+pub struct Framebuffer {
+  id: GlFramebuffer,
+  pub color: Option<Texture>,
+  pub depth: Option<Texture>,
+  pub width: u32,
+  pub height: u32,
+  renderbuffers: Vec<GlRenderbuffer>,
+}
+
+impl Framebuffer {
+  pub unsafe fn with_color_and_depth(gl: &Context, width: u32, height: u32) -> Result<Self> {
+    let id = gl.create_framebuffer().map_err(Error::msg)?;
+    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(id));
+
+    // Color attachment is a 2D image that contains output of rendering
+    let color = Self::color_texture(gl, width, height, glow::RGBA)?;
+    gl.framebuffer_texture_2d(
+      glow::FRAMEBUFFER,
+      glow::COLOR_ATTACHMENT0,
+      glow::TEXTURE_2D,
+      Some(color.texture),
+      0,
+    );
+
+    // Depth and stencil share a single texture. Depth textures can't be filtered or
+    // mipmapped, so sample them with NEAREST.
+    let depth = TextureBuilder::new(gl)
+      .with_format(glow::DEPTH_STENCIL)
+      .with_tex_parameter(glow::TEXTURE_MIN_FILTER, glow::NEAREST)
+      .with_tex_parameter(glow::TEXTURE_MAG_FILTER, glow::NEAREST)
+      .with_tex_parameter(glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE)
+      .with_tex_parameter(glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE)
+      .render_texture(width, height)?;
+    gl.framebuffer_texture_2d(
+      glow::FRAMEBUFFER,
+      glow::DEPTH_STENCIL_ATTACHMENT,
+      glow::TEXTURE_2D,
+      Some(depth.texture),
+      0,
+    );
+
+    let framebuffer = Framebuffer {
+      id,
+      color: Some(color),
+      depth: Some(depth),
+      width,
+      height,
+      renderbuffers: vec![],
+    };
+    framebuffer.check_completeness(gl)?;
+
+    gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+
+    Ok(framebuffer)
+  }
+
+  // Framebuffer for passes that don't need depth testing, e.g. full-screen post-processing
+  pub unsafe fn with_color(gl: &Context, width: u32, height: u32) -> Result<Self> {
+    Self::with_color_format(gl, width, height, glow::RGBA)
+  }
+
+  // Like with_color, but with a specific internal format, e.g. RGBA16F for HDR colors
+  pub unsafe fn with_color_format(
+    gl: &Context,
+    width: u32,
+    height: u32,
+    internal_format: u32,
+  ) -> Result<Self> {
+    let mut framebuffer = Self::empty(gl, width, height)?;
+    let color = Self::color_texture(gl, width, height, internal_format)?;
+    framebuffer.attach_texture(gl, glow::COLOR_ATTACHMENT0, &color);
+    framebuffer.color = Some(color);
+    framebuffer.check_completeness(gl)?;
+    framebuffer.unbind(gl);
+    Ok(framebuffer)
+  }
+
+  unsafe fn color_texture(
+    gl: &Context,
+    width: u32,
+    height: u32,
+    internal_format: u32,
+  ) -> Result<Texture> {
+    let (format, data_type) = texture::pixel_format(internal_format);
+    if data_type == glow::FLOAT && !gl.capabilities().has_color_buffer_float {
+      bail!("Rendering to floating point textures is not supported by this context");
+    }
+
+    TextureBuilder::new(gl)
+      .with_format(format)
+      .with_internal_format(internal_format)
+      .with_tex_parameter(glow::TEXTURE_MIN_FILTER, glow::LINEAR)
+      .with_tex_parameter(glow::TEXTURE_MAG_FILTER, glow::LINEAR)
+      .with_tex_parameter(glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE)
+      .with_tex_parameter(glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE)
+      .render_texture(width, height)
+  }
+
+  // Make a framebuffer with no attachments. It's left bound so the caller can attach
+  // render targets, and then check completeness once everything is attached.
+  pub unsafe fn empty(gl: &Context, width: u32, height: u32) -> Result<Self> {
+    let id = gl.create_framebuffer().map_err(Error::msg)?;
+    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(id));
+    Ok(Framebuffer {
+      id,
+      color: None,
+      depth: None,
+      width,
+      height,
+      renderbuffers: vec![],
+    })
+  }
+
+  // Attach a texture to the currently bound framebuffer. The framebuffer doesn't take
+  // ownership of the texture.
+  pub unsafe fn attach_texture(&self, gl: &Context, attachment: u32, texture: &Texture) {
+    gl.framebuffer_texture_2d(
+      glow::FRAMEBUFFER,
+      attachment,
+      glow::TEXTURE_2D,
+      Some(texture.texture),
+      0,
+    );
+  }
+
+  // Attach a renderbuffer to the currently bound framebuffer. Renderbuffers are for
+  // attachments we never sample from, and are owned by the framebuffer.
+  pub unsafe fn attach_renderbuffer(
+    &mut self,
+    gl: &Context,
+    attachment: u32,
+    internal_format: u32,
+  ) -> Result<()> {
+    let rbo = gl.create_renderbuffer().map_err(Error::msg)?;
+    gl.bind_renderbuffer(glow::RENDERBUFFER, Some(rbo));
+    gl.renderbuffer_storage(
+      glow::RENDERBUFFER,
+      internal_format,
+      self.width as i32,
+      self.height as i32,
+    );
+    gl.bind_renderbuffer(glow::RENDERBUFFER, None);
+    gl.framebuffer_renderbuffer(glow::FRAMEBUFFER, attachment, glow::RENDERBUFFER, Some(rbo));
+    self.renderbuffers.push(rbo);
+    Ok(())
+  }
+
+  // Multisampled textures can't be sampled by regular shaders, so this framebuffer renders
+  // into renderbuffers and has no color texture. Use Framebuffer::resolve_to to copy the
+  // result into a regular framebuffer before sampling it.
+  pub unsafe fn multisampled(gl: &Context, width: u32, height: u32, samples: u32) -> Result<Self> {
+    let max_samples = gl.get_parameter_i32(glow::MAX_SAMPLES) as u32;
+    let samples = samples.min(max_samples) as i32;
+
+    let id = gl.create_framebuffer().map_err(Error::msg)?;
+    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(id));
+
+    let mut renderbuffers = vec![];
+    for (internal_format, attachment) in &[
+      (glow::RGBA8, glow::COLOR_ATTACHMENT0),
+      (glow::DEPTH24_STENCIL8, glow::DEPTH_STENCIL_ATTACHMENT),
+    ] {
+      let rbo = gl.create_renderbuffer().map_err(Error::msg)?;
+      gl.bind_renderbuffer(glow::RENDERBUFFER, Some(rbo));
+      gl.renderbuffer_storage_multisample(
+        glow::RENDERBUFFER,
+        samples,
+        *internal_format,
+        width as i32,
+        height as i32,
+      );
+      gl.framebuffer_renderbuffer(
+        glow::FRAMEBUFFER,
+        *attachment,
+        glow::RENDERBUFFER,
+        Some(rbo),
+      );
+      renderbuffers.push(rbo);
+    }
+    gl.bind_renderbuffer(glow::RENDERBUFFER, None);
+
+    let framebuffer = Framebuffer {
+      id,
+      color: None,
+      depth: None,
+      width,
+      height,
+      renderbuffers,
+    };
+    framebuffer.check_completeness(gl)?;
+
+    gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+
+    Ok(framebuffer)
+  }
+
+  // Copy (and for multisampled framebuffers, resolve) the color buffer into target.
+  // Afterwards target's color texture can be bound like any other texture.
+  pub unsafe fn resolve_to(&self, gl: &Context, target: &Framebuffer) {
+    gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(self.id));
+    gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(target.id));
+    gl.blit_framebuffer(
+      0,
+      0,
+      self.width as i32,
+      self.height as i32,
+      0,
+      0,
+      target.width as i32,
+      target.height as i32,
+      glow::COLOR_BUFFER_BIT,
+      glow::NEAREST,
+    );
+    gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+  }
+
+  // Redirect subsequent draw calls into this framebuffer
+  pub unsafe fn bind(&self, gl: &Context) {
+    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.id));
+  }
+
+  // Go back to drawing to the default framebuffer (i.e. the screen)
+  pub unsafe fn unbind(&self, gl: &Context) {
+    gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+  }
+
+  // Fail if the framebuffer is missing attachments or they have mismatched sizes/formats.
+  // Leaves this framebuffer bound.
+  pub unsafe fn check_completeness(&self, gl: &Context) -> Result<()> {
+    self.bind(gl);
+    let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
+    if status != glow::FRAMEBUFFER_COMPLETE {
+      bail!("Framebuffer is not complete (status {:#x})", status);
+    }
+    Ok(())
+  }
+
+  pub fn id(&self) -> GlFramebuffer {
+    self.id
+  }
+
+  // RGBA bytes of the color attachment, with rows starting at the bottom as in GL. Leaves
+  // the default framebuffer bound. GLES can only read float attachments as floats, so
+  // those are read as such and then clamped to [0, 1] and converted to bytes.
+  pub unsafe fn read_pixels(&self, gl: &Context) -> Vec<u8> {
+    self.bind(gl);
+    let pixels = match self.color.as_ref().map(Texture::data_type) {
+      Some(glow::FLOAT) => read_rgba_f32(gl, self.width, self.height)
+        .into_iter()
+        .map(|value| (value.clamp(0., 1.) * 255.).round() as u8)
+        .collect(),
+      _ => read_rgba(gl, self.width, self.height),
+    };
+    self.unbind(gl);
+    pixels
+  }
+
+  // Write the color attachment to a PNG, e.g. to capture a render. Native-only since it
+  // writes to the filesystem.
+  #[cfg(not(target_arch = "wasm32"))]
+  pub unsafe fn save_png(&self, gl: &Context, path: impl AsRef<Path>) -> Result<()> {
+    let image = RgbaImage::from_raw(self.width, self.height, self.read_pixels(gl)).unwrap();
+    imageops::flip_vertical(&image).save_with_format(path, image::ImageFormat::Png)?;
+    Ok(())
+  }
+
+  // Free the framebuffer along with the attachments it owns
+  pub unsafe fn dispose(self, gl: &Context) {
+    gl.delete_framebuffer(self.id);
+    if let Some(color) = self.color {
+      color.dispose(gl);
+    }
+    if let Some(depth) = self.depth {
+      depth.dispose(gl);
+    }
+    for rbo in self.renderbuffers {
+      gl.delete_renderbuffer(rbo);
+    }
+  }
+}
+
+// Pair of framebuffers for multi-pass effects like blurring, where each pass reads the
+// output of the previous pass and writes into the other framebuffer.
+pub struct PingPong {
+  fbs: [Framebuffer; 2],
+  current: usize,
+}
+
+impl PingPong {
+  pub unsafe fn new(gl: &Context, width: u32, height: u32) -> Result<Self> {
+    Self::with_color_format(gl, width, height, glow::RGBA)
+  }
+
+  pub unsafe fn with_color_format(
+    gl: &Context,
+    width: u32,
+    height: u32,
+    internal_format: u32,
+  ) -> Result<Self> {
+    Ok(PingPong {
+      fbs: [
+        Framebuffer::with_color_format(gl, width, height, internal_format)?,
+        Framebuffer::with_color_format(gl, width, height, internal_format)?,
+      ],
+      current: 1,
+    })
+  }
+
+  // Swap the framebuffers and bind the one to write into. Returns it along with the
+  // texture written by the previous pass.
+  pub unsafe fn next(&mut self, gl: &Context) -> (&Framebuffer, &Texture) {
+    let read = self.current;
+    self.current = 1 - self.current;
+
+    let write = &self.fbs[self.current];
+    write.bind(gl);
+    (write, self.fbs[read].color.as_ref().unwrap())
+  }
+
+  // Texture written by the most recent pass
+  pub fn result(&self) -> &Texture {
+    self.fbs[self.current].color.as_ref().unwrap()
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    let [a, b] = self.fbs;
+    a.dispose(gl);
+    b.dispose(gl);
+  }
+}
+
+// Which attachments a ResizableFramebuffer is built with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramebufferAttachments {
+  ColorAndDepth,
+  Color,
+  Multisampled { samples: u32 },
+}
+
+// Framebuffer that tracks the window size. GL attachments can't change size, so resizing
+// throws away the old framebuffer and builds a new one with the same attachments.
+pub struct ResizableFramebuffer {
+  framebuffer: Framebuffer,
+  attachments: FramebufferAttachments,
+}
+
+impl ResizableFramebuffer {
+  pub unsafe fn new(
+    gl: &Context,
+    attachments: FramebufferAttachments,
+    width: u32,
+    height: u32,
+  ) -> Result<Self> {
+    Ok(ResizableFramebuffer {
+      framebuffer: Self::build(gl, attachments, width, height)?,
+      attachments,
+    })
+  }
+
+  unsafe fn build(
+    gl: &Context,
+    attachments: FramebufferAttachments,
+    width: u32,
+    height: u32,
+  ) -> Result<Framebuffer> {
+    match attachments {
+      FramebufferAttachments::ColorAndDepth => Framebuffer::with_color_and_depth(gl, width, height),
+      FramebufferAttachments::Color => Framebuffer::with_color(gl, width, height),
+      FramebufferAttachments::Multisampled { samples } => {
+        Framebuffer::multisampled(gl, width, height, samples)
+      }
+    }
+  }
+
+  // Textures previously borrowed from the framebuffer are deleted by this, so fetch them
+  // again afterwards
+  pub unsafe fn resize(&mut self, gl: &Context, width: u32, height: u32) -> Result<()> {
+    if width == self.framebuffer.width && height == self.framebuffer.height {
+      return Ok(());
+    }
+
+    let framebuffer = Self::build(gl, self.attachments, width, height)?;
+    std::mem::replace(&mut self.framebuffer, framebuffer).dispose(gl);
+    Ok(())
+  }
+
+  pub fn framebuffer(&self) -> &Framebuffer {
+    &self.framebuffer
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.framebuffer.dispose(gl);
+  }
+}
+
+// Read the color of the bound framebuffer into an image, e.g. to compare a render against
+// a golden image in tests. GL rows start at the bottom, so they're flipped to match images.
+pub unsafe fn read_pixels_to_image(gl: &Context, width: u32, height: u32) -> RgbaImage {
+  let image = RgbaImage::from_raw(width, height, read_rgba(gl, width, height)).unwrap();
+  imageops::flip_vertical(&image)
+}
+
+unsafe fn read_rgba(gl: &Context, width: u32, height: u32) -> Vec<u8> {
+  let mut pixels = vec![0; (width * height * 4) as usize];
+  gl.read_pixels(
+    0,
+    0,
+    width as i32,
+    height as i32,
+    glow::RGBA,
+    glow::UNSIGNED_BYTE,
+    glow::PixelPackData::Slice(&mut pixels),
+  );
+  pixels
+}
+
+unsafe fn read_rgba_f32(gl: &Context, width: u32, height: u32) -> Vec<f32> {
+  let mut pixels = vec![0f32; (width * height * 4) as usize];
+  let (_, bytes, _) = pixels.align_to_mut::<u8>();
+  gl.read_pixels(
+    0,
+    0,
+    width as i32,
+    height as i32,
+    glow::RGBA,
+    glow::FLOAT,
+    glow::PixelPackData::Slice(bytes),
+  );
+  pixels
+}
@@ -0,0 +1,78 @@
+use crate::{
+  post_process::ScreenQuad, prelude::*, shader::ShaderDefines, shader_cache::ShaderCache,
+  texture::Texture,
+};
+
+// Sources are compiled into the binary, so FXAA needs no file loading at runtime
+const VERTEX_SOURCE: &str = include_str!("../assets/shaders/screen.vert");
+const FRAGMENT_SOURCE: &str = include_str!("../assets/shaders/fxaa.frag");
+
+// How far FXAA searches along each edge. Higher quality handles longer, shallower edges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FxaaQuality {
+  Low,
+  Medium,
+  High,
+}
+
+impl FxaaQuality {
+  fn preset(self) -> u32 {
+    match self {
+      FxaaQuality::Low => 10,
+      FxaaQuality::Medium => 20,
+      FxaaQuality::High => 39,
+    }
+  }
+}
+
+// Fast approximate antialiasing: finds edges from luma contrast and blends across them.
+// Much cheaper than MSAA, and works on WebGL or intermediate framebuffers where
+// multisampling isn't available.
+pub struct Fxaa {
+  variants: ShaderCache,
+  quad: ScreenQuad,
+
+  pub quality: FxaaQuality,
+  // Minimum contrast, relative to the brightest neighbor, for a pixel to count as an edge
+  pub edge_threshold: f32,
+  // Absolute minimum contrast, so dark regions aren't blurred
+  pub edge_threshold_min: f32,
+  // How much to smooth aliasing within a single pixel, from 0 (off) to 1 (softer)
+  pub subpixel: f32,
+}
+
+impl Fxaa {
+  pub unsafe fn new(gl: &Context) -> Result<Self> {
+    Ok(Fxaa {
+      variants: ShaderCache::new(),
+      quad: ScreenQuad::new(gl)?,
+      quality: FxaaQuality::Medium,
+      edge_threshold: 1. / 8.,
+      edge_threshold_min: 1. / 32.,
+      subpixel: 0.75,
+    })
+  }
+
+  // Antialias ldr_tex (tonemapped, gamma-encoded color) into the bound framebuffer.
+  // resolution is the size of ldr_tex in pixels.
+  pub unsafe fn render(&mut self, gl: &Context, ldr_tex: &Texture, resolution: Vec2) -> Result<()> {
+    let defines = ShaderDefines::new().with("FXAA_QUALITY__PRESET", self.quality.preset());
+    let variants = &mut self.variants;
+    let shader = variants.get_or_compile(gl, VERTEX_SOURCE, FRAGMENT_SOURCE, None, &defines)?;
+
+    let mut shader = shader.activate(gl);
+    shader.bind_uniform(gl, "screenTexture", ldr_tex);
+    let inverse_size = glm::vec2(1. / resolution.x, 1. / resolution.y);
+    shader.bind_uniform(gl, "inverse_screen_size", &inverse_size);
+    shader.bind_uniform(gl, "edge_threshold", &self.edge_threshold);
+    shader.bind_uniform(gl, "edge_threshold_min", &self.edge_threshold_min);
+    shader.bind_uniform(gl, "subpixel", &self.subpixel);
+    self.quad.draw(gl, &mut shader);
+    Ok(())
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.variants.dispose(gl);
+    self.quad.dispose(gl);
+  }
+}
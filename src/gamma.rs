@@ -0,0 +1,117 @@
+use crate::{
+  prelude::*,
+  shader::{ActiveShader, BindUniform},
+};
+use std::cell::Cell;
+
+// How the final presented color is converted from linear to display space.
+//
+// Lighting math assumes linear colors, but monitors expect gamma-encoded colors, so the
+// last pass has to encode its output exactly once. Color textures authored in sRGB (most
+// diffuse maps) should be loaded with an sRGB internal format, e.g.
+// `TextureBuilder::new(gl).with_internal_format(glow::SRGB8_ALPHA8)`, so sampling them
+// returns linear values. Otherwise they get gamma-encoded twice and look washed out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GammaCorrection {
+  // Output colors as-is
+  Off,
+  // Let the driver encode on write to an sRGB-capable default framebuffer
+  Srgb,
+  // Encode in the final shader pass with pow(color, 1 / gamma)
+  Manual(f32),
+}
+
+pub const DEFAULT_GAMMA: f32 = 2.2;
+
+thread_local! {
+  static GAMMA_CORRECTION: Cell<GammaCorrection> = Cell::new(GammaCorrection::Off);
+}
+
+pub fn gamma_correction() -> GammaCorrection {
+  GAMMA_CORRECTION.with(|mode| mode.get())
+}
+
+pub unsafe fn set_gamma_correction(gl: &Context, mode: GammaCorrection) {
+  // WebGL has no GL_FRAMEBUFFER_SRGB, so do it in the shader instead
+  #[cfg(target_arch = "wasm32")]
+  let mode = match mode {
+    GammaCorrection::Srgb => GammaCorrection::Manual(DEFAULT_GAMMA),
+    mode => mode,
+  };
+
+  #[cfg(not(target_arch = "wasm32"))]
+  {
+    if mode == GammaCorrection::Srgb {
+      gl.enable(glow::FRAMEBUFFER_SRGB);
+    } else {
+      gl.disable(glow::FRAMEBUFFER_SRGB);
+    }
+  }
+
+  #[cfg(target_arch = "wasm32")]
+  let _ = gl;
+
+  GAMMA_CORRECTION.with(|current| current.set(mode));
+}
+
+// Gamma that the final shader pass should encode with. This is 1 unless correction is
+// done manually, so that it never stacks with the sRGB framebuffer.
+pub fn shader_gamma() -> f32 {
+  match gamma_correction() {
+    GammaCorrection::Manual(gamma) => gamma,
+    GammaCorrection::Off | GammaCorrection::Srgb => 1.,
+  }
+}
+
+// An RGB color stored in linear space. Colors picked in a color picker are sRGB-encoded, so
+// they go through from_srgb; binding one to a shader uploads the linear value as a vec3.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color(Vec3);
+
+impl Color {
+  pub fn from_linear(rgb: Vec3) -> Self {
+    Color(rgb)
+  }
+
+  // Decode with the piecewise sRGB curve rather than pow(c, 2.2), which is noticeably off
+  // in the darks
+  pub fn from_srgb(rgb: Vec3) -> Self {
+    Color(rgb.map(|c| {
+      if c <= 0.04045 {
+        c / 12.92
+      } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+      }
+    }))
+  }
+
+  // From 0-255 components, e.g. a hex code
+  pub fn from_srgb8(r: u8, g: u8, b: u8) -> Self {
+    Self::from_srgb(glm::vec3(r as f32, g as f32, b as f32) / 255.)
+  }
+
+  pub fn linear(&self) -> Vec3 {
+    self.0
+  }
+
+  pub fn srgb(&self) -> Vec3 {
+    self.0.map(|c| {
+      if c <= 0.0031308 {
+        c * 12.92
+      } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+      }
+    })
+  }
+
+  // Alpha is never gamma-encoded, so it's appended as-is for vec4 uniforms
+  pub fn with_alpha(&self, alpha: f32) -> Vec4 {
+    glm::vec4(self.0.x, self.0.y, self.0.z, alpha)
+  }
+}
+
+impl BindUniform for Color {
+  unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
+    self.0.bind_uniform(gl, shader, name);
+  }
+}
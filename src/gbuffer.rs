@@ -0,0 +1,102 @@
+use crate::{
+  framebuffer::Framebuffer,
+  prelude::*,
+  texture::{Texture, TextureBuilder},
+};
+
+// Geometry buffer for deferred shading. The geometry pass writes per-pixel attributes into
+// the three color attachments, and the lighting pass samples them to shade each pixel once.
+pub struct GBuffer {
+  framebuffer: Framebuffer,
+  pub position: Texture,
+  pub normal: Texture,
+  pub albedo_spec: Texture,
+
+  // Metallic, roughness and ambient occlusion in RGB, for PBR (see GBuffer::with_material)
+  pub material: Option<Texture>,
+}
+
+impl GBuffer {
+  pub unsafe fn new(gl: &Context, width: u32, height: u32) -> Result<Self> {
+    Self::build(gl, width, height, false)
+  }
+
+  // Like new, plus a fourth attachment for the PBR material parameters
+  pub unsafe fn with_material(gl: &Context, width: u32, height: u32) -> Result<Self> {
+    Self::build(gl, width, height, true)
+  }
+
+  unsafe fn build(gl: &Context, width: u32, height: u32, with_material: bool) -> Result<Self> {
+    let mut framebuffer = Framebuffer::empty(gl, width, height)?;
+
+    let make_texture = |format, internal_format| {
+      TextureBuilder::new(gl)
+        .with_format(format)
+        .with_internal_format(internal_format)
+        .with_tex_parameter(glow::TEXTURE_MIN_FILTER, glow::NEAREST)
+        .with_tex_parameter(glow::TEXTURE_MAG_FILTER, glow::NEAREST)
+        .with_tex_parameter(glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE)
+        .with_tex_parameter(glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE)
+        .render_texture(width, height)
+    };
+
+    // Positions and normals need more precision than a byte per channel. Albedo goes in
+    // RGB and the specular intensity in alpha, so that one needs four channels. RGB16F
+    // isn't color-renderable on ES or WebGL 2, so positions and normals leave alpha unused.
+    let position = make_texture(glow::RGBA, glow::RGBA16F)?;
+    let normal = make_texture(glow::RGBA, glow::RGBA16F)?;
+    let albedo_spec = make_texture(glow::RGBA, glow::RGBA16F)?;
+
+    let material = if with_material {
+      Some(make_texture(glow::RGBA, glow::RGBA8)?)
+    } else {
+      None
+    };
+
+    let mut textures = vec![&position, &normal, &albedo_spec];
+    textures.extend(material.as_ref());
+    let attachments = (0..textures.len() as u32)
+      .map(|i| glow::COLOR_ATTACHMENT0 + i)
+      .collect::<Vec<_>>();
+    for (attachment, texture) in attachments.iter().zip(&textures) {
+      framebuffer.attach_texture(gl, *attachment, texture);
+    }
+
+    // Tell OpenGL to write each fragment shader output into its attachment
+    gl.draw_buffers(&attachments);
+
+    framebuffer.attach_renderbuffer(gl, glow::DEPTH_STENCIL_ATTACHMENT, glow::DEPTH24_STENCIL8)?;
+    framebuffer.check_completeness(gl)?;
+    framebuffer.unbind(gl);
+
+    Ok(GBuffer {
+      framebuffer,
+      position,
+      normal,
+      albedo_spec,
+      material,
+    })
+  }
+
+  pub unsafe fn bind(&self, gl: &Context) {
+    self.framebuffer.bind(gl);
+  }
+
+  pub unsafe fn unbind(&self, gl: &Context) {
+    self.framebuffer.unbind(gl);
+  }
+
+  pub fn framebuffer(&self) -> &Framebuffer {
+    &self.framebuffer
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.framebuffer.dispose(gl);
+    self.position.dispose(gl);
+    self.normal.dispose(gl);
+    self.albedo_spec.dispose(gl);
+    if let Some(material) = self.material {
+      material.dispose(gl);
+    }
+  }
+}
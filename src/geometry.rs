@@ -3,6 +3,7 @@ use crate::{
   mesh::{Mesh, Vertex},
   prelude::*,
 };
+use std::f32::consts::PI;
 
 pub enum Geometry {
   Cube {
@@ -15,6 +16,26 @@ pub enum Geometry {
     width: f32,
     normal: Vec3,
   },
+  // Flat square on the XZ plane facing +Y, split into subdivisions x subdivisions cells,
+  // e.g. for a ground that gets lit per vertex or displaced. Texture coordinates span
+  // [0, 1] across the whole grid.
+  Grid {
+    size: f32,
+    subdivisions: u32,
+  },
+  // UV sphere centered on the origin. Rings run from pole to pole along Y, and sectors
+  // go around it.
+  Sphere {
+    radius: f32,
+    rings: u32,
+    sectors: u32,
+  },
+  // Closed cylinder along Y, centered on the origin
+  Cylinder {
+    radius: f32,
+    height: f32,
+    sectors: u32,
+  },
 }
 
 impl Geometry {
@@ -99,6 +120,154 @@ impl Geometry {
         let indices = vec![0, 1, 2, 1, 3, 2, 0, 2, 1, 1, 2, 3];
         (vertices, indices)
       }
+
+      Geometry::Grid { size, subdivisions } => {
+        let subdivisions = subdivisions.max(1);
+        let row = subdivisions + 1;
+        let vertices = (0..row)
+          .flat_map(|i| {
+            (0..row).map(move |j| {
+              let u = i as f32 / subdivisions as f32;
+              let v = j as f32 / subdivisions as f32;
+              Vertex {
+                position: glm::vec3((u - 0.5) * size, 0., (v - 0.5) * size),
+                normal: glm::vec3(0., 1., 0.),
+                tex_coords: glm::vec2(u, v),
+              }
+            })
+          })
+          .collect();
+
+        // Two counter-clockwise triangles (seen from above) per cell
+        let indices = (0..subdivisions)
+          .flat_map(|i| {
+            (0..subdivisions).flat_map(move |j| {
+              let corner = i * row + j;
+              vec![
+                corner,
+                corner + 1,
+                corner + row,
+                corner + 1,
+                corner + row + 1,
+                corner + row,
+              ]
+            })
+          })
+          .collect();
+        (vertices, indices)
+      }
+
+      Geometry::Sphere {
+        radius,
+        rings,
+        sectors,
+      } => {
+        let (rings, sectors) = (rings.max(2), sectors.max(3));
+        let row = sectors + 1;
+
+        // The first and last sector of each ring overlap so the seam gets both texture
+        // coordinates 0 and 1
+        let vertices = (0..=rings)
+          .flat_map(|r| {
+            let phi = PI * r as f32 / rings as f32;
+            (0..=sectors).map(move |s| {
+              let theta = 2. * PI * s as f32 / sectors as f32;
+              let normal = glm::vec3(phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin());
+              Vertex {
+                position: normal * radius,
+                normal,
+                tex_coords: glm::vec2(s as f32 / sectors as f32, 1. - r as f32 / rings as f32),
+              }
+            })
+          })
+          .collect();
+
+        // Each quad between two rings is two counter-clockwise triangles seen from outside,
+        // except at the poles where one of them collapses to a line
+        let indices = (0..rings)
+          .flat_map(|r| {
+            (0..sectors).flat_map(move |s| {
+              let (a, c) = (r * row + s, r * row + s + 1);
+              let (b, d) = (a + row, c + row);
+              let mut tris = vec![];
+              if r != 0 {
+                tris.extend_from_slice(&[a, c, b]);
+              }
+              if r != rings - 1 {
+                tris.extend_from_slice(&[c, d, b]);
+              }
+              tris
+            })
+          })
+          .collect();
+        (vertices, indices)
+      }
+
+      Geometry::Cylinder {
+        radius,
+        height,
+        sectors,
+      } => {
+        let sectors = sectors.max(3);
+        let half = height / 2.;
+        let angle = |s: u32| 2. * PI * s as f32 / sectors as f32;
+
+        // The side has its own vertices so its normals point outward, with a top and
+        // bottom vertex per sector
+        let mut vertices = (0..=sectors)
+          .flat_map(|s| {
+            let normal = glm::vec3(angle(s).cos(), 0., angle(s).sin());
+            let u = s as f32 / sectors as f32;
+            vec![
+              Vertex {
+                position: normal * radius + glm::vec3(0., half, 0.),
+                normal,
+                tex_coords: glm::vec2(u, 1.),
+              },
+              Vertex {
+                position: normal * radius - glm::vec3(0., half, 0.),
+                normal,
+                tex_coords: glm::vec2(u, 0.),
+              },
+            ]
+          })
+          .collect::<Vec<_>>();
+        let mut indices = (0..sectors)
+          .flat_map(|s| {
+            let (a, b, c, d) = (2 * s, 2 * s + 1, 2 * s + 2, 2 * s + 3);
+            vec![a, c, b, c, d, b]
+          })
+          .collect::<Vec<_>>();
+
+        // Each cap is a fan around its center, with texture coordinates mapping the disc
+        // into the unit square
+        for y in &[half, -half] {
+          let normal = glm::vec3(0., y.signum(), 0.);
+          let center = vertices.len() as u32;
+          vertices.push(Vertex {
+            position: glm::vec3(0., *y, 0.),
+            normal,
+            tex_coords: glm::vec2(0.5, 0.5),
+          });
+          for s in 0..=sectors {
+            let (cos, sin) = (angle(s).cos(), angle(s).sin());
+            vertices.push(Vertex {
+              position: glm::vec3(cos * radius, *y, sin * radius),
+              normal,
+              tex_coords: glm::vec2(cos * 0.5 + 0.5, sin * 0.5 + 0.5),
+            });
+          }
+          for s in 0..sectors {
+            let (p, q) = (center + 1 + s, center + 2 + s);
+            if *y > 0. {
+              indices.extend_from_slice(&[center, q, p]);
+            } else {
+              indices.extend_from_slice(&[center, p, q]);
+            }
+          }
+        }
+        (vertices, indices)
+      }
     }
   }
 
@@ -107,3 +276,114 @@ impl Geometry {
     Mesh::new(gl, vertices, indices, material)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Every normal must be unit length, and every triangle must be counter-clockwise when
+  // seen from the side its vertex normals point to, i.e. front-facing with GL's defaults.
+  // Closed shapes centered on the origin also have to face away from it.
+  fn check_winding(vertices: &[Vertex], indices: &[u32], closed: bool) {
+    for vertex in vertices {
+      assert!(
+        (vertex.normal.norm() - 1.).abs() < 1e-5,
+        "{:?}",
+        vertex.normal
+      );
+    }
+
+    for tri in indices.chunks_exact(3) {
+      let [a, b, c] = [
+        &vertices[tri[0] as usize],
+        &vertices[tri[1] as usize],
+        &vertices[tri[2] as usize],
+      ];
+      let face = glm::cross(&(b.position - a.position), &(c.position - a.position));
+      assert!(face.norm() > 1e-6, "degenerate triangle {:?}", tri);
+      for vertex in &[a, b, c] {
+        assert!(
+          face.dot(&vertex.normal) > 0.,
+          "triangle {:?} faces inward",
+          tri
+        );
+      }
+      if closed {
+        let centroid = (a.position + b.position + c.position) / 3.;
+        assert!(
+          face.dot(&centroid) > 0.,
+          "triangle {:?} faces the center",
+          tri
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn cube_faces_outward() {
+    let (vertices, indices) = Geometry::Cube {
+      length: 1.,
+      width: 2.,
+      height: 3.,
+    }
+    .to_vertices_indices();
+    assert_eq!(indices.len(), 36);
+    check_winding(&vertices, &indices, true);
+  }
+
+  #[test]
+  fn sphere_faces_outward() {
+    let (vertices, indices) = Geometry::Sphere {
+      radius: 2.,
+      rings: 8,
+      sectors: 12,
+    }
+    .to_vertices_indices();
+    check_winding(&vertices, &indices, true);
+    for vertex in &vertices {
+      assert!((vertex.position / 2. - vertex.normal).norm() < 1e-5);
+    }
+  }
+
+  #[test]
+  fn cylinder_faces_outward() {
+    let (vertices, indices) = Geometry::Cylinder {
+      radius: 1.,
+      height: 2.,
+      sectors: 16,
+    }
+    .to_vertices_indices();
+    check_winding(&vertices, &indices, true);
+  }
+
+  #[test]
+  fn plane_faces_both_ways() {
+    let normal = glm::vec3(0., 1., 0.);
+    let (vertices, indices) = Geometry::Plane {
+      length: 2.,
+      width: 3.,
+      normal,
+    }
+    .to_vertices_indices();
+
+    // The first two triangles face the normal, and the other two are the same triangles
+    // wound the other way for the back side
+    let (front, back) = indices.split_at(6);
+    check_winding(&vertices, front, false);
+    let reversed = back
+      .chunks_exact(3)
+      .flat_map(|tri| vec![tri[0], tri[2], tri[1]])
+      .collect::<Vec<_>>();
+    check_winding(&vertices, &reversed, false);
+  }
+
+  #[test]
+  fn grid_faces_up() {
+    let (vertices, indices) = Geometry::Grid {
+      size: 4.,
+      subdivisions: 3,
+    }
+    .to_vertices_indices();
+    check_winding(&vertices, &indices, false);
+  }
+}
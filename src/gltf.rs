@@ -0,0 +1,522 @@
+use crate::{
+  animation::{Animation, BoneTrack, Interpolation, Keyframes, Skeleton},
+  io,
+  material::PbrMaterial,
+  mesh::{Mesh, SkinVertex, Vertex},
+  prelude::*,
+  shader::ActiveShader,
+  texture::{Texture, TextureBuilder},
+  transform::Transform,
+};
+use ::gltf::{
+  animation as gltf_animation,
+  animation::util::ReadOutputs,
+  buffer, image as gltf_image,
+  mesh::{Mode, Primitive},
+  Document, Gltf, Node,
+};
+use futures::future::try_join_all;
+use std::{
+  collections::{HashMap, HashSet},
+  path::Path,
+};
+
+// A glTF file with its buffers and images loaded
+struct GltfData {
+  document: Document,
+  buffers: Vec<Vec<u8>>,
+  textures: Vec<Texture>,
+}
+
+// Decode a `data:[<media type>];base64,<data>` URI, which glTF files use to embed buffers
+// and images in the JSON itself
+fn decode_data_uri(uri: &str) -> Result<Vec<u8>> {
+  let rest = uri.strip_prefix("data:").context("Not a data URI")?;
+  let comma = rest.find(',').context("Data URI is missing a comma")?;
+  let (header, data) = (&rest[..comma], &rest[comma + 1..]);
+  if !header.ends_with(";base64") {
+    bail!("Only base64 data URIs are supported, got {:?}", header);
+  }
+  Ok(base64::decode(data)?)
+}
+
+// Buffers and images referenced by URI are loaded relative to the file, using io so that
+// it works on the web too
+async unsafe fn load_data(gl: &Context, path: &Path) -> Result<GltfData> {
+  let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+  let bytes = io::load_bytes(path).await?;
+  let Gltf { document, blob } = Gltf::from_slice(&bytes)?;
+
+  // Buffers are either the binary chunk of a .glb or external files
+  let buffers = try_join_all(document.buffers().map(|buffer| {
+    let blob = &blob;
+    async move {
+      match buffer.source() {
+        buffer::Source::Bin => blob.clone().context("glTF binary chunk missing"),
+        buffer::Source::Uri(uri) if uri.starts_with("data:") => decode_data_uri(uri),
+        buffer::Source::Uri(uri) => io::load_bytes(base_dir.join(uri)).await,
+      }
+    }
+  }))
+  .await?;
+
+  // Images are either stored in a buffer view, embedded in a data URI, or in external files
+  let images = try_join_all(document.images().map(|image| {
+    let buffers = &buffers;
+    async move {
+      match image.source() {
+        gltf_image::Source::View { view, .. } => {
+          let start = view.offset();
+          let data = &buffers[view.buffer().index()][start..start + view.length()];
+          Ok(image::load_from_memory(data)?)
+        }
+        gltf_image::Source::Uri { uri, .. } if uri.starts_with("data:") => {
+          Ok(image::load_from_memory(&decode_data_uri(uri)?)?)
+        }
+        gltf_image::Source::Uri { uri, .. } => io::load_image(base_dir.join(uri)).await,
+      }
+    }
+  }))
+  .await?;
+
+  // Base color and emissive images are sRGB encoded, so sampling them should decode to
+  // linear. Every other map stores linear data as-is.
+  let srgb_images = document
+    .materials()
+    .flat_map(|material| {
+      let base_color = material.pbr_metallic_roughness().base_color_texture();
+      let emissive = material.emissive_texture();
+      base_color
+        .into_iter()
+        .chain(emissive)
+        .map(|info| info.texture().source().index())
+    })
+    .collect::<HashSet<_>>();
+
+  // glTF texture coordinates start at the top-left, so the images don't need flipping
+  let textures = images
+    .into_iter()
+    .enumerate()
+    .map(|(index, image)| {
+      let internal_format = if srgb_images.contains(&index) {
+        glow::SRGB8_ALPHA8
+      } else {
+        glow::RGBA8
+      };
+      TextureBuilder::new(gl)
+        .with_flip(false)
+        .with_internal_format(internal_format)
+        .build(image)
+    })
+    .collect::<Result<Vec<_>>>()?;
+
+  Ok(GltfData {
+    document,
+    buffers,
+    textures,
+  })
+}
+
+// Load every primitive in a glTF 2.0 file (.gltf or binary .glb) as a Mesh along with its
+// material
+pub async unsafe fn load_gltf(
+  gl: &Context,
+  path: impl AsRef<Path>,
+) -> Result<Vec<(Mesh, PbrMaterial)>> {
+  let data = load_data(gl, path.as_ref()).await?;
+  Ok(
+    load_meshes(gl, &data, |_| Ok(None))?
+      .into_iter()
+      .flatten()
+      .collect(),
+  )
+}
+
+// Build a Mesh for each triangle primitive, grouped by glTF mesh. skin_vertices gives the
+// skinning data of a primitive, if it should have any.
+unsafe fn load_meshes(
+  gl: &Context,
+  data: &GltfData,
+  skin_vertices: impl Fn(&Primitive) -> Result<Option<Vec<SkinVertex>>>,
+) -> Result<Vec<Vec<(Mesh, PbrMaterial)>>> {
+  let GltfData {
+    document,
+    buffers,
+    textures,
+  } = data;
+  let texture = |info: Option<::gltf::Texture>| -> Option<Texture> {
+    info.map(|texture| textures[texture.source().index()].clone())
+  };
+
+  let mut meshes = vec![];
+  for gltf_mesh in document.meshes() {
+    let mut primitives = vec![];
+    for primitive in gltf_mesh.primitives() {
+      // Points, lines and strips aren't supported yet
+      if primitive.mode() != Mode::Triangles {
+        continue;
+      }
+
+      let reader = primitive.reader(|buffer| Some(buffers[buffer.index()].as_slice()));
+
+      let positions = reader
+        .read_positions()
+        .context("glTF primitive has no positions")?;
+      let mut normals = reader.read_normals();
+      let mut tex_coords = reader.read_tex_coords(0).map(|tc| tc.into_f32());
+      let vertices = positions
+        .map(|position| Vertex {
+          position: position.into(),
+          normal: normals
+            .as_mut()
+            .and_then(Iterator::next)
+            .map_or_else(glm::zero, Into::into),
+          tex_coords: tex_coords
+            .as_mut()
+            .and_then(Iterator::next)
+            .map_or_else(glm::zero, Into::into),
+        })
+        .collect::<Vec<_>>();
+
+      // Primitives without indices draw their vertices in order
+      let indices = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertices.len() as u32).collect(),
+      };
+
+      let material = primitive.material();
+      let pbr = material.pbr_metallic_roughness();
+      let pbr_material = PbrMaterial {
+        albedo: pbr.base_color_factor().into(),
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        ao: 1.,
+        emissive: material.emissive_factor().into(),
+        albedo_map: texture(pbr.base_color_texture().map(|info| info.texture())),
+        metallic_roughness_map: texture(
+          pbr.metallic_roughness_texture().map(|info| info.texture()),
+        ),
+        normal_map: texture(material.normal_texture().map(|info| info.texture())),
+        ao_map: texture(material.occlusion_texture().map(|info| info.texture())),
+      };
+
+      let mut mesh = Mesh::new(gl, vertices, indices, None)?;
+      if let Some(skin) = skin_vertices(&primitive)? {
+        mesh.set_skin(gl, &skin)?;
+      }
+      primitives.push((mesh, pbr_material));
+    }
+    meshes.push(primitives);
+  }
+
+  Ok(meshes)
+}
+
+// A node of a glTF scene, placed relative to its parent
+pub struct GltfNode {
+  pub name: Option<String>,
+  pub transform: Transform,
+  pub parent: Option<usize>,
+
+  // Index into GltfScene::meshes
+  pub mesh: Option<usize>,
+}
+
+// The node hierarchy of a glTF scene with the meshes it uses. Nodes are ordered so that
+// parents come before their children.
+pub struct GltfScene {
+  pub meshes: Vec<Vec<(Mesh, PbrMaterial)>>,
+  pub nodes: Vec<GltfNode>,
+
+  // Every texture of the file, which the materials share
+  pub textures: Vec<Texture>,
+}
+
+impl GltfScene {
+  // Model matrix of each node, combining the transforms of all its ancestors
+  pub fn world_matrices(&self) -> Vec<Mat4> {
+    let mut matrices: Vec<Mat4> = Vec::with_capacity(self.nodes.len());
+    for node in &self.nodes {
+      let local = node.transform.matrix(None);
+      matrices.push(match node.parent {
+        Some(parent) => matrices[parent] * local,
+        None => local,
+      });
+    }
+    matrices
+  }
+
+  // Draw every mesh with its node's matrix bound as `model` and its PbrMaterial as
+  // `material`
+  pub unsafe fn draw(&self, gl: &Context, shader: &mut ActiveShader) {
+    for (node, matrix) in self.nodes.iter().zip(self.world_matrices()) {
+      if let Some(mesh) = node.mesh {
+        shader.bind_uniform(gl, "model", &matrix);
+        for (mesh, material) in &self.meshes[mesh] {
+          shader.bind_uniform(gl, "material", material);
+          mesh.draw(gl, shader);
+        }
+      }
+    }
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    for (mesh, _) in self.meshes.into_iter().flatten() {
+      mesh.dispose(gl);
+    }
+    for texture in self.textures {
+      texture.dispose(gl);
+    }
+  }
+}
+
+// Load the default scene of a glTF file (or its first, if there's no default) with its
+// node hierarchy
+pub async unsafe fn load_gltf_scene(gl: &Context, path: impl AsRef<Path>) -> Result<GltfScene> {
+  let data = load_data(gl, path.as_ref()).await?;
+  let gltf_scene = data
+    .document
+    .default_scene()
+    .or_else(|| data.document.scenes().next())
+    .context("glTF file has no scenes")?;
+
+  // Walk the hierarchy depth-first so parents are added before their children
+  let mut nodes = vec![];
+  let mut stack = gltf_scene
+    .nodes()
+    .map(|node| (node, None))
+    .collect::<Vec<_>>();
+  while let Some((node, parent)) = stack.pop() {
+    let index = nodes.len();
+    nodes.push(GltfNode {
+      name: node.name().map(str::to_owned),
+      transform: node_transform(&node),
+      parent,
+      mesh: node.mesh().map(|mesh| mesh.index()),
+    });
+    stack.extend(node.children().map(|child| (child, Some(index))));
+  }
+
+  let meshes = load_meshes(gl, &data, |_| Ok(None))?;
+  Ok(GltfScene {
+    meshes,
+    nodes,
+    textures: data.textures,
+  })
+}
+
+pub struct SkinnedModel {
+  pub meshes: Vec<(Mesh, PbrMaterial)>,
+  pub skeleton: Skeleton,
+  pub animations: Vec<Animation>,
+
+  // Every texture of the file, which the materials share
+  pub textures: Vec<Texture>,
+}
+
+impl SkinnedModel {
+  pub unsafe fn dispose(self, gl: &Context) {
+    for (mesh, _) in self.meshes {
+      mesh.dispose(gl);
+    }
+    for texture in self.textures {
+      texture.dispose(gl);
+    }
+  }
+}
+
+// Load a glTF file's meshes along with its first skin as a Skeleton, and every animation
+// of that skin's joints. Draw the meshes with skinning.vert (built with the BoneBlock
+// def, see ShaderBuilder::with_block_def) after uploading the skeleton.
+// Transforms of nodes above the skeleton's root joints are ignored.
+pub async unsafe fn load_skinned_gltf(
+  gl: &Context,
+  path: impl AsRef<Path>,
+) -> Result<SkinnedModel> {
+  let data = load_data(gl, path.as_ref()).await?;
+  let document = &data.document;
+  let buffers = &data.buffers;
+  let skin = document.skins().next().context("glTF file has no skins")?;
+  let joints = skin.joints().collect::<Vec<_>>();
+
+  // Skeleton needs parents before children, so order the joints by their depth in the
+  // node hierarchy. Each joint's parent is its closest ancestor that is also a joint.
+  let mut node_parents = vec![None; document.nodes().len()];
+  for node in document.nodes() {
+    for child in node.children() {
+      node_parents[child.index()] = Some(node.index());
+    }
+  }
+  let node_parents = &node_parents;
+  let ancestors =
+    |node: usize| std::iter::successors(node_parents[node], move |parent| node_parents[*parent]);
+  let joint_of_node = joints
+    .iter()
+    .enumerate()
+    .map(|(joint, node)| (node.index(), joint))
+    .collect::<HashMap<_, _>>();
+  let mut order = (0..joints.len()).collect::<Vec<_>>();
+  order.sort_by_key(|joint| ancestors(joints[*joint].index()).count());
+  let mut bone_of_joint = vec![0; joints.len()];
+  for (bone, joint) in order.iter().enumerate() {
+    bone_of_joint[*joint] = bone;
+  }
+
+  let parents = order
+    .iter()
+    .map(|joint| {
+      ancestors(joints[*joint].index())
+        .find_map(|node| joint_of_node.get(&node))
+        .map(|parent| bone_of_joint[*parent])
+    })
+    .collect();
+  let inverse_bind_matrices = match skin
+    .reader(|buffer| Some(buffers[buffer.index()].as_slice()))
+    .read_inverse_bind_matrices()
+  {
+    Some(matrices) => {
+      let matrices = matrices.map(Mat4::from).collect::<Vec<_>>();
+      order.iter().map(|joint| matrices[*joint]).collect()
+    }
+    None => vec![Mat4::identity(); joints.len()],
+  };
+  let mut skeleton = Skeleton::new(parents, inverse_bind_matrices)?;
+
+  let rest_pose = order
+    .iter()
+    .map(|joint| node_transform(&joints[*joint]))
+    .collect::<Vec<_>>();
+  skeleton.set_pose(
+    &rest_pose
+      .iter()
+      .map(|transform| transform.matrix(None))
+      .collect::<Vec<_>>(),
+  );
+
+  let animations = document
+    .animations()
+    .map(|animation| {
+      let mut tracks = vec![BoneTrack::default(); joints.len()];
+      let mut duration = 0f32;
+      for channel in animation.channels() {
+        let bone = match joint_of_node.get(&channel.target().node().index()) {
+          Some(joint) => bone_of_joint[*joint],
+          None => continue,
+        };
+        let reader = channel.reader(|buffer| Some(buffers[buffer.index()].as_slice()));
+        let times = reader
+          .read_inputs()
+          .context("glTF animation channel has no inputs")?
+          .collect::<Vec<_>>();
+        duration = duration.max(times.last().copied().unwrap_or(0.));
+
+        // Cubic splines store an in-tangent, value and out-tangent per keyframe. Only the
+        // values are kept, and interpolated linearly.
+        let (interpolation, stride, offset) = match channel.sampler().interpolation() {
+          gltf_animation::Interpolation::Step => (Interpolation::Step, 1, 0),
+          gltf_animation::Interpolation::Linear => (Interpolation::Linear, 1, 0),
+          gltf_animation::Interpolation::CubicSpline => (Interpolation::Linear, 3, 1),
+        };
+        let track = &mut tracks[bone];
+        match reader
+          .read_outputs()
+          .context("glTF animation channel has no outputs")?
+        {
+          ReadOutputs::Translations(values) => {
+            let values = values.map(Vec3::from);
+            track.translations = Some(keyframes(times, values, interpolation, stride, offset));
+          }
+          ReadOutputs::Rotations(values) => {
+            let values = values.into_f32().map(|q| glm::quat(q[0], q[1], q[2], q[3]));
+            track.rotations = Some(keyframes(times, values, interpolation, stride, offset));
+          }
+          ReadOutputs::Scales(values) => {
+            let values = values.map(Vec3::from);
+            track.scales = Some(keyframes(times, values, interpolation, stride, offset));
+          }
+          ReadOutputs::MorphTargetWeights(_) => {}
+        }
+      }
+
+      Ok(Animation {
+        name: animation.name().map(str::to_owned),
+        duration,
+        rest_pose: rest_pose.clone(),
+        tracks,
+      })
+    })
+    .collect::<Result<Vec<_>>>()?;
+
+  // Vertex joint indices refer to the skin's joint list, so they're remapped to bones
+  let meshes = load_meshes(gl, &data, |primitive| {
+    let reader = primitive.reader(|buffer| Some(buffers[buffer.index()].as_slice()));
+    let (joints, weights) = match (reader.read_joints(0), reader.read_weights(0)) {
+      (Some(joints), Some(weights)) => (joints, weights),
+      _ => return Ok(None),
+    };
+    Ok(Some(
+      joints
+        .into_u16()
+        .zip(weights.into_f32())
+        .map(|(joints, weights)| SkinVertex {
+          bone_indices: [
+            bone_of_joint[joints[0] as usize] as i32,
+            bone_of_joint[joints[1] as usize] as i32,
+            bone_of_joint[joints[2] as usize] as i32,
+            bone_of_joint[joints[3] as usize] as i32,
+          ],
+          bone_weights: weights.into(),
+        })
+        .collect(),
+    ))
+  })?;
+
+  Ok(SkinnedModel {
+    meshes: meshes.into_iter().flatten().collect(),
+    skeleton,
+    animations,
+    textures: data.textures,
+  })
+}
+
+fn keyframes<T>(
+  times: Vec<f32>,
+  values: impl Iterator<Item = T>,
+  interpolation: Interpolation,
+  stride: usize,
+  offset: usize,
+) -> Keyframes<T> {
+  Keyframes {
+    times,
+    values: values.skip(offset).step_by(stride).collect(),
+    interpolation,
+  }
+}
+
+fn node_transform(node: &Node) -> Transform {
+  let (translation, rotation, scale) = node.transform().decomposed();
+  Transform {
+    position: translation.into(),
+    rotation: glm::quat(rotation[0], rotation[1], rotation[2], rotation[3]),
+    scale: scale.into(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn decodes_base64_data_uris() {
+    let uri = "data:application/octet-stream;base64,AAECAw==";
+    assert_eq!(decode_data_uri(uri).unwrap(), vec![0, 1, 2, 3]);
+    assert_eq!(decode_data_uri("data:;base64,").unwrap(), Vec::<u8>::new());
+  }
+
+  #[test]
+  fn rejects_malformed_data_uris() {
+    assert!(decode_data_uri("data:text/plain,hello").is_err());
+    assert!(decode_data_uri("data:application/octet-stream;base64").is_err());
+    assert!(decode_data_uri("data:;base64,not base64!").is_err());
+  }
+}
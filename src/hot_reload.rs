@@ -0,0 +1,112 @@
+use crate::{
+  prelude::*,
+  texture::{T2d, Texture, TextureBuilder},
+};
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+  cell::RefCell,
+  collections::HashMap,
+  path::{Path, PathBuf},
+  sync::mpsc::{channel, Receiver},
+  time::Duration,
+};
+
+// One file watcher shared by every watched texture. Each change to a watched file bumps
+// its generation, and each handle reloads when it sees a newer generation than it loaded.
+struct TextureWatcher {
+  watcher: RecommendedWatcher,
+  events: Receiver<DebouncedEvent>,
+  generations: HashMap<PathBuf, u64>,
+}
+
+impl TextureWatcher {
+  fn new() -> Result<Self> {
+    let (sender, events) = channel();
+    let watcher = notify::watcher(sender, Duration::from_millis(100))?;
+    Ok(TextureWatcher {
+      watcher,
+      events,
+      generations: HashMap::new(),
+    })
+  }
+
+  fn watch(&mut self, path: &Path) -> Result<u64> {
+    if !self.generations.contains_key(path) {
+      self.watcher.watch(path, RecursiveMode::NonRecursive)?;
+      self.generations.insert(path.to_owned(), 0);
+    }
+    Ok(self.generations[path])
+  }
+
+  fn generation(&mut self, path: &Path) -> u64 {
+    for event in self.events.try_iter() {
+      // Editors often save by writing a new file and renaming it over the old one
+      if let DebouncedEvent::Write(path) | DebouncedEvent::Create(path) = event {
+        if let Some(generation) = self.generations.get_mut(&path) {
+          *generation += 1;
+        }
+      }
+    }
+    self.generations.get(path).copied().unwrap_or(0)
+  }
+}
+
+thread_local! {
+  static WATCHER: RefCell<Option<TextureWatcher>> = RefCell::new(None);
+}
+
+fn with_watcher<T>(f: impl FnOnce(&mut TextureWatcher) -> Result<T>) -> Result<T> {
+  WATCHER.with(|watcher| {
+    let mut watcher = watcher.borrow_mut();
+    if watcher.is_none() {
+      *watcher = Some(TextureWatcher::new()?);
+    }
+    f(watcher.as_mut().unwrap())
+  })
+}
+
+// A texture that reloads itself when its file changes on disk, for iterating on textures
+// without restarting
+pub struct WatchedTexture {
+  texture: Texture,
+  path: PathBuf,
+  generation: u64,
+}
+
+impl Texture<T2d> {
+  pub async unsafe fn watch(gl: &Context, path: impl AsRef<Path>) -> Result<WatchedTexture> {
+    // notify reports absolute paths, so watch the canonical path to recognize its events
+    let path = path.as_ref().canonicalize()?;
+    let generation = with_watcher(|watcher| watcher.watch(&path))?;
+    let texture = TextureBuilder::new(gl).load(&path).await?;
+    Ok(WatchedTexture {
+      texture,
+      path,
+      generation,
+    })
+  }
+}
+
+impl WatchedTexture {
+  pub fn texture(&self) -> &Texture {
+    &self.texture
+  }
+
+  // Reload the texture if its file changed since it was last loaded. Returns whether it
+  // was reloaded. If loading fails (e.g. the file is half-written) the old texture is kept.
+  pub async unsafe fn poll_reload(&mut self, gl: &Context) -> Result<bool> {
+    let generation = with_watcher(|watcher| Ok(watcher.generation(&self.path)))?;
+    if generation == self.generation {
+      return Ok(false);
+    }
+
+    let texture = TextureBuilder::new(gl).load(&self.path).await?;
+    std::mem::replace(&mut self.texture, texture).dispose(gl);
+    self.generation = generation;
+    Ok(true)
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.texture.dispose(gl);
+  }
+}
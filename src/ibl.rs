@@ -0,0 +1,424 @@
+use crate::{
+  framebuffer::Framebuffer,
+  geometry::Geometry,
+  material::PbrMaterial,
+  mesh::Mesh,
+  post_process::{ScreenQuad, SCREEN_VERTEX_SHADER},
+  prelude::*,
+  render_state::RenderState,
+  shader::{ActiveShader, BindUniform, Shader},
+  texture::{TCubemap, Texture, TextureBuilder, TextureTarget},
+};
+use futures::join;
+use std::path::Path;
+
+const ENVIRONMENT_SIZE: u32 = 512;
+const IRRADIANCE_SIZE: u32 = 32;
+const PREFILTERED_SIZE: u32 = 128;
+const PREFILTERED_MIPS: u32 = 5;
+const BRDF_LUT_SIZE: u32 = 512;
+
+// Precomputed image-based lighting for PBR materials, baked from an HDR environment:
+//  - irradiance: diffuse light arriving from the whole hemisphere around each direction
+//  - prefiltered: the environment blurred for increasing roughness in each mip level
+//  - brdf_lut: scale and bias to F0 for the specular split-sum, by angle and roughness
+pub struct Ibl {
+  pub environment: Texture<TCubemap>,
+  pub irradiance: Texture<TCubemap>,
+  pub prefiltered: Texture<TCubemap>,
+  pub brdf_lut: Texture,
+}
+
+impl Ibl {
+  // The caller is responsible for restoring the viewport afterwards
+  pub async unsafe fn bake(gl: &Context, hdr_path: impl AsRef<Path>) -> Result<Self> {
+    let cubemap_vertex = "assets/shaders/cubemap.vert";
+    let (equirect, irradiance_shader, prefilter_shader, brdf_shader) = join!(
+      TextureBuilder::new(gl).load_hdr(hdr_path),
+      load_shader(
+        gl,
+        cubemap_vertex,
+        "assets/shaders/irradiance.frag",
+        PBR_DEFS
+      ),
+      load_shader(
+        gl,
+        cubemap_vertex,
+        "assets/shaders/prefilter.frag",
+        PBR_DEFS
+      ),
+      load_shader(
+        gl,
+        SCREEN_VERTEX_SHADER,
+        "assets/shaders/brdf_lut.frag",
+        PBR_DEFS
+      ),
+    );
+
+    // If anything failed to load, free the rest before returning the first error
+    let (equirect, irradiance_shader, prefilter_shader, brdf_shader) =
+      match (equirect, irradiance_shader, prefilter_shader, brdf_shader) {
+        (Ok(equirect), Ok(irradiance), Ok(prefilter), Ok(brdf)) => {
+          (equirect, irradiance, prefilter, brdf)
+        }
+        (equirect, irradiance, prefilter, brdf) => {
+          let mut error = None;
+          match equirect {
+            Ok(equirect) => equirect.dispose(gl),
+            Err(err) => error = Some(err),
+          }
+          for shader in std::array::IntoIter::new([irradiance, prefilter, brdf]) {
+            match shader {
+              Ok(shader) => shader.dispose(gl),
+              Err(err) => {
+                error.get_or_insert(err);
+              }
+            }
+          }
+          return Err(error.unwrap());
+        }
+      };
+
+    // Mipmaps reduce the aliasing from sampling bright spots when prefiltering
+    let environment = equirect_to_cubemap(gl, &equirect, ENVIRONMENT_SIZE).await;
+    equirect.dispose(gl);
+
+    let maps = environment.and_then(|environment| {
+      match bake_maps(
+        gl,
+        &environment,
+        &irradiance_shader,
+        &prefilter_shader,
+        &brdf_shader,
+      ) {
+        Ok((irradiance, prefiltered, brdf_lut)) => Ok(Ibl {
+          environment,
+          irradiance,
+          prefiltered,
+          brdf_lut,
+        }),
+        Err(err) => {
+          environment.dispose(gl);
+          Err(err)
+        }
+      }
+    });
+
+    for shader in std::array::IntoIter::new([irradiance_shader, prefilter_shader, brdf_shader]) {
+      shader.dispose(gl);
+    }
+    RenderState::default().apply(gl);
+    gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+
+    maps
+  }
+
+  // Uniforms for ibl_ambient in the lighting shader
+  pub fn maps(&self) -> IblMaps {
+    IblMaps {
+      irradiance: self.irradiance.clone(),
+      prefiltered: self.prefiltered.clone(),
+      brdf_lut: self.brdf_lut.clone(),
+      max_reflection_lod: (PREFILTERED_MIPS - 1) as f32,
+    }
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.environment.dispose(gl);
+    self.irradiance.dispose(gl);
+    self.prefiltered.dispose(gl);
+    self.brdf_lut.dispose(gl);
+  }
+}
+
+// Binding an Ibl binds its maps as an IblMaps, so a baked Ibl can go straight to
+// ibl_ambient. The environment itself is only for drawing the sky, so it's left out.
+impl BindUniform for Ibl {
+  unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
+    shader.bind_uniform(gl, name, &self.maps());
+  }
+}
+
+#[derive(BindUniform, ShaderTypeDef, Clone)]
+pub struct IblMaps {
+  pub irradiance: Texture<TCubemap>,
+  pub prefiltered: Texture<TCubemap>,
+  pub brdf_lut: Texture,
+  pub max_reflection_lod: f32,
+}
+
+impl IblMaps {
+  // GLSL for ambient lighting from an Ibl, added with ShaderBuilder::with_def. Add the
+  // result to the direct lighting from cook_torrance.
+  pub const IBL_DEF: &'static str = r#"
+vec3 fresnel_schlick_roughness(float cos_theta, vec3 F0, float roughness) {
+  return F0 + (max(vec3(1.0 - roughness), F0) - F0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+vec3 ibl_ambient(
+  IblMaps ibl, vec3 N, vec3 V, vec3 albedo, float metallic, float roughness, float ao
+) {
+  float n_dot_v = max(dot(N, V), 0.0);
+  vec3 F0 = mix(vec3(0.04), albedo, metallic);
+  vec3 F = fresnel_schlick_roughness(n_dot_v, F0, roughness);
+  vec3 kD = (1.0 - F) * (1.0 - metallic);
+
+  vec3 diffuse = texture(ibl.irradiance, N).rgb * albedo;
+
+  vec3 R = reflect(-V, N);
+  vec3 prefiltered = textureLod(ibl.prefiltered, R, roughness * ibl.max_reflection_lod).rgb;
+  vec2 brdf = texture(ibl.brdf_lut, vec2(n_dot_v, roughness)).rg;
+  vec3 specular = prefiltered * (F * brdf.x + brdf.y);
+
+  return (kD * diffuse + specular) * ao;
+}
+"#;
+}
+
+// Bake the irradiance, prefiltered, and BRDF maps from environment, freeing any maps that
+// were already baked if a later one fails. Leaves the viewport and framebuffer changed.
+unsafe fn bake_maps(
+  gl: &Context,
+  environment: &Texture<TCubemap>,
+  irradiance_shader: &Shader,
+  prefilter_shader: &Shader,
+  brdf_shader: &Shader,
+) -> Result<(Texture<TCubemap>, Texture<TCubemap>, Texture)> {
+  let cube = Geometry::Cube {
+    length: 2.,
+    width: 2.,
+    height: 2.,
+  }
+  .to_mesh(gl, None)?;
+  cube_render_state().apply(gl);
+
+  let irradiance = cubemap_texture(gl, IRRADIANCE_SIZE, false).and_then(|irradiance| {
+    let mut shader = irradiance_shader.activate(gl);
+    shader.bind_uniform(gl, "environment", environment);
+    let result = render_to_cubemap(gl, &cube, &mut shader, &irradiance, IRRADIANCE_SIZE, 0);
+    dispose_on_error(gl, result, irradiance)
+  });
+
+  // Each mip level of the prefiltered map holds the environment at a higher roughness
+  let prefiltered = cubemap_texture(gl, PREFILTERED_SIZE, true).and_then(|prefiltered| {
+    prefiltered.generate_mipmaps(gl);
+    let mut shader = prefilter_shader.activate(gl);
+    shader.bind_uniform(gl, "environment", environment);
+    shader.bind_uniform(gl, "environment_size", &(ENVIRONMENT_SIZE as f32));
+    let result = (0..PREFILTERED_MIPS).try_for_each(|mip| {
+      let roughness = mip as f32 / (PREFILTERED_MIPS - 1) as f32;
+      shader.bind_uniform(gl, "roughness", &roughness);
+      let size = PREFILTERED_SIZE >> mip;
+      render_to_cubemap(gl, &cube, &mut shader, &prefiltered, size, mip as i32)
+    });
+    dispose_on_error(gl, result, prefiltered)
+  });
+  cube.dispose(gl);
+
+  let brdf_lut = bake_brdf_lut(gl, brdf_shader);
+
+  match (irradiance, prefiltered, brdf_lut) {
+    (Ok(irradiance), Ok(prefiltered), Ok(brdf_lut)) => Ok((irradiance, prefiltered, brdf_lut)),
+    (irradiance, prefiltered, brdf_lut) => {
+      let mut error = None;
+      for map in vec![irradiance, prefiltered] {
+        match map {
+          Ok(map) => map.dispose(gl),
+          Err(err) => {
+            error.get_or_insert(err);
+          }
+        }
+      }
+      match brdf_lut {
+        Ok(brdf_lut) => brdf_lut.dispose(gl),
+        Err(err) => {
+          error.get_or_insert(err);
+        }
+      }
+      Err(error.unwrap())
+    }
+  }
+}
+
+// The BRDF lookup table doesn't depend on the environment at all
+unsafe fn bake_brdf_lut(gl: &Context, brdf_shader: &Shader) -> Result<Texture> {
+  let brdf_lut = TextureBuilder::new(gl)
+    .with_format(glow::RG)
+    .with_internal_format(glow::RG16F)
+    .with_tex_parameter(glow::TEXTURE_MIN_FILTER, glow::LINEAR)
+    .with_tex_parameter(glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE)
+    .with_tex_parameter(glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE)
+    .render_texture(BRDF_LUT_SIZE, BRDF_LUT_SIZE)?;
+  let framebuffer = match Framebuffer::empty(gl, BRDF_LUT_SIZE, BRDF_LUT_SIZE) {
+    Ok(framebuffer) => framebuffer,
+    Err(err) => return dispose_on_error(gl, Err(err), brdf_lut),
+  };
+
+  framebuffer.attach_texture(gl, glow::COLOR_ATTACHMENT0, &brdf_lut);
+  let result = framebuffer.check_completeness(gl).and_then(|()| {
+    gl.viewport(0, 0, BRDF_LUT_SIZE as i32, BRDF_LUT_SIZE as i32);
+    let quad = ScreenQuad::new(gl)?;
+    quad.draw(gl, &mut brdf_shader.activate(gl));
+    quad.dispose(gl);
+    Ok(())
+  });
+  framebuffer.dispose(gl);
+
+  dispose_on_error(gl, result, brdf_lut)
+}
+
+// Hand back texture if result is Ok, or free it and return the error
+unsafe fn dispose_on_error<Target: TextureTarget>(
+  gl: &Context,
+  result: Result<()>,
+  texture: Texture<Target>,
+) -> Result<Texture<Target>> {
+  match result {
+    Ok(()) => Ok(texture),
+    Err(err) => {
+      texture.dispose(gl);
+      Err(err)
+    }
+  }
+}
+
+// Project an equirectangular environment, e.g. from TextureBuilder::load_hdr, onto the
+// faces of a mipmapped RGBA16F cubemap. The caller is responsible for restoring the
+// viewport afterwards.
+pub async unsafe fn equirect_to_cubemap(
+  gl: &Context,
+  equirect: &Texture,
+  size: u32,
+) -> Result<Texture<TCubemap>> {
+  let shader = load_shader(
+    gl,
+    "assets/shaders/cubemap.vert",
+    "assets/shaders/equirect_to_cubemap.frag",
+    &[],
+  )
+  .await?;
+  let cube = Geometry::Cube {
+    length: 2.,
+    width: 2.,
+    height: 2.,
+  }
+  .to_mesh(gl, None);
+  let cube = match cube {
+    Ok(cube) => cube,
+    Err(err) => {
+      shader.dispose(gl);
+      return Err(err);
+    }
+  };
+  cube_render_state().apply(gl);
+
+  let cubemap = cubemap_texture(gl, size, true).and_then(|cubemap| {
+    let mut active = shader.activate(gl);
+    active.bind_uniform(gl, "equirectangular_map", equirect);
+    let result = render_to_cubemap(gl, &cube, &mut active, &cubemap, size, 0);
+    cubemap.generate_mipmaps(gl);
+    dispose_on_error(gl, result, cubemap)
+  });
+
+  cube.dispose(gl);
+  shader.dispose(gl);
+  RenderState::default().apply(gl);
+
+  cubemap
+}
+
+// We look at the cube from the inside, and nothing overlaps, so draw every face as-is
+pub fn cube_render_state() -> RenderState {
+  RenderState {
+    depth_test: false,
+    cull_face: None,
+    ..Default::default()
+  }
+}
+
+// The baking shaders sample the GGX distribution and use PBR_PI
+const PBR_DEFS: &[&str] = &[PbrMaterial::PBR_DEF];
+
+async unsafe fn load_shader(
+  gl: &Context,
+  vertex_path: &str,
+  fragment_path: &str,
+  defs: &[&'static str],
+) -> Result<Shader> {
+  Ok(Shader::load_with_defs(gl, vertex_path, fragment_path, None, defs).await?)
+}
+
+// RGBA16F since WebGL can't render into three-channel float textures
+pub unsafe fn cubemap_texture(
+  gl: &Context,
+  size: u32,
+  mipmapped: bool,
+) -> Result<Texture<TCubemap>> {
+  let min_filter = if mipmapped {
+    glow::LINEAR_MIPMAP_LINEAR
+  } else {
+    glow::LINEAR
+  };
+  TextureBuilder::new(gl)
+    .as_cubemap()
+    .with_format(glow::RGBA)
+    .with_internal_format(glow::RGBA16F)
+    .with_tex_parameter(glow::TEXTURE_MIN_FILTER, min_filter)
+    .with_tex_parameter(glow::TEXTURE_MAG_FILTER, glow::LINEAR)
+    .with_tex_parameter(glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE)
+    .with_tex_parameter(glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE)
+    .with_tex_parameter(glow::TEXTURE_WRAP_R, glow::CLAMP_TO_EDGE)
+    .render_cubemap(size)
+}
+
+// Draw the cube once for each face of target, looking out from the center through that
+// face. The shader gets cubemap_projection and cubemap_view as uniforms, as in
+// cubemap.vert. The previously bound framebuffer is bound again afterwards.
+pub unsafe fn render_to_cubemap(
+  gl: &Context,
+  cube: &Mesh,
+  shader: &mut ActiveShader,
+  target: &Texture<TCubemap>,
+  size: u32,
+  mip: i32,
+) -> Result<()> {
+  let origin = glm::zero::<Vec3>();
+  let views = [
+    (glm::vec3(1., 0., 0.), glm::vec3(0., -1., 0.)),
+    (glm::vec3(-1., 0., 0.), glm::vec3(0., -1., 0.)),
+    (glm::vec3(0., 1., 0.), glm::vec3(0., 0., 1.)),
+    (glm::vec3(0., -1., 0.), glm::vec3(0., 0., -1.)),
+    (glm::vec3(0., 0., 1.), glm::vec3(0., -1., 0.)),
+    (glm::vec3(0., 0., -1.), glm::vec3(0., -1., 0.)),
+  ];
+  let projection = glm::perspective(1., 90f32.to_radians(), 0.1, 10.);
+  shader.bind_uniform(gl, "cubemap_projection", &projection);
+
+  let framebuffer = Framebuffer::empty(gl, size, size)?;
+  framebuffer.bind(gl);
+  gl.viewport(0, 0, size as i32, size as i32);
+  let result = views
+    .iter()
+    .enumerate()
+    .try_for_each(|(i, (direction, up))| {
+      gl.framebuffer_texture_2d(
+        glow::FRAMEBUFFER,
+        glow::COLOR_ATTACHMENT0,
+        glow::TEXTURE_CUBE_MAP_POSITIVE_X + i as u32,
+        Some(target.texture),
+        mip,
+      );
+      if i == 0 {
+        framebuffer.check_completeness(gl)?;
+      }
+
+      shader.bind_uniform(gl, "cubemap_view", &glm::look_at(&origin, direction, up));
+      gl.clear(glow::COLOR_BUFFER_BIT);
+      cube.draw(gl, shader);
+      Ok(())
+    });
+  gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+  framebuffer.dispose(gl);
+
+  result
+}
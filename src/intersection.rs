@@ -0,0 +1,63 @@
+use crate::{
+  math::{self, Aabb, Ray},
+  prelude::*,
+};
+
+// Intersection tests on raw vectors, e.g. for mouse picking against a Camera::screen_ray.
+// The returned t is in units of dir, so the hit point is origin + t * dir. To pick, test
+// every object's box first and only run the exact mesh test on the boxes that were hit.
+// Ray::new normalizes the direction, so hits are divided by its length to get back to
+// units of dir.
+
+// Parameter of the first hit of the ray with the box, or 0 if the ray starts inside it
+pub fn ray_aabb_intersection(origin: Vec3, dir: Vec3, min: Vec3, max: Vec3) -> Option<f32> {
+  let t = math::ray_aabb(&Ray::new(origin, dir), &Aabb::new(min, max))?;
+  Some(t / dir.norm())
+}
+
+// Parameter of the nearest hit of the ray with any triangle of an indexed mesh
+pub fn ray_mesh_intersection(
+  origin: Vec3,
+  dir: Vec3,
+  vertices: &[Vec3],
+  indices: &[u32],
+) -> Option<f32> {
+  let ray = Ray::new(origin, dir);
+  let nearest = indices
+    .chunks_exact(3)
+    .filter_map(|tri| {
+      math::ray_triangle(
+        &ray,
+        &vertices[tri[0] as usize],
+        &vertices[tri[1] as usize],
+        &vertices[tri[2] as usize],
+      )
+    })
+    .fold(None, |nearest: Option<f32>, t| {
+      Some(nearest.map_or(t, |nearest| nearest.min(t)))
+    })?;
+  Some(nearest / dir.norm())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn hits_are_in_units_of_dir() {
+    let origin = glm::vec3(0., 0., 5.);
+    let dir = glm::vec3(0., 0., -2.);
+    let min = glm::vec3(-1., -1., -1.);
+    let max = glm::vec3(1., 1., 1.);
+    let t = ray_aabb_intersection(origin, dir, min, max).unwrap();
+    assert!((t - 2.).abs() < 1e-5, "{}", t);
+
+    let vertices = [
+      glm::vec3(-1., -1., 0.),
+      glm::vec3(1., -1., 0.),
+      glm::vec3(0., 1., 0.),
+    ];
+    let t = ray_mesh_intersection(origin, dir, &vertices, &[0, 1, 2]).unwrap();
+    assert!((t - 2.5).abs() < 1e-5, "{}", t);
+  }
+}
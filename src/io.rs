@@ -1,5 +1,8 @@
 use image::DynamicImage;
-use std::{io, path::Path};
+use std::{
+  io,
+  path::{Path, PathBuf},
+};
 
 #[cfg(target_arch = "wasm32")]
 fn js_error(value: wasm_bindgen::JsValue) -> anyhow::Error {
@@ -44,6 +47,15 @@ pub async fn load_file(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
   };
 }
 
+// Like load_file, but the error says which path failed to load
+pub async fn load_bytes(path: impl AsRef<Path>) -> anyhow::Result<Vec<u8>> {
+  use anyhow::Context;
+  let path = path.as_ref();
+  load_file(path)
+    .await
+    .with_context(|| format!("Failed to load {}", path.display()))
+}
+
 pub async fn load_image(path: impl AsRef<Path>) -> anyhow::Result<DynamicImage> {
   let path = path.as_ref();
 
@@ -92,7 +104,7 @@ pub async fn load_image(path: impl AsRef<Path>) -> anyhow::Result<DynamicImage>
     use image::io::Reader;
     use std::io::Cursor;
 
-    let bytes = load_file(path).await?;
+    let bytes = load_bytes(path).await?;
     let format = image::guess_format(bytes.as_slice())?;
     let mut img_reader = Reader::new(Cursor::new(bytes));
     img_reader.set_format(format);
@@ -100,7 +112,49 @@ pub async fn load_image(path: impl AsRef<Path>) -> anyhow::Result<DynamicImage>
   }
 }
 
+// A decoded Radiance .hdr image: linear RGB floats, three per pixel, with rows from the top
+pub struct HdrImage {
+  pub width: u32,
+  pub height: u32,
+  pub pixels: Vec<f32>,
+}
+
+pub async fn load_hdr(path: impl AsRef<Path>) -> anyhow::Result<HdrImage> {
+  use anyhow::Context;
+  use image::codecs::hdr::HdrDecoder;
+  let path = path.as_ref();
+
+  // The image crate doesn't decode OpenEXR yet, so .exr files need converting to .hdr
+  match path.extension().and_then(|ext| ext.to_str()) {
+    Some("hdr") => {}
+    Some("exr") => anyhow::bail!("OpenEXR is not supported, convert {} to .hdr", path.display()),
+    _ => anyhow::bail!("{} is not a .hdr image", path.display()),
+  }
+
+  let bytes = load_bytes(path).await?;
+  let decoder = HdrDecoder::new(bytes.as_slice())
+    .with_context(|| format!("Failed to decode {}", path.display()))?;
+  let metadata = decoder.metadata();
+  let pixels = decoder
+    .read_image_hdr()?
+    .iter()
+    .flat_map(|pixel| pixel.0.to_vec())
+    .collect();
+
+  Ok(HdrImage {
+    width: metadata.width,
+    height: metadata.height,
+    pixels,
+  })
+}
+
 pub async fn load_string(path: impl AsRef<Path>) -> anyhow::Result<String> {
-  let bytes = load_file(path).await?;
+  let bytes = load_bytes(path).await?;
   Ok(String::from_utf8(bytes)?)
 }
+
+// Load several text files concurrently. On the web each load is a separate fetch, so this
+// is much faster than awaiting them one after another. Results are in the same order as paths.
+pub async fn load_all(paths: Vec<PathBuf>) -> anyhow::Result<Vec<String>> {
+  futures::future::try_join_all(paths.iter().map(load_string)).await
+}
@@ -9,6 +9,27 @@ pub struct DirLight {
   pub specular: Vec3,
 }
 
+impl DirLight {
+  // Orthographic view-projection matrix looking along the light's direction, covering a
+  // box of half-width `extent` around `center`. Directional lights have no position, so we
+  // back the eye up from the center far enough that the whole box is in front of it.
+  pub fn shadow_view_proj(&self, center: Vec3, extent: f32, near: f32, far: f32) -> Mat4 {
+    let direction = glm::normalize(&self.direction);
+    let eye = center - direction * (far - near) / 2.;
+
+    // look_at degenerates if the light points straight up or down
+    let up = if direction.x.abs() < 1e-4 && direction.z.abs() < 1e-4 {
+      glm::vec3(0., 0., 1.)
+    } else {
+      glm::vec3(0., 1., 0.)
+    };
+
+    let view = glm::look_at(&eye, &center, &up);
+    let projection = glm::ortho(-extent, extent, -extent, extent, near, far);
+    projection * view
+  }
+}
+
 #[derive(BindUniform, ShaderTypeDef)]
 pub struct PointLight {
   pub position: Vec3,
@@ -1,7 +1,14 @@
 #![feature(const_fn)] // needed for std140 crate
 #![allow(dead_code)]
 
-use crate::{camera::Camera, prelude::*, scene::Scene, user_inputs::UserInputs, window::Window};
+use crate::{
+  camera::Camera,
+  prelude::*,
+  render_state::{ClearFlags, RenderState},
+  scene::Scene,
+  user_inputs::UserInputs,
+  window::{FrameTimer, Window},
+};
 use instant::Instant;
 use screen_capture::ScreenCapture;
 #[cfg(target_arch = "wasm32")]
@@ -13,27 +20,72 @@ use winit::{
   window::WindowBuilder,
 };
 
+mod animation;
+mod bloom;
 mod camera;
+mod capabilities;
+mod cascaded_shadow_map;
+mod context;
+mod debug;
+mod debug_draw;
+mod depth_of_field;
+mod depth_peeling;
+mod framebuffer;
+mod fxaa;
+mod gamma;
+mod gbuffer;
 mod geometry;
+mod gltf;
+#[cfg(not(target_arch = "wasm32"))]
+mod hot_reload;
+mod ibl;
+mod intersection;
 mod io;
 mod light;
 mod material;
+mod math;
 mod mesh;
 mod model;
+#[cfg(not(target_arch = "wasm32"))]
+mod normals;
+mod overlay;
+mod particles;
+mod pbr;
+mod point_shadow;
+mod post_process;
 mod prelude;
+mod query;
+mod render_state;
 mod scene;
 mod screen_capture;
 mod shader;
+mod shader_cache;
+mod shadow_map;
+mod sky;
+#[cfg(feature = "spirv-reflect")]
+mod spirv_uniforms;
+mod ssao;
+mod ssr;
+mod storage_buffer;
 mod text;
 mod texture;
+mod tonemap;
+mod transform;
+mod transform_feedback;
 mod user_inputs;
+mod viewport;
+mod volumetric_fog;
 mod window;
+mod world;
 
 struct State {
   scene: Scene,
   camera: Camera,
   user_inputs: UserInputs,
   shader_effect: i32,
+  screen_capture: ScreenCapture,
+  width: u32,
+  height: u32,
 
   start: Instant,
   last_tick: Instant,
@@ -47,6 +99,21 @@ impl State {
   pub fn dt(&self) -> f32 {
     self.last_tick.elapsed().as_nanos() as f32 / 1e9
   }
+
+  // Keep everything sized to the window consistent with it
+  pub unsafe fn on_resize(&mut self, gl: &Context, width: u32, height: u32) -> Result<()> {
+    // Minimized windows report a size of zero, which would make for an invalid framebuffer
+    if width == 0 || height == 0 {
+      return Ok(());
+    }
+
+    gl.viewport(0, 0, width as i32, height as i32);
+    self.camera.set_aspect_ratio(width as f32 / height as f32);
+    self.screen_capture.resize(gl, width, height)?;
+    self.width = width;
+    self.height = height;
+    Ok(())
+  }
 }
 
 fn lock_cursor(window: &Window) {
@@ -54,14 +121,16 @@ fn lock_cursor(window: &Window) {
   window.winit().set_cursor_grab(true).unwrap();
 }
 
+// Frame rate cap when vsync is off
 const DRAW_RATE: f32 = 60.;
+const VSYNC: bool = true;
 
 unsafe fn run_event_loop(
   gl: Context,
   event_loop: EventLoop<()>,
   window: Window,
   mut state: State,
-  draw: impl Fn(&Context, &mut State) + 'static,
+  draw: impl Fn(&Context, &mut State, f32) + 'static,
   update: impl Fn(&mut State, Event<()>, bool) + 'static,
 ) {
   #[cfg(target_arch = "wasm32")]
@@ -71,6 +140,7 @@ unsafe fn run_event_loop(
 
   // Event loop
   let mut last_draw = Instant::now();
+  let mut frame_timer = FrameTimer::new();
   event_loop.run(move |event, _, control_flow| {
     // Poll means the loop will return continually to check for events rather than listening to
     // a cvar or something
@@ -83,14 +153,20 @@ unsafe fn run_event_loop(
 
       // Draw to the screen when requested
       Event::RedrawRequested(_) => {
-        draw(&gl, &mut state);
+        draw(&gl, &mut state, frame_timer.tick());
         window.swap_buffers();
       }
 
+      // With vsync, ask for a redraw once all events are handled. Drawing is then paced by
+      // swap_buffers blocking on native, and by requestAnimationFrame on the web.
+      Event::MainEventsCleared if window.vsync() => {
+        window.winit().request_redraw();
+      }
+
       Event::WindowEvent { ref event, .. } => match event {
-        // Resize OpenGL viewport when window is resized
+        // Resize OpenGL viewport and window-sized render targets when window is resized
         WindowEvent::Resized(size) => {
-          gl.viewport(0, 0, size.width as i32, size.height as i32);
+          state.on_resize(&gl, size.width, size.height).unwrap();
         }
 
         // Exit loop when CloseRequested raised
@@ -127,8 +203,8 @@ unsafe fn run_event_loop(
     };
 
     let since_last_draw = last_draw.elapsed().as_nanos() as f32 / 1e9;
-    if since_last_draw > 1. / DRAW_RATE {
-      draw(&gl, &mut state);
+    if !window.vsync() && since_last_draw > 1. / DRAW_RATE {
+      draw(&gl, &mut state, frame_timer.tick());
       window.swap_buffers();
       last_draw = Instant::now();
     }
@@ -146,7 +222,7 @@ async fn run() -> anyhow::Result<()> {
     let event_loop = EventLoop::new();
 
     // Build platform-specific window and OpenGL context
-    let (window, gl) = Window::build(wb, &event_loop);
+    let (window, gl) = Window::build(wb, &event_loop, VSYNC);
 
     // Native platforms let you immediately lock the mouse
     #[cfg(not(target_arch = "wasm32"))]
@@ -164,23 +240,17 @@ async fn run() -> anyhow::Result<()> {
     // Set camera parameters
     let camera = Camera::new(
       glm::vec3(0.5, 1.5, 5.),
-      glm::perspective(
-        width as f32 / height as f32,
-        (45f32).to_radians(),
-        0.1,
-        100.,
-      ),
       glm::zero(),
+      (45f32).to_radians(),
+      width as f32 / height as f32,
     );
 
     // Turn on OpenGL features
-    gl.enable(glow::DEPTH_TEST);
+    RenderState::default().apply(&gl);
     gl.enable(glow::STENCIL_TEST);
-    gl.enable(glow::BLEND);
-    gl.enable(glow::CULL_FACE);
-
     gl.depth_func(glow::LEQUAL);
-    gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+
+    let screen_capture = ScreenCapture::new(&gl, width, height).await?;
 
     // Build monotlithic state object
     let state = State {
@@ -190,26 +260,33 @@ async fn run() -> anyhow::Result<()> {
       start: Instant::now(),
       last_tick: Instant::now(),
       shader_effect: 0,
+      screen_capture,
+      width,
+      height,
     };
 
-    let screen_capture = ScreenCapture::new(&gl, width, height).await?;
-
-    let draw = move |gl: &Context, state: &mut State| {
-      screen_capture.record(gl);
+    let draw = move |gl: &Context, state: &mut State, _dt: f32| {
+      state.screen_capture.record(gl);
 
       // Clear the screen with a default color
-      gl.clear_color(0.1, 0.1, 0.1, 1.0);
-      gl.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT | glow::STENCIL_BUFFER_BIT);
-      gl.enable(glow::DEPTH_TEST);
+      render_state::clear(gl, glm::vec4(0.1, 0.1, 0.1, 1.0), ClearFlags::ALL);
+      RenderState::default().apply(gl);
 
       // Draw the scene
       state
         .scene
-        .draw(gl, &state.camera, state.elapsed(), width, height)
+        .draw(
+          gl,
+          &state.camera,
+          state.elapsed(),
+          state.width,
+          state.height,
+        )
         .unwrap();
 
-      screen_capture.replay(gl, |gl, shader| {
-        shader.bind_uniform(gl, "effect", &state.shader_effect);
+      let shader_effect = state.shader_effect;
+      state.screen_capture.replay(gl, |gl, shader| {
+        shader.bind_uniform(gl, "effect", &shader_effect);
       });
     };
 
@@ -1,4 +1,4 @@
-use crate::{prelude::*, texture::Texture};
+use crate::{prelude::*, shader::ActiveShader, texture::Texture};
 
 #[derive(BindUniform, ShaderTypeDef, Clone)]
 pub struct Material {
@@ -6,3 +6,181 @@ pub struct Material {
   pub specular: Texture,
   pub shininess: f32,
 }
+
+// Physically based alternative to Material, using the metallic-roughness model from glTF.
+// Each texture is optional, and the shader falls back to the constant factor when it's
+// missing (see pbr_sample and cook_torrance in PBR_DEF).
+#[derive(BindUniform, ShaderTypeDef, Clone)]
+pub struct PbrMaterial {
+  pub albedo: Vec4,
+  pub metallic: f32,
+  pub roughness: f32,
+  pub ao: f32,
+  pub emissive: Vec3,
+  pub albedo_map: Option<Texture>,
+  // Roughness in the green channel and metalness in the blue channel, as in glTF
+  pub metallic_roughness_map: Option<Texture>,
+  pub normal_map: Option<Texture>,
+  pub ao_map: Option<Texture>,
+}
+
+impl Default for PbrMaterial {
+  fn default() -> Self {
+    PbrMaterial {
+      albedo: glm::vec4(1., 1., 1., 1.),
+      metallic: 0.,
+      roughness: 0.5,
+      ao: 1.,
+      emissive: glm::zero(),
+      albedo_map: None,
+      metallic_roughness_map: None,
+      normal_map: None,
+      ao_map: None,
+    }
+  }
+}
+
+impl PbrMaterial {
+  // GLSL for Cook-Torrance lighting, added with ShaderBuilder::with_def. The constant is
+  // PBR_PI rather than PI so it doesn't clash with shaders that define PI.
+  pub const PBR_DEF: &'static str = r#"
+const float PBR_PI = 3.14159265359;
+
+// Combine a material's factors with its textures at a texture coordinate
+void pbr_sample(
+  PbrMaterial material, vec2 uv,
+  out vec3 albedo, out float metallic, out float roughness, out float ao
+) {
+  vec4 base = material.albedo;
+  if (material.has_albedo_map) {
+    base *= texture(material.albedo_map, uv);
+  }
+  albedo = base.rgb;
+
+  metallic = material.metallic;
+  roughness = material.roughness;
+  if (material.has_metallic_roughness_map) {
+    vec4 mr = texture(material.metallic_roughness_map, uv);
+    roughness *= mr.g;
+    metallic *= mr.b;
+  }
+
+  ao = material.ao;
+  if (material.has_ao_map) {
+    ao *= texture(material.ao_map, uv).r;
+  }
+}
+
+// Trowbridge-Reitz GGX normal distribution: how many microfacets face along H
+float distribution_ggx(vec3 N, vec3 H, float roughness) {
+  float a = roughness * roughness;
+  float a2 = a * a;
+  float n_dot_h = max(dot(N, H), 0.0);
+  float denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+  return a2 / (PBR_PI * denom * denom);
+}
+
+// Smith's method with Schlick-GGX: how much microfacets shadow each other
+float geometry_schlick_ggx(float n_dot_v, float roughness) {
+  float r = roughness + 1.0;
+  float k = (r * r) / 8.0;
+  return n_dot_v / (n_dot_v * (1.0 - k) + k);
+}
+
+float geometry_smith(vec3 N, vec3 V, vec3 L, float roughness) {
+  return geometry_schlick_ggx(max(dot(N, V), 0.0), roughness)
+    * geometry_schlick_ggx(max(dot(N, L), 0.0), roughness);
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 F0) {
+  return F0 + (1.0 - F0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Outgoing radiance toward V from one light arriving along L with the given radiance.
+// N, V and L must be normalized.
+vec3 cook_torrance(
+  vec3 N, vec3 V, vec3 L, vec3 radiance, vec3 albedo, float metallic, float roughness
+) {
+  vec3 H = normalize(V + L);
+
+  // Dielectrics reflect about 4% at normal incidence, metals reflect their albedo
+  vec3 F0 = mix(vec3(0.04), albedo, metallic);
+
+  float NDF = distribution_ggx(N, H, roughness);
+  float G = geometry_smith(N, V, L, roughness);
+  vec3 F = fresnel_schlick(max(dot(H, V), 0.0), F0);
+
+  float n_dot_l = max(dot(N, L), 0.0);
+  vec3 specular = (NDF * G * F) / (4.0 * max(dot(N, V), 0.0) * n_dot_l + 0.0001);
+
+  // Energy that isn't reflected is refracted, and metals absorb all of it
+  vec3 kD = (vec3(1.0) - F) * (1.0 - metallic);
+
+  return (kD * albedo / PBR_PI + specular) * radiance * n_dot_l;
+}
+"#;
+}
+
+// Material for parallax occlusion mapping, which offsets texture coordinates along the view
+// direction so a flat surface looks like it has depth. The height map's red channel is the
+// surface height, from 0 (deepest) to 1 (flush with the geometry), and parallax_scale is
+// how deep 0 is in texture space.
+#[derive(BindUniform, ShaderTypeDef, Clone)]
+pub struct ParallaxMaterial {
+  pub albedo: Texture,
+  pub normal: Texture,
+  pub height: Texture,
+  pub parallax_scale: f32,
+}
+
+impl ParallaxMaterial {
+  // Bind the material as `parallax_material`. Its textures take three consecutive slots.
+  pub unsafe fn bind(&self, gl: &Context, shader: &mut ActiveShader) {
+    shader.bind_uniform(gl, "parallax_material", self);
+  }
+
+  // GLSL for the texture coordinate lookup, added with ShaderBuilder::with_def. view_dir
+  // is the tangent-space direction from the fragment to the camera, so the surface normal
+  // is +Z.
+  pub const PARALLAX_DEF: &'static str = r#"
+// Below this cosine between the view and the normal, the ray march smears the texture, so
+// the coordinates are left as they are
+const float PARALLAX_MIN_VIEW_COS = 0.1;
+
+vec2 parallax_uv(ParallaxMaterial material, vec2 uv, vec3 view_dir) {
+  if (view_dir.z < PARALLAX_MIN_VIEW_COS) {
+    return uv;
+  }
+
+  // Take more steps at grazing angles, where the ray crosses more texels
+  float num_steps = mix(32.0, 8.0, view_dir.z);
+  float step_depth = 1.0 / num_steps;
+  vec2 step_uv = view_dir.xy / view_dir.z * material.parallax_scale * step_depth;
+
+  // March into the surface until the ray first passes below the height field
+  float ray_depth = 0.0;
+  float surface_depth = 1.0 - texture(material.height, uv).r;
+  while (ray_depth < surface_depth && ray_depth < 1.0) {
+    uv -= step_uv;
+    ray_depth += step_depth;
+    surface_depth = 1.0 - texture(material.height, uv).r;
+  }
+
+  // Then binary search the last step for the intersection
+  for (int i = 0; i < 5; i++) {
+    step_uv *= 0.5;
+    step_depth *= 0.5;
+    if (ray_depth < surface_depth) {
+      uv -= step_uv;
+      ray_depth += step_depth;
+    } else {
+      uv += step_uv;
+      ray_depth -= step_depth;
+    }
+    surface_depth = 1.0 - texture(material.height, uv).r;
+  }
+
+  return uv;
+}
+"#;
+}
@@ -0,0 +1,221 @@
+use crate::prelude::*;
+
+// Half-line starting at origin. Direction is kept normalized so distances along the ray
+// are in world units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+  pub origin: Vec3,
+  pub direction: Vec3,
+}
+
+impl Ray {
+  pub fn new(origin: Vec3, direction: Vec3) -> Self {
+    Ray {
+      origin,
+      direction: glm::normalize(&direction),
+    }
+  }
+
+  pub fn at(&self, t: f32) -> Vec3 {
+    self.origin + self.direction * t
+  }
+}
+
+// Axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+  pub min: Vec3,
+  pub max: Vec3,
+}
+
+impl Aabb {
+  pub fn new(min: Vec3, max: Vec3) -> Self {
+    Aabb { min, max }
+  }
+
+  // Smallest box containing every point
+  pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Vec3>) -> Option<Self> {
+    let mut points = points.into_iter();
+    let first = *points.next()?;
+    Some(points.fold(Aabb::new(first, first), |aabb, point| Aabb {
+      min: glm::min2(&aabb.min, point),
+      max: glm::max2(&aabb.max, point),
+    }))
+  }
+
+  pub fn center(&self) -> Vec3 {
+    (self.min + self.max) / 2.
+  }
+
+  pub fn ray_intersect(&self, ray: &Ray) -> Option<f32> {
+    ray_aabb(ray, self)
+  }
+}
+
+// Distance along the ray to the nearest hit with the box, using the slab method: clip the
+// ray against each pair of parallel planes and check the resulting intervals overlap.
+// Returns 0 if the ray starts inside the box.
+pub fn ray_aabb(ray: &Ray, aabb: &Aabb) -> Option<f32> {
+  let mut t_min = 0f32;
+  let mut t_max = f32::INFINITY;
+
+  for axis in 0..3 {
+    // Division by a zero direction gives +-infinity, which the min/max below handle
+    let inv_dir = 1. / ray.direction[axis];
+    let t0 = (aabb.min[axis] - ray.origin[axis]) * inv_dir;
+    let t1 = (aabb.max[axis] - ray.origin[axis]) * inv_dir;
+    let (t0, t1) = if inv_dir < 0. { (t1, t0) } else { (t0, t1) };
+
+    t_min = t_min.max(t0);
+    t_max = t_max.min(t1);
+    if t_max < t_min {
+      return None;
+    }
+  }
+
+  Some(t_min)
+}
+
+// Distance along the ray to its hit with the triangle (a, b, c), using the Möller–Trumbore
+// algorithm. Both sides of the triangle count as hits.
+pub fn ray_triangle(ray: &Ray, a: &Vec3, b: &Vec3, c: &Vec3) -> Option<f32> {
+  const EPSILON: f32 = 1e-7;
+
+  let edge1 = b - a;
+  let edge2 = c - a;
+  let h = glm::cross(&ray.direction, &edge2);
+  let det = glm::dot(&edge1, &h);
+
+  // Ray is parallel to the triangle
+  if det.abs() < EPSILON {
+    return None;
+  }
+
+  // Barycentric coordinates (u, v) of the hit must lie inside the triangle
+  let inv_det = 1. / det;
+  let s = ray.origin - a;
+  let u = inv_det * glm::dot(&s, &h);
+  if !(0. ..=1.).contains(&u) {
+    return None;
+  }
+
+  let q = glm::cross(&s, &edge1);
+  let v = inv_det * glm::dot(&ray.direction, &q);
+  if v < 0. || u + v > 1. {
+    return None;
+  }
+
+  let t = inv_det * glm::dot(&edge2, &q);
+  if t > EPSILON {
+    Some(t)
+  } else {
+    None
+  }
+}
+
+// Small xorshift random number generator. Not statistically strong, but plenty for visual
+// noise like particle jitter or sample kernels, and needs no extra crates on the web.
+#[derive(Debug, Clone)]
+pub struct Rng {
+  state: u32,
+}
+
+impl Rng {
+  pub fn new(seed: u32) -> Self {
+    // Xorshift gets stuck at zero
+    Rng { state: seed.max(1) }
+  }
+
+  // Uniformly random number in [0, 1]
+  pub fn next_f32(&mut self) -> f32 {
+    let mut x = self.state;
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    self.state = x;
+    x as f32 / u32::MAX as f32
+  }
+
+  pub fn range(&mut self, min: f32, max: f32) -> f32 {
+    min + (max - min) * self.next_f32()
+  }
+}
+
+impl Default for Rng {
+  fn default() -> Self {
+    Rng::new(0x9E3779B9)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn unit_box() -> Aabb {
+    Aabb::new(glm::vec3(-1., -1., -1.), glm::vec3(1., 1., 1.))
+  }
+
+  #[test]
+  fn ray_aabb_hits_near_face() {
+    let ray = Ray::new(glm::vec3(0., 0., 5.), glm::vec3(0., 0., -1.));
+    assert_eq!(ray_aabb(&ray, &unit_box()), Some(4.));
+  }
+
+  #[test]
+  fn ray_aabb_misses_box_beside_ray() {
+    let ray = Ray::new(glm::vec3(3., 0., 5.), glm::vec3(0., 0., -1.));
+    assert_eq!(ray_aabb(&ray, &unit_box()), None);
+  }
+
+  #[test]
+  fn ray_aabb_misses_box_behind_ray() {
+    let ray = Ray::new(glm::vec3(0., 0., 5.), glm::vec3(0., 0., 1.));
+    assert_eq!(ray_aabb(&ray, &unit_box()), None);
+  }
+
+  #[test]
+  fn ray_aabb_inside_box_is_zero() {
+    let ray = Ray::new(glm::vec3(0., 0., 0.), glm::vec3(1., 1., 0.));
+    assert_eq!(ray_aabb(&ray, &unit_box()), Some(0.));
+  }
+
+  #[test]
+  fn ray_aabb_parallel_to_slab() {
+    // Zero direction components divide to infinity, which must not produce a hit
+    let ray = Ray::new(glm::vec3(0., 2., 5.), glm::vec3(0., 0., -1.));
+    assert_eq!(ray_aabb(&ray, &unit_box()), None);
+  }
+
+  #[test]
+  fn ray_triangle_hits_inside() {
+    let (a, b, c) = (
+      glm::vec3(-1., -1., 0.),
+      glm::vec3(1., -1., 0.),
+      glm::vec3(0., 1., 0.),
+    );
+    let ray = Ray::new(glm::vec3(0., 0., 2.), glm::vec3(0., 0., -1.));
+    let t = ray_triangle(&ray, &a, &b, &c).unwrap();
+    assert!((t - 2.).abs() < 1e-6);
+
+    // Back faces count too
+    let ray = Ray::new(glm::vec3(0., 0., -2.), glm::vec3(0., 0., 1.));
+    assert!(ray_triangle(&ray, &a, &b, &c).is_some());
+  }
+
+  #[test]
+  fn ray_triangle_misses_outside_and_parallel() {
+    let (a, b, c) = (
+      glm::vec3(-1., -1., 0.),
+      glm::vec3(1., -1., 0.),
+      glm::vec3(0., 1., 0.),
+    );
+    let outside = Ray::new(glm::vec3(1., 1., 2.), glm::vec3(0., 0., -1.));
+    assert_eq!(ray_triangle(&outside, &a, &b, &c), None);
+
+    let parallel = Ray::new(glm::vec3(0., 0., 2.), glm::vec3(1., 0., 0.));
+    assert_eq!(ray_triangle(&parallel, &a, &b, &c), None);
+
+    let behind = Ray::new(glm::vec3(0., 0., 2.), glm::vec3(0., 0., 1.));
+    assert_eq!(ray_triangle(&behind, &a, &b, &c), None);
+  }
+}
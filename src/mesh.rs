@@ -1,5 +1,22 @@
-use crate::{material::Material, model::Model, prelude::*, shader::ActiveShader};
-use std::mem::size_of;
+use crate::{
+  geometry::Geometry, material::Material, model::Model, prelude::*, shader::ActiveShader,
+  texture::Texture,
+};
+use std::{cell::Cell, mem::size_of};
+
+// Each morph target takes two vertex attributes (position and normal deltas) after the
+// first MORPH_ATTRIB_START, and GL only guarantees 16 attributes, so this is also the most
+// targets a mesh can have.
+pub const DEFAULT_MAX_MORPH_TARGETS: usize = 5;
+
+// Morph target i puts its position delta at this location + 2i, and its normal delta
+// right after. This comes after the bone attributes of skinning.vert and the tangent, so
+// a mesh can have all three.
+const MORPH_ATTRIB_START: u32 = 6;
+
+// Location of the per-vertex tangent from Mesh::set_tangents, right after the bone
+// attributes of skinning.vert
+const TANGENT_ATTRIB: u32 = 5;
 
 #[derive(Debug, Clone)]
 #[repr(C)]
@@ -22,6 +39,15 @@ impl Vertex {
   }
 }
 
+// Per-vertex skinning data for Mesh::set_skin: up to four bones and how much each one
+// moves the vertex. Weights should sum to 1.
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct SkinVertex {
+  pub bone_indices: [i32; 4],
+  pub bone_weights: Vec4,
+}
+
 #[derive(Clone)]
 pub struct Mesh {
   pub vertices: Vec<Vertex>,
@@ -31,6 +57,18 @@ pub struct Mesh {
   vao: GlVertexArray,
   vbo: GlBuffer,
   ebo: GlBuffer,
+
+  // Index buffer of triangle edges, for drawing wireframes where polygon_mode is missing.
+  // Built the first time the mesh is drawn as a wireframe.
+  edge_ebo: Cell<Option<GlBuffer>>,
+
+  // One buffer of interleaved position and normal deltas per morph target
+  morph_vbos: Vec<GlBuffer>,
+  morph_weights: Vec<f32>,
+  max_morph_targets: usize,
+
+  skin_vbo: Option<GlBuffer>,
+  tangent_vbo: Option<GlBuffer>,
 }
 
 impl Mesh {
@@ -87,13 +125,188 @@ impl Mesh {
       vao,
       ebo,
       vbo,
+      edge_ebo: Cell::new(None),
+      morph_vbos: vec![],
+      morph_weights: vec![],
+      max_morph_targets: DEFAULT_MAX_MORPH_TARGETS,
+      skin_vbo: None,
+      tangent_vbo: None,
     })
   }
 
+  // Unit cube centered on the origin
+  pub unsafe fn cube(gl: &Context) -> Result<Mesh> {
+    Geometry::Cube {
+      length: 1.,
+      width: 1.,
+      height: 1.,
+    }
+    .to_mesh(gl, None)
+  }
+
+  // Unit sphere centered on the origin, see Geometry::Sphere
+  pub unsafe fn uv_sphere(gl: &Context, rings: u32, sectors: u32) -> Result<Mesh> {
+    Geometry::Sphere {
+      radius: 1.,
+      rings,
+      sectors,
+    }
+    .to_mesh(gl, None)
+  }
+
+  // Unit square on the XZ plane facing +Y, visible from both sides
+  pub unsafe fn plane(gl: &Context) -> Result<Mesh> {
+    Geometry::Plane {
+      length: 1.,
+      width: 1.,
+      normal: glm::vec3(0., 1., 0.),
+    }
+    .to_mesh(gl, None)
+  }
+
+  // Cylinder of radius 1 and height 2 along Y, centered on the origin
+  pub unsafe fn cylinder(gl: &Context, sectors: u32) -> Result<Mesh> {
+    Geometry::Cylinder {
+      radius: 1.,
+      height: 2.,
+      sectors,
+    }
+    .to_mesh(gl, None)
+  }
+
+  // Lower the number of morph targets, e.g. to save vertex attributes. Shaders using morph
+  // targets need MAX_MORPH_TARGETS defined to the same value. There's no room for more
+  // than DEFAULT_MAX_MORPH_TARGETS, so larger values are clamped to it.
+  pub fn with_max_morph_targets(mut self, max_morph_targets: usize) -> Self {
+    self.max_morph_targets = max_morph_targets.min(DEFAULT_MAX_MORPH_TARGETS);
+    self
+  }
+
+  // Add a blend shape, given as a position delta then a normal delta (6 floats) for each
+  // vertex. Returns the target's index into the weights of set_morph_weights.
+  pub unsafe fn add_morph_target(&mut self, gl: &Context, deltas: &[f32]) -> Result<usize> {
+    let index = self.morph_vbos.len();
+    if index >= self.max_morph_targets {
+      bail!(
+        "Mesh already has the maximum of {} morph targets",
+        self.max_morph_targets
+      );
+    }
+    if deltas.len() != self.vertices.len() * 6 {
+      bail!(
+        "Expected {} morph target deltas for {} vertices, got {}",
+        self.vertices.len() * 6,
+        self.vertices.len(),
+        deltas.len()
+      );
+    }
+
+    let location = MORPH_ATTRIB_START + 2 * index as u32;
+    let vbo = gl.create_buffer().map_err(Error::msg)?;
+    gl.bind_vertex_array(Some(self.vao));
+    gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
+    let (_, deltas_bytes, _) = deltas.align_to::<u8>();
+    gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, deltas_bytes, glow::STATIC_DRAW);
+
+    let size_f32 = size_of::<f32>() as i32;
+    for (i, attrib) in [location, location + 1].iter().enumerate() {
+      gl.enable_vertex_attrib_array(*attrib);
+      gl.vertex_attrib_pointer_f32(
+        *attrib,
+        3,
+        glow::FLOAT,
+        false,
+        6 * size_f32,
+        3 * i as i32 * size_f32,
+      );
+    }
+    gl.bind_vertex_array(None);
+
+    self.morph_vbos.push(vbo);
+    self.morph_weights.push(0.);
+    Ok(index)
+  }
+
+  // Attach bone indices and weights for each vertex, at locations 3 and 4 as expected by
+  // skinning.vert
+  pub unsafe fn set_skin(&mut self, gl: &Context, skin: &[SkinVertex]) -> Result<()> {
+    if skin.len() != self.vertices.len() {
+      bail!(
+        "Expected skinning data for {} vertices, got {}",
+        self.vertices.len(),
+        skin.len()
+      );
+    }
+
+    let vbo = match self.skin_vbo {
+      Some(vbo) => vbo,
+      None => gl.create_buffer().map_err(Error::msg)?,
+    };
+    gl.bind_vertex_array(Some(self.vao));
+    gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
+    let (_, skin_bytes, _) = skin.align_to::<u8>();
+    gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, skin_bytes, glow::STATIC_DRAW);
+
+    // Bone indices stay integers, so they need the _i32 variant of the attribute pointer
+    let stride = size_of::<SkinVertex>() as i32;
+    gl.enable_vertex_attrib_array(3);
+    gl.vertex_attrib_pointer_i32(3, 4, glow::INT, stride, 0);
+    gl.enable_vertex_attrib_array(4);
+    gl.vertex_attrib_pointer_f32(
+      4,
+      4,
+      glow::FLOAT,
+      false,
+      stride,
+      size_of::<[i32; 4]>() as i32,
+    );
+    gl.bind_vertex_array(None);
+
+    self.skin_vbo = Some(vbo);
+    Ok(())
+  }
+
+  // Attach a tangent for each vertex at location 5, e.g. from compute_tangents
+  pub unsafe fn set_tangents(&mut self, gl: &Context, tangents: &[Vec4]) -> Result<()> {
+    if tangents.len() != self.vertices.len() {
+      bail!(
+        "Expected tangents for {} vertices, got {}",
+        self.vertices.len(),
+        tangents.len()
+      );
+    }
+
+    let vbo = match self.tangent_vbo {
+      Some(vbo) => vbo,
+      None => gl.create_buffer().map_err(Error::msg)?,
+    };
+    gl.bind_vertex_array(Some(self.vao));
+    gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
+    let (_, tangent_bytes, _) = tangents.align_to::<u8>();
+    gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, tangent_bytes, glow::STATIC_DRAW);
+    gl.enable_vertex_attrib_array(TANGENT_ATTRIB);
+    gl.vertex_attrib_pointer_f32(TANGENT_ATTRIB, 4, glow::FLOAT, false, 0, 0);
+    gl.bind_vertex_array(None);
+
+    self.tangent_vbo = Some(vbo);
+    Ok(())
+  }
+
+  // Weights are bound to the morph_weights uniform array each time the mesh is drawn.
+  // Targets without a weight are left at their previous one.
+  pub fn set_morph_weights(&mut self, weights: &[f32]) {
+    for (weight, new_weight) in self.morph_weights.iter_mut().zip(weights) {
+      *weight = *new_weight;
+    }
+  }
+
   pub unsafe fn draw(&self, gl: &Context, shader: &mut ActiveShader) {
     if let Some(material) = self.material.as_ref() {
       shader.bind_uniform(gl, "material", material);
     }
+    if !self.morph_weights.is_empty() {
+      shader.bind_uniform(gl, "morph_weights", &self.morph_weights);
+    }
 
     gl.bind_vertex_array(Some(self.vao));
     gl.draw_elements(
@@ -107,7 +320,190 @@ impl Mesh {
     shader.reset_textures();
   }
 
+  // Draw only the edges of each triangle, e.g. for debugging geometry
+  pub unsafe fn draw_wireframe(&self, gl: &Context, shader: &mut ActiveShader) {
+    if !cfg!(target_arch = "wasm32") {
+      gl.polygon_mode(glow::FRONT_AND_BACK, glow::LINE);
+      self.draw(gl, shader);
+      gl.polygon_mode(glow::FRONT_AND_BACK, glow::FILL);
+      return;
+    }
+
+    // WebGL has no polygon_mode, so draw lines along each triangle edge instead
+    match self.edge_ebo(gl) {
+      Err(err) => log::error!("Failed to create wireframe index buffer: {}", err),
+
+      Ok(edge_ebo) => {
+        if let Some(material) = self.material.as_ref() {
+          shader.bind_uniform(gl, "material", material);
+        }
+
+        // The element buffer is part of the vertex array's state, so swap in the edges
+        // and put the triangles back afterwards
+        gl.bind_vertex_array(Some(self.vao));
+        gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(edge_ebo));
+        gl.draw_elements(
+          glow::LINES,
+          (self.indices.len() * 2) as i32,
+          glow::UNSIGNED_INT,
+          0,
+        );
+        gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(self.ebo));
+        gl.bind_vertex_array(None);
+
+        shader.reset_textures();
+      }
+    }
+  }
+
+  unsafe fn edge_ebo(&self, gl: &Context) -> Result<GlBuffer> {
+    if let Some(edge_ebo) = self.edge_ebo.get() {
+      return Ok(edge_ebo);
+    }
+
+    let edges = self
+      .indices
+      .chunks_exact(3)
+      .flat_map(|tri| vec![tri[0], tri[1], tri[1], tri[2], tri[2], tri[0]])
+      .collect::<Vec<_>>();
+    let edge_ebo = gl.create_buffer().map_err(Error::msg)?;
+    gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(edge_ebo));
+    let (_, edges_bytes, _) = edges.align_to::<u8>();
+    gl.buffer_data_u8_slice(glow::ELEMENT_ARRAY_BUFFER, edges_bytes, glow::STATIC_DRAW);
+    gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, None);
+
+    self.edge_ebo.set(Some(edge_ebo));
+    Ok(edge_ebo)
+  }
+
+  // Free the vertex array and its buffers. The material's textures may be shared with
+  // other meshes, so they are left for the caller to dispose.
+  pub unsafe fn dispose(self, gl: &Context) {
+    gl.delete_vertex_array(self.vao);
+    gl.delete_buffer(self.vbo);
+    gl.delete_buffer(self.ebo);
+    if let Some(edge_ebo) = self.edge_ebo.get() {
+      gl.delete_buffer(edge_ebo);
+    }
+    for vbo in self.morph_vbos {
+      gl.delete_buffer(vbo);
+    }
+    if let Some(skin_vbo) = self.skin_vbo {
+      gl.delete_buffer(skin_vbo);
+    }
+    if let Some(tangent_vbo) = self.tangent_vbo {
+      gl.delete_buffer(tangent_vbo);
+    }
+  }
+
   pub fn to_model(self) -> Model {
     Model { meshes: vec![self] }
   }
 }
+
+// Per-vertex tangents for normal mapping, pointing along increasing U of the texture
+// coordinates. Each triangle's tangent is accumulated into its vertices, then made
+// perpendicular to the vertex normal. The w component is the handedness of the UV mapping,
+// so the bitangent is cross(normal, tangent.xyz) * tangent.w.
+pub fn compute_tangents(vertices: &[Vertex], indices: &[u32]) -> Vec<Vec4> {
+  let mut tangents = vec![Vec3::zeros(); vertices.len()];
+  let mut bitangents = vec![Vec3::zeros(); vertices.len()];
+  for tri in indices.chunks_exact(3) {
+    let [a, b, c] = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+    let (v0, v1, v2) = (&vertices[a], &vertices[b], &vertices[c]);
+    let edge1 = v1.position - v0.position;
+    let edge2 = v2.position - v0.position;
+    let duv1 = v1.tex_coords - v0.tex_coords;
+    let duv2 = v2.tex_coords - v0.tex_coords;
+
+    // Degenerate UVs give no direction, so the triangle is skipped
+    let det = duv1.x * duv2.y - duv2.x * duv1.y;
+    if det.abs() < f32::EPSILON {
+      continue;
+    }
+    let tangent = (edge1 * duv2.y - edge2 * duv1.y) / det;
+    let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / det;
+    for i in &[a, b, c] {
+      tangents[*i] += tangent;
+      bitangents[*i] += bitangent;
+    }
+  }
+
+  vertices
+    .iter()
+    .zip(tangents.iter().zip(&bitangents))
+    .map(|(vertex, (tangent, bitangent))| {
+      let n = vertex.normal;
+      let t = tangent - n * glm::dot(&n, tangent);
+      let t = if glm::length2(&t) > 0. {
+        glm::normalize(&t)
+      } else {
+        // No UV gradient to follow, so any direction perpendicular to the normal will do
+        let axis = if n.x.abs() < 0.9 {
+          Vec3::x()
+        } else {
+          Vec3::y()
+        };
+        glm::normalize(&glm::cross(&n, &axis))
+      };
+      let handedness = if glm::dot(&glm::cross(&n, &t), bitangent) < 0. {
+        -1.
+      } else {
+        1.
+      };
+      glm::vec4(t.x, t.y, t.z, handedness)
+    })
+    .collect()
+}
+
+// A mesh with diffuse, normal and specular maps, drawn with normal_mapping.vert. The maps
+// are bound to diffuse_map, normal_map and specular_map at texture slots 0, 1 and 2, and
+// use_normal_map tells the shader whether to perturb the normal or use the vertex normal.
+pub struct NormalMappedMesh {
+  pub mesh: Mesh,
+  pub diffuse_map: Texture,
+  pub normal_map: Option<Texture>,
+  pub specular_map: Texture,
+}
+
+impl NormalMappedMesh {
+  // Computes the mesh's tangents from its vertices and attaches them
+  pub unsafe fn new(
+    gl: &Context,
+    mut mesh: Mesh,
+    diffuse_map: Texture,
+    normal_map: Option<Texture>,
+    specular_map: Texture,
+  ) -> Result<Self> {
+    let tangents = compute_tangents(&mesh.vertices, &mesh.indices);
+    mesh.set_tangents(gl, &tangents)?;
+    Ok(NormalMappedMesh {
+      mesh,
+      diffuse_map,
+      normal_map,
+      specular_map,
+    })
+  }
+
+  pub unsafe fn draw(&self, gl: &Context, shader: &mut ActiveShader) {
+    // Textures take slots in the order they're bound, so start from slot 0. Without a
+    // normal map, slot 1 is skipped to keep the specular map at slot 2.
+    shader.reset_textures();
+    shader.bind_uniform(gl, "diffuse_map", &self.diffuse_map);
+    match &self.normal_map {
+      Some(normal_map) => shader.bind_uniform(gl, "normal_map", normal_map),
+      None => {
+        shader.new_texture_slot();
+      }
+    }
+    shader.bind_uniform(gl, "specular_map", &self.specular_map);
+    shader.bind_uniform(gl, "use_normal_map", &self.normal_map.is_some());
+
+    self.mesh.draw(gl, shader);
+  }
+
+  // The maps may be shared with other meshes, so like Mesh::dispose this leaves them be
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.mesh.dispose(gl);
+  }
+}
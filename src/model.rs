@@ -37,7 +37,7 @@ impl Model {
         Some("jpeg") | Some("jpg") | Some("png") => {
           BytesOrImage::Image(io::load_image(path).await?)
         }
-        _ => BytesOrImage::Bytes(io::load_file(path).await?),
+        _ => BytesOrImage::Bytes(io::load_bytes(path).await?),
       };
       Ok::<_, Error>((file.to_owned(), data))
     }))
@@ -133,4 +133,18 @@ impl Model {
       mesh.draw(gl, shader);
     }
   }
+
+  pub unsafe fn draw_wireframe(&self, gl: &Context, shader: &mut ActiveShader) {
+    for mesh in &self.meshes {
+      mesh.draw_wireframe(gl, shader);
+    }
+  }
+
+  // Free every mesh in the model. Like all GL resources in the crate, models are freed
+  // explicitly since glow needs a &Context to delete anything.
+  pub unsafe fn dispose(self, gl: &Context) {
+    for mesh in self.meshes {
+      mesh.dispose(gl);
+    }
+  }
 }
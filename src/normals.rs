@@ -0,0 +1,51 @@
+use std::path::Path;
+
+use crate::{camera::Camera, mesh::Mesh, prelude::*, shader::Shader};
+
+// Debug view that draws each vertex normal as a line, to check normals when lighting
+// looks wrong. Relies on a geometry shader, so it isn't available on the web.
+pub struct NormalVisualizer {
+  shader: Shader,
+  pub color: [f32; 4],
+}
+
+impl NormalVisualizer {
+  pub async unsafe fn new(gl: &Context) -> Result<Self> {
+    let shader = Shader::load(
+      gl,
+      "assets/shaders/normals.vert",
+      "assets/shaders/normals.frag",
+      Some(Path::new("assets/shaders/normals.geom")),
+    )
+    .await?;
+
+    Ok(NormalVisualizer {
+      shader,
+      color: [1., 1., 0., 1.],
+    })
+  }
+
+  // Draw the normals of mesh, transformed by model, as lines of the given length
+  pub unsafe fn draw_normals(
+    &self,
+    gl: &Context,
+    mesh: &Mesh,
+    model: &Mat4,
+    camera: &Camera,
+    length: f32,
+  ) {
+    let mut shader = self.shader.activate(gl);
+    shader.bind_uniform(gl, "model", model);
+    shader.bind_uniform(gl, "camera_view", &camera.view_matrix());
+    shader.bind_uniform(gl, "camera_projection", &camera.projection());
+    shader.bind_uniform(gl, "normal_length", &length);
+    shader.bind_uniform(gl, "color", &self.color);
+
+    // The mesh's material isn't used by the shader, so drawing it normally is fine
+    mesh.draw(gl, &mut shader);
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.shader.dispose(gl);
+  }
+}
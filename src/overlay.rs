@@ -0,0 +1,175 @@
+use crate::{prelude::*, render_state::RenderState, shader::Shader, texture::Texture};
+use std::mem::size_of;
+
+const VERTEX_SOURCE: &str = r#"
+layout (location = 0) in vec2 left_bottom;
+layout (location = 1) in vec2 right_top;
+layout (location = 2) in vec4 color;
+
+// Not `projection`, which would clash with the built-in CameraBlock
+uniform mat4 overlay_projection;
+
+out vec2 f_tex_coords;
+out vec4 f_color;
+
+void main() {
+  // Triangle strip corners, in the order (0, 0), (1, 0), (0, 1), (1, 1)
+  vec2 corner = vec2(gl_VertexID & 1, gl_VertexID >> 1);
+  f_tex_coords = corner;
+  f_color = color;
+  gl_Position = overlay_projection * vec4(mix(left_bottom, right_top, corner), 0.0, 1.0);
+}
+"#;
+
+const FRAGMENT_SOURCE: &str = r#"
+out vec4 FragColor;
+
+in vec2 f_tex_coords;
+in vec4 f_color;
+
+uniform bool has_texture;
+uniform sampler2D tex;
+
+void main() {
+  FragColor = has_texture ? texture(tex, f_tex_coords) * f_color : f_color;
+}
+"#;
+
+#[repr(C)]
+#[derive(Clone, Debug)]
+struct QuadVertex {
+  left_bottom: Vec2,
+  right_top: Vec2,
+  color: Vec4,
+}
+
+// Consecutive quads sharing a texture (or all untextured) are drawn together
+struct Batch {
+  texture: Option<Texture>,
+  quads: Vec<QuadVertex>,
+}
+
+// Immediate-mode 2D drawing over the scene, e.g. for HUDs and debug panels. Coordinates
+// are in pixels from the bottom-left of the screen. Quads are queued during the frame and
+// drawn in order by flush.
+pub struct Overlay {
+  projection: Mat4,
+  batches: Vec<Batch>,
+  shader: Shader,
+  vertex_array: GlVertexArray,
+  vertex_buffer: GlBuffer,
+}
+
+impl Overlay {
+  pub unsafe fn new(gl: &Context, screen_width: u32, screen_height: u32) -> Result<Self> {
+    let shader = Shader::from_static(gl, VERTEX_SOURCE, FRAGMENT_SOURCE)?;
+
+    let vertex_array = gl.create_vertex_array().map_err(Error::msg)?;
+    gl.bind_vertex_array(Some(vertex_array));
+
+    let vertex_buffer = gl.create_buffer().map_err(Error::msg)?;
+    gl.bind_buffer(glow::ARRAY_BUFFER, Some(vertex_buffer));
+
+    // One instance per quad
+    let size_f32 = size_of::<f32>() as i32;
+    let sizes = [2, 2, 4];
+    let stride = sizes.iter().sum::<i32>() * size_f32;
+
+    let mut offset = 0;
+    for (i, size) in sizes.iter().enumerate() {
+      gl.enable_vertex_attrib_array(i as u32);
+      gl.vertex_attrib_pointer_f32(
+        i as u32,
+        *size,
+        glow::FLOAT,
+        false,
+        stride,
+        offset * size_f32,
+      );
+      gl.vertex_attrib_divisor(i as u32, 1);
+      offset += size;
+    }
+
+    gl.bind_vertex_array(None);
+
+    let mut overlay = Overlay {
+      projection: Mat4::identity(),
+      batches: vec![],
+      shader,
+      vertex_array,
+      vertex_buffer,
+    };
+    overlay.resize(screen_width, screen_height);
+    Ok(overlay)
+  }
+
+  pub fn resize(&mut self, screen_width: u32, screen_height: u32) {
+    self.projection = glm::ortho(0., screen_width as f32, 0., screen_height as f32, -1., 1.);
+  }
+
+  pub fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: Vec4) {
+    self.push(None, x, y, w, h, color);
+  }
+
+  pub fn textured_rect(&mut self, x: f32, y: f32, w: f32, h: f32, texture: &Texture) {
+    self.push(Some(texture), x, y, w, h, glm::vec4(1., 1., 1., 1.));
+  }
+
+  fn push(&mut self, texture: Option<&Texture>, x: f32, y: f32, w: f32, h: f32, color: Vec4) {
+    let quad = QuadVertex {
+      left_bottom: glm::vec2(x, y),
+      right_top: glm::vec2(x + w, y + h),
+      color,
+    };
+
+    let id = texture.map(|texture| texture.texture);
+    match self.batches.last_mut() {
+      Some(batch) if batch.texture.as_ref().map(|texture| texture.texture) == id => {
+        batch.quads.push(quad)
+      }
+      _ => self.batches.push(Batch {
+        texture: texture.cloned(),
+        quads: vec![quad],
+      }),
+    }
+  }
+
+  // Draw everything queued since the last flush over the current framebuffer, then clear
+  // the queue
+  pub unsafe fn flush(&mut self, gl: &Context) {
+    if self.batches.is_empty() {
+      return;
+    }
+
+    RenderState {
+      depth_test: false,
+      depth_write: false,
+      cull_face: None,
+      ..Default::default()
+    }
+    .apply(gl);
+
+    let mut shader = self.shader.activate(gl);
+    shader.bind_uniform(gl, "overlay_projection", &self.projection);
+    gl.bind_vertex_array(Some(self.vertex_array));
+    gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vertex_buffer));
+    for batch in self.batches.drain(..) {
+      let (_, quads_bytes, _) = batch.quads.align_to::<u8>();
+      gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, quads_bytes, glow::STREAM_DRAW);
+
+      shader.bind_uniform(gl, "has_texture", &batch.texture.is_some());
+      shader.bind_uniform(gl, "tex", &batch.texture);
+      gl.draw_arrays_instanced(glow::TRIANGLE_STRIP, 0, 4, batch.quads.len() as i32);
+      shader.reset_textures();
+    }
+    gl.bind_vertex_array(None);
+
+    RenderState::default().apply(gl);
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.shader.dispose(gl);
+    gl.delete_vertex_array(self.vertex_array);
+    gl.delete_buffer(self.vertex_buffer);
+  }
+}
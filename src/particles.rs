@@ -0,0 +1,501 @@
+use std::mem::size_of;
+
+use crate::{
+  camera::Camera,
+  math::Rng,
+  prelude::*,
+  render_state::RenderState,
+  shader::{Shader, ShaderBuilder},
+  transform_feedback::TransformFeedbackPingPong,
+};
+
+// Per-instance data uploaded for each live particle
+#[repr(C)]
+#[derive(Clone, Debug)]
+struct ParticleVertex {
+  center: Vec3,
+  color: Vec4,
+  size: f32,
+}
+
+struct Particle {
+  position: Vec3,
+  velocity: Vec3,
+  age: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct EmitterParams {
+  pub position: Vec3,
+  // Particles spawned per second
+  pub emission_rate: f32,
+  // Seconds each particle lives
+  pub lifetime: f32,
+  pub velocity: Vec3,
+  // Random variation added to velocity along each axis
+  pub spread: f32,
+  pub gravity: Vec3,
+  pub start_color: Vec4,
+  pub end_color: Vec4,
+  pub start_size: f32,
+  pub end_size: f32,
+}
+
+impl Default for EmitterParams {
+  fn default() -> Self {
+    EmitterParams {
+      position: glm::zero(),
+      emission_rate: 50.,
+      lifetime: 2.,
+      velocity: glm::vec3(0., 1., 0.),
+      spread: 0.3,
+      gravity: glm::zero(),
+      start_color: glm::vec4(1., 1., 1., 1.),
+      end_color: glm::vec4(1., 1., 1., 0.),
+      start_size: 0.1,
+      end_size: 0.05,
+    }
+  }
+}
+
+// Particles simulated on the CPU and drawn as camera-facing quads in a single instanced
+// draw call, the same way Font draws glyphs.
+pub struct ParticleSystem {
+  pub params: EmitterParams,
+  particles: Vec<Particle>,
+  // Fractional particles left over from previous frames' emission
+  emit_accumulator: f32,
+  rng: Rng,
+
+  vertex_array: GlVertexArray,
+  vertex_buffer: GlBuffer,
+}
+
+impl ParticleSystem {
+  pub unsafe fn new(gl: &Context, params: EmitterParams) -> Result<Self> {
+    let vertex_array = gl.create_vertex_array().map_err(Error::msg)?;
+    gl.bind_vertex_array(Some(vertex_array));
+
+    let vertex_buffer = gl.create_buffer().map_err(Error::msg)?;
+    gl.bind_buffer(glow::ARRAY_BUFFER, Some(vertex_buffer));
+
+    // Set vertex attribute locations to each member of ParticleVertex struct
+    let size_f32 = size_of::<f32>() as i32;
+    let sizes = [3, 4, 1];
+    let stride = sizes.iter().sum::<i32>() * size_f32;
+
+    let mut offset = 0;
+    for (i, size) in sizes.iter().enumerate() {
+      gl.enable_vertex_attrib_array(i as u32);
+      gl.vertex_attrib_pointer_f32(
+        i as u32,
+        *size,
+        glow::FLOAT,
+        false,
+        stride,
+        offset * size_f32,
+      );
+      gl.vertex_attrib_divisor(i as u32, 1);
+      offset += size;
+    }
+
+    gl.bind_vertex_array(None);
+
+    Ok(ParticleSystem {
+      params,
+      particles: vec![],
+      emit_accumulator: 0.,
+      rng: Rng::default(),
+      vertex_array,
+      vertex_buffer,
+    })
+  }
+
+  pub async unsafe fn load_shader(gl: &Context) -> Result<Shader> {
+    Ok(
+      Shader::load(
+        gl,
+        "assets/shaders/particle.vert",
+        "assets/shaders/particle.frag",
+        None,
+      )
+      .await?,
+    )
+  }
+
+  pub fn len(&self) -> usize {
+    self.particles.len()
+  }
+
+  pub fn update(&mut self, dt: f32) {
+    // Age and move existing particles, dropping dead ones
+    let params = &self.params;
+    self
+      .particles
+      .retain(|particle| particle.age + dt < params.lifetime);
+    for particle in self.particles.iter_mut() {
+      particle.age += dt;
+      particle.velocity += params.gravity * dt;
+      particle.position += particle.velocity * dt;
+    }
+
+    // Spawn new particles at a steady rate regardless of frame rate
+    self.emit_accumulator += self.params.emission_rate * dt;
+    while self.emit_accumulator >= 1. {
+      self.emit_accumulator -= 1.;
+      let rng = &mut self.rng;
+      let jitter = glm::vec3(rng.range(-1., 1.), rng.range(-1., 1.), rng.range(-1., 1.));
+      let jitter = jitter * self.params.spread;
+      self.particles.push(Particle {
+        position: self.params.position,
+        velocity: self.params.velocity + jitter,
+        age: 0.,
+      });
+    }
+  }
+
+  pub unsafe fn draw(&self, gl: &Context, shader: &Shader, camera: &Camera) {
+    let params = &self.params;
+    let vertices = self
+      .particles
+      .iter()
+      .map(|particle| {
+        let t = particle.age / params.lifetime;
+        ParticleVertex {
+          center: particle.position,
+          color: glm::lerp(&params.start_color, &params.end_color, t),
+          size: glm::lerp_scalar(params.start_size, params.end_size, t),
+        }
+      })
+      .collect::<Vec<_>>();
+
+    gl.bind_vertex_array(Some(self.vertex_array));
+    gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vertex_buffer));
+    let (_, vertices_bytes, _) = vertices.align_to::<u8>();
+    gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, vertices_bytes, glow::STREAM_DRAW);
+
+    let mut shader = shader.activate(gl);
+    shader.bind_uniform(
+      gl,
+      "view_projection",
+      &(camera.projection() * camera.view_matrix()),
+    );
+    shader.bind_uniform(gl, "camera_right", &glm::normalize(&camera.right()));
+    shader.bind_uniform(gl, "camera_up", &camera.camera_up());
+
+    // Particles are translucent, so test against the scene's depth without writing to it
+    RenderState {
+      depth_write: false,
+      cull_face: None,
+      ..Default::default()
+    }
+    .apply(gl);
+    gl.draw_arrays_instanced(glow::TRIANGLE_STRIP, 0, 4, vertices.len() as i32);
+    RenderState::default().apply(gl);
+
+    gl.bind_vertex_array(None);
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    gl.delete_vertex_array(self.vertex_array);
+    gl.delete_buffer(self.vertex_buffer);
+  }
+}
+
+// Advances each particle one step. Particles are born at a steady rate: each one starts
+// with a negative age and is spawned at the emitter when its age reaches zero, then
+// respawned whenever it outlives its lifetime.
+const GPU_UPDATE_VERTEX_SOURCE: &str = r#"
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 velocity;
+layout (location = 2) in float age;
+layout (location = 3) in float lifetime;
+
+out vec3 tf_position;
+out vec3 tf_velocity;
+out float tf_age;
+out float tf_lifetime;
+
+uniform float dt;
+uniform float time;
+uniform vec3 gravity;
+uniform vec3 emitter_position;
+uniform vec3 initial_velocity;
+uniform float spread;
+
+float hash(float n) {
+  return fract(sin(n) * 43758.5453);
+}
+
+void main() {
+  float new_age = age + dt;
+  bool born = age < 0.0 && new_age >= 0.0;
+  tf_lifetime = lifetime;
+
+  if (born || new_age >= lifetime) {
+    float seed = float(gl_VertexID) * 12.9898 + fract(time) * 78.233;
+    vec3 jitter = vec3(hash(seed), hash(seed + 1.7), hash(seed + 3.1)) * 2.0 - 1.0;
+    tf_position = emitter_position;
+    tf_velocity = initial_velocity + jitter * spread;
+    tf_age = born ? new_age : new_age - lifetime;
+  } else if (new_age < 0.0) {
+    tf_position = position;
+    tf_velocity = velocity;
+    tf_age = new_age;
+  } else {
+    tf_velocity = velocity + gravity * dt;
+    tf_position = position + tf_velocity * dt;
+    tf_age = new_age;
+  }
+}
+"#;
+
+// The update pass doesn't rasterize anything, but a program still needs a fragment shader
+const GPU_UPDATE_FRAGMENT_SOURCE: &str = r#"
+out vec4 FragColor;
+
+void main() {
+  FragColor = vec4(0.0);
+}
+"#;
+
+const GPU_RENDER_VERTEX_SOURCE: &str = r#"
+layout (location = 0) in vec3 position;
+layout (location = 2) in float age;
+layout (location = 3) in float lifetime;
+
+uniform mat4 view_projection;
+uniform float point_size;
+uniform vec4 start_color;
+uniform vec4 end_color;
+
+out vec4 f_color;
+
+void main() {
+  f_color = mix(start_color, end_color, clamp(age / lifetime, 0.0, 1.0));
+
+  // Unborn particles are moved outside the clip volume
+  if (age < 0.0) {
+    gl_Position = vec4(2.0, 2.0, 2.0, 1.0);
+    gl_PointSize = 0.0;
+    return;
+  }
+
+  gl_Position = view_projection * vec4(position, 1.0);
+  gl_PointSize = point_size / gl_Position.w;
+}
+"#;
+
+const GPU_RENDER_FRAGMENT_SOURCE: &str = r#"
+out vec4 FragColor;
+
+in vec4 f_color;
+
+void main() {
+  // Round, soft-edged points
+  vec2 offset = gl_PointCoord * 2.0 - 1.0;
+  float dist2 = dot(offset, offset);
+  if (dist2 > 1.0) {
+    discard;
+  }
+  FragColor = vec4(f_color.rgb, f_color.a * (1.0 - dist2));
+}
+"#;
+
+// Per-particle state, read and written by the update pass
+#[repr(C)]
+#[derive(Clone, Debug)]
+struct GpuParticle {
+  position: Vec3,
+  velocity: Vec3,
+  age: f32,
+  lifetime: f32,
+}
+
+// Particles simulated entirely on the GPU with transform feedback, so nothing is uploaded
+// per frame. Each update reads every particle from one buffer and writes the next state into
+// the other, then the buffers swap roles. Particles are drawn as point sprites.
+pub struct GpuParticleSystem {
+  pub initial_velocity: Vec3,
+  // Random variation added to velocity along each axis
+  pub spread: f32,
+  pub start_color: Vec4,
+  pub end_color: Vec4,
+  // Point diameter in pixels at a distance of one unit
+  pub point_size: f32,
+  gravity: Vec3,
+  emitter_position: Vec3,
+  capacity: u32,
+  time: f32,
+
+  update_shader: Shader,
+  render_shader: Shader,
+  ping_pong: TransformFeedbackPingPong,
+  // A vertex array reading particles from each of the ping-pong buffers
+  vertex_arrays: [(GlBuffer, GlVertexArray); 2],
+}
+
+impl GpuParticleSystem {
+  // Particles are spawned spawn_rate times per second, and each lives just long enough that
+  // all capacity particles are alive at once
+  pub unsafe fn new(gl: &Context, capacity: u32, spawn_rate: f32) -> Result<Self> {
+    let update_shader = ShaderBuilder::new()
+      .name("gpu particle update")
+      .vertex_source(GPU_UPDATE_VERTEX_SOURCE.to_owned())
+      .fragment_source(GPU_UPDATE_FRAGMENT_SOURCE.to_owned())
+      .transform_feedback_varyings(
+        &["tf_position", "tf_velocity", "tf_age", "tf_lifetime"],
+        glow::INTERLEAVED_ATTRIBS,
+      )
+      .build(gl)?;
+    let render_shader =
+      Shader::from_static(gl, GPU_RENDER_VERTEX_SOURCE, GPU_RENDER_FRAGMENT_SOURCE)?;
+
+    let lifetime = capacity as f32 / spawn_rate;
+    let particles = (0..capacity)
+      .map(|i| GpuParticle {
+        position: glm::zero(),
+        velocity: glm::zero(),
+        age: -(i as f32) / spawn_rate,
+        lifetime,
+      })
+      .collect::<Vec<_>>();
+    let (_, particles_bytes, _) = particles.align_to::<u8>();
+
+    let size_f32 = size_of::<f32>() as i32;
+    let sizes = [3, 3, 1, 1];
+    let stride = sizes.iter().sum::<i32>() * size_f32;
+
+    let ping_pong = TransformFeedbackPingPong::new(gl, particles_bytes.len(), particles_bytes)?;
+    let mut vertex_arrays = vec![];
+    for buffer in &ping_pong.buffers() {
+      let vertex_array = gl.create_vertex_array().map_err(Error::msg)?;
+      gl.bind_vertex_array(Some(vertex_array));
+      gl.bind_buffer(glow::ARRAY_BUFFER, Some(*buffer));
+
+      let mut offset = 0;
+      for (i, size) in sizes.iter().enumerate() {
+        gl.enable_vertex_attrib_array(i as u32);
+        gl.vertex_attrib_pointer_f32(
+          i as u32,
+          *size,
+          glow::FLOAT,
+          false,
+          stride,
+          offset * size_f32,
+        );
+        offset += size;
+      }
+
+      vertex_arrays.push((*buffer, vertex_array));
+    }
+    gl.bind_vertex_array(None);
+    gl.bind_buffer(glow::ARRAY_BUFFER, None);
+
+    Ok(GpuParticleSystem {
+      initial_velocity: glm::vec3(0., 1., 0.),
+      spread: 0.3,
+      start_color: glm::vec4(1., 1., 1., 1.),
+      end_color: glm::vec4(1., 1., 1., 0.),
+      point_size: 20.,
+      gravity: glm::zero(),
+      emitter_position: glm::zero(),
+      capacity,
+      time: 0.,
+      update_shader,
+      render_shader,
+      ping_pong,
+      vertex_arrays: [vertex_arrays[0], vertex_arrays[1]],
+    })
+  }
+
+  pub fn set_gravity(&mut self, gravity: Vec3) {
+    self.gravity = gravity;
+  }
+
+  pub fn set_emitter_position(&mut self, position: Vec3) {
+    self.emitter_position = position;
+  }
+
+  pub fn capacity(&self) -> u32 {
+    self.capacity
+  }
+
+  pub unsafe fn update(&mut self, gl: &Context, dt: f32) {
+    self.time += dt;
+
+    let mut shader = self.update_shader.activate(gl);
+    shader.bind_uniform(gl, "dt", &dt);
+    shader.bind_uniform(gl, "time", &self.time);
+    shader.bind_uniform(gl, "gravity", &self.gravity);
+    shader.bind_uniform(gl, "emitter_position", &self.emitter_position);
+    shader.bind_uniform(gl, "initial_velocity", &self.initial_velocity);
+    shader.bind_uniform(gl, "spread", &self.spread);
+
+    // Only the captured outputs matter, so capture skips rasterization entirely
+    let (input, feedback) = self.ping_pong.next();
+    let vertex_array = vertex_array_for(&self.vertex_arrays, input);
+    let capacity = self.capacity as i32;
+    feedback.capture(gl, glow::POINTS, |gl| {
+      gl.bind_vertex_array(Some(vertex_array));
+      gl.draw_arrays(glow::POINTS, 0, capacity);
+      gl.bind_vertex_array(None);
+    });
+  }
+
+  pub unsafe fn draw(&self, gl: &Context, camera: &Camera) {
+    let mut shader = self.render_shader.activate(gl);
+    shader.bind_uniform(
+      gl,
+      "view_projection",
+      &(camera.projection() * camera.view_matrix()),
+    );
+    shader.bind_uniform(gl, "point_size", &self.point_size);
+    shader.bind_uniform(gl, "start_color", &self.start_color);
+    shader.bind_uniform(gl, "end_color", &self.end_color);
+
+    // GLES and WebGL always take point sizes from the shader and don't have the flag, but
+    // desktop GL has to be asked to
+    let program_point_size = !gl.capabilities().variant.is_es();
+    if program_point_size {
+      gl.enable(glow::PROGRAM_POINT_SIZE);
+    }
+
+    // Particles are translucent, so test against the scene's depth without writing to it
+    RenderState {
+      depth_write: false,
+      cull_face: None,
+      ..Default::default()
+    }
+    .apply(gl);
+    let vertex_array = vertex_array_for(&self.vertex_arrays, self.ping_pong.result());
+    gl.bind_vertex_array(Some(vertex_array));
+    gl.draw_arrays(glow::POINTS, 0, self.capacity as i32);
+    gl.bind_vertex_array(None);
+    if program_point_size {
+      gl.disable(glow::PROGRAM_POINT_SIZE);
+    }
+    RenderState::default().apply(gl);
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.update_shader.dispose(gl);
+    self.render_shader.dispose(gl);
+    for (_, vertex_array) in &self.vertex_arrays {
+      gl.delete_vertex_array(*vertex_array);
+    }
+    self.ping_pong.dispose(gl);
+  }
+}
+
+// The vertex array that reads particles from one of the ping-pong buffers
+fn vertex_array_for(
+  vertex_arrays: &[(GlBuffer, GlVertexArray); 2],
+  buffer: GlBuffer,
+) -> GlVertexArray {
+  vertex_arrays
+    .iter()
+    .find(|(vertex_buffer, _)| *vertex_buffer == buffer)
+    .map(|(_, vertex_array)| *vertex_array)
+    .unwrap()
+}
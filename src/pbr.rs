@@ -0,0 +1,197 @@
+use crate::{
+  bloom::Bloom,
+  camera::{Camera, CameraBlock},
+  framebuffer::Framebuffer,
+  gbuffer::GBuffer,
+  ibl::{Ibl, IblMaps},
+  light::{DirLight, PointLight},
+  material::PbrMaterial,
+  mesh::Mesh,
+  post_process::PostProcess,
+  prelude::*,
+  render_state::{self, ClearFlags, RenderState},
+  shader::{Shader, UniformBlock},
+  ssao::Ssao,
+  texture::{TCubemap, Texture},
+  tonemap::{ToneAlgorithm, Tonemapper},
+  transform::Transform,
+};
+
+const SSAO_KERNEL_SIZE: u32 = 32;
+
+// The surroundings of a PBR scene: image-based lighting baked from an environment map
+// (e.g. with Ibl::bake), the sky drawn behind the scene, and any direct lights.
+pub trait Environment {
+  fn ibl_maps(&self) -> IblMaps;
+  fn skybox(&self) -> &Texture<TCubemap>;
+
+  fn dir_lights(&self) -> Vec<&DirLight> {
+    vec![]
+  }
+
+  fn point_lights(&self) -> Vec<&PointLight> {
+    vec![]
+  }
+}
+
+// An environment lit only by its own map
+impl Environment for Ibl {
+  fn ibl_maps(&self) -> IblMaps {
+    self.maps()
+  }
+
+  fn skybox(&self) -> &Texture<TCubemap> {
+    &self.environment
+  }
+}
+
+// Deferred PBR pipeline. Each frame goes through:
+//  1. a geometry pass writing PbrMaterials into a G-buffer
+//  2. SSAO over the G-buffer
+//  3. a lighting pass with Cook-Torrance for direct lights and IBL for ambient light,
+//     into an HDR framebuffer
+//  4. bloom, then tonemapping onto the screen
+pub struct PbrRenderer {
+  gbuffer: GBuffer,
+  geometry_shader: Shader,
+  camera_ubo: UniformBlock<CameraBlock>,
+  ssao: Ssao,
+  lighting_pass: PostProcess,
+  hdr: Framebuffer,
+  bloomed: Framebuffer,
+  bloom: Bloom,
+  tonemapper: Tonemapper,
+  width: u32,
+  height: u32,
+
+  pub exposure: f32,
+  pub tone_algorithm: ToneAlgorithm,
+  pub bloom_threshold: f32,
+  pub bloom_iterations: u32,
+
+  // Whether tonemapping encodes for the display. Turn off when drawing into a
+  // ScreenCapture, whose replay encodes instead.
+  pub encode_output: bool,
+}
+
+impl PbrRenderer {
+  pub async unsafe fn new(gl: &Context, width: u32, height: u32) -> Result<Self> {
+    let geometry_shader = Shader::load_with_defs(
+      gl,
+      "assets/shaders/pbr_gbuffer.vert",
+      "assets/shaders/pbr_gbuffer.frag",
+      None,
+      &[PbrMaterial::PBR_DEF],
+    )
+    .await?;
+
+    Ok(PbrRenderer {
+      gbuffer: GBuffer::with_material(gl, width, height)?,
+      geometry_shader,
+      camera_ubo: UniformBlock::new(gl, 0)?,
+      ssao: Ssao::new(gl, width, height, SSAO_KERNEL_SIZE).await?,
+      lighting_pass: PostProcess::with_defs(
+        gl,
+        "assets/shaders/pbr_lighting.frag",
+        &[PbrMaterial::PBR_DEF, IblMaps::IBL_DEF],
+      )
+      .await?,
+      hdr: Framebuffer::with_color_format(gl, width, height, glow::RGBA16F)?,
+      bloomed: Framebuffer::with_color_format(gl, width, height, glow::RGBA16F)?,
+      bloom: Bloom::new(gl, width, height).await?,
+      tonemapper: Tonemapper::new(gl).await?,
+      width,
+      height,
+      exposure: 1.,
+      tone_algorithm: ToneAlgorithm::Aces,
+      bloom_threshold: 1.,
+      bloom_iterations: 5,
+      encode_output: true,
+    })
+  }
+
+  // Draw the objects lit by the environment into the default framebuffer
+  pub unsafe fn render(
+    &mut self,
+    gl: &Context,
+    objects: &[(&Mesh, &PbrMaterial, &Transform)],
+    camera: &Camera,
+    environment: &dyn Environment,
+  ) -> Result<()> {
+    gl.viewport(0, 0, self.width as i32, self.height as i32);
+
+    // Other owners of a camera block may have taken its binding slot since last frame
+    self.camera_ubo.bind_base(gl, self.camera_ubo.binding());
+    self.camera_ubo.upload(gl, &camera.uniform_block());
+
+    // Geometry pass. Blending would mix G-buffer attributes, so it's off.
+    self.gbuffer.bind(gl);
+    render_state::clear(gl, glm::zero(), ClearFlags::COLOR | ClearFlags::DEPTH);
+    RenderState {
+      blend: None,
+      ..Default::default()
+    }
+    .apply(gl);
+    let mut shader = self.geometry_shader.activate(gl);
+    shader.bind_uniform(gl, "CameraBlock", &self.camera_ubo);
+    for (mesh, material, transform) in objects {
+      shader.bind_uniform(gl, "model", transform);
+      shader.bind_uniform(gl, "material", material);
+      mesh.draw(gl, &mut shader);
+    }
+    RenderState::default().apply(gl);
+
+    self.ssao.render(gl, &self.gbuffer, camera);
+
+    // Lighting pass
+    self.hdr.bind(gl);
+    let gbuffer = &self.gbuffer;
+    let (ssao, camera_ubo) = (&self.ssao, &self.camera_ubo);
+    let inverse_view_projection = (camera.projection() * camera.view_matrix())
+      .try_inverse()
+      .unwrap_or_else(Mat4::identity);
+    self.lighting_pass.draw_with(gl, |gl, shader| {
+      shader.bind_uniform(gl, "CameraBlock", camera_ubo);
+      shader.bind_uniform(gl, "gPosition", &gbuffer.position);
+      shader.bind_uniform(gl, "gNormal", &gbuffer.normal);
+      shader.bind_uniform(gl, "gAlbedoSpec", &gbuffer.albedo_spec);
+      shader.bind_uniform(gl, "gMaterial", &gbuffer.material);
+      shader.bind_uniform(gl, "ssao", ssao.texture());
+      shader.bind_uniform(gl, "ibl", &environment.ibl_maps());
+      shader.bind_uniform(gl, "skybox", environment.skybox());
+      shader.bind_uniform(gl, "inverse_view_projection", &inverse_view_projection);
+      shader.bind_uniform(gl, "dir_lights", &environment.dir_lights());
+      shader.bind_uniform(gl, "point_lights", &environment.point_lights());
+    });
+
+    // Post-processing
+    let hdr = self.hdr.color.as_ref().unwrap();
+    self
+      .bloom
+      .render(gl, hdr, self.bloom_threshold, self.bloom_iterations);
+    gl.viewport(0, 0, self.width as i32, self.height as i32);
+    self.bloomed.bind(gl);
+    self.bloom.composite(gl, hdr);
+    self.bloomed.unbind(gl);
+
+    self.tonemapper.render(
+      gl,
+      self.bloomed.color.as_ref().unwrap(),
+      self.exposure,
+      self.tone_algorithm,
+      self.encode_output,
+    )
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.gbuffer.dispose(gl);
+    self.geometry_shader.dispose(gl);
+    self.camera_ubo.dispose(gl);
+    self.ssao.dispose(gl);
+    self.lighting_pass.dispose(gl);
+    self.hdr.dispose(gl);
+    self.bloomed.dispose(gl);
+    self.bloom.dispose(gl);
+    self.tonemapper.dispose(gl);
+  }
+}
@@ -0,0 +1,221 @@
+use crate::{
+  framebuffer::Framebuffer,
+  light::PointLight,
+  prelude::*,
+  texture::{TCubemap, Texture, TextureBuilder},
+};
+
+// Look direction and up vector for each cubemap face, in the order of
+// TEXTURE_CUBE_MAP_POSITIVE_X + i. The up vectors follow the cubemap convention so that
+// sampling with a direction finds the texel that was rendered in that direction.
+const FACES: [([f32; 3], [f32; 3]); 6] = [
+  ([1., 0., 0.], [0., -1., 0.]),
+  ([-1., 0., 0.], [0., -1., 0.]),
+  ([0., 1., 0.], [0., 0., 1.]),
+  ([0., -1., 0.], [0., 0., -1.]),
+  ([0., 0., 1.], [0., -1., 0.]),
+  ([0., 0., -1.], [0., -1., 0.]),
+];
+
+// Depth cubemap for a point light, which casts shadows in every direction. Render the
+// scene into each of the six faces with face_view_proj as the light-space matrix, then
+// sample it in the lighting pass with point_shadow_factor.
+pub struct PointShadow {
+  framebuffer: Framebuffer,
+  texture: Texture<TCubemap>,
+  pub resolution: u32,
+
+  // Clip planes of each face's projection. Nothing farther than `far` from the light
+  // casts a shadow.
+  pub near: f32,
+  pub far: f32,
+  // Distance offset to keep surfaces from shadowing themselves ("shadow acne"). Unlike
+  // ShadowMap's bias, this is in world units since the lookup compares distances.
+  pub bias: f32,
+  // Width of the NxN grid of texels averaged by percentage-closer filtering. 1 gives hard
+  // shadow edges, larger values give softer edges.
+  pub pcf_kernel_size: u32,
+}
+
+impl PointShadow {
+  pub unsafe fn new(gl: &Context, resolution: u32) -> Result<Self> {
+    let framebuffer = Framebuffer::empty(gl, resolution, resolution)?;
+
+    let texture = TextureBuilder::new(gl)
+      .as_cubemap()
+      .with_format(glow::DEPTH_COMPONENT)
+      .with_tex_parameter(glow::TEXTURE_MIN_FILTER, glow::NEAREST)
+      .with_tex_parameter(glow::TEXTURE_MAG_FILTER, glow::NEAREST)
+      .with_tex_parameter(glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE)
+      .with_tex_parameter(glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE)
+      .with_tex_parameter(glow::TEXTURE_WRAP_R, glow::CLAMP_TO_EDGE)
+      .render_cubemap(resolution)?;
+
+    let point_shadow = PointShadow {
+      framebuffer,
+      texture,
+      resolution,
+      near: 0.1,
+      far: 25.,
+      bias: 0.05,
+      pcf_kernel_size: 3,
+    };
+
+    // There's no color attachment, so tell OpenGL not to read or write color data
+    point_shadow.attach_face(gl, 0);
+    gl.draw_buffer(glow::NONE);
+    gl.read_buffer(glow::NONE);
+
+    point_shadow.framebuffer.check_completeness(gl)?;
+    point_shadow.framebuffer.unbind(gl);
+
+    Ok(point_shadow)
+  }
+
+  pub fn texture(&self) -> &Texture<TCubemap> {
+    &self.texture
+  }
+
+  unsafe fn attach_face(&self, gl: &Context, face: u32) {
+    gl.framebuffer_texture_2d(
+      glow::FRAMEBUFFER,
+      glow::DEPTH_ATTACHMENT,
+      glow::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+      Some(self.texture.texture),
+      0,
+    );
+  }
+
+  // Bind one face (0 to 5) of the cubemap and resize the viewport to match it. Clear the
+  // depth buffer after binding each face. The caller is responsible for restoring the
+  // viewport afterwards.
+  pub unsafe fn bind_face(&self, gl: &Context, face: u32) {
+    self.framebuffer.bind(gl);
+    self.attach_face(gl, face);
+    gl.viewport(0, 0, self.resolution as i32, self.resolution as i32);
+  }
+
+  pub unsafe fn unbind(&self, gl: &Context) {
+    self.framebuffer.unbind(gl);
+  }
+
+  // View-projection matrix for rendering a face from the light's position
+  pub fn face_view_proj(&self, light: &PointLight, face: u32) -> Mat4 {
+    face_view_proj(light.position, face, self.near, self.far)
+  }
+
+  // Uniforms for looking up this shadow cubemap with point_shadow_factor
+  pub fn params(&self, light: &PointLight) -> PointShadowParams {
+    PointShadowParams {
+      map: self.texture.clone(),
+      light_pos: light.position,
+      near: self.near,
+      far: self.far,
+      bias: self.bias,
+      pcf_kernel_size: self.pcf_kernel_size as i32,
+    }
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.framebuffer.dispose(gl);
+    self.texture.dispose(gl);
+  }
+}
+
+// 90 degree perspective looking out through one face of a cube centered on light_pos
+fn face_view_proj(light_pos: Vec3, face: u32, near: f32, far: f32) -> Mat4 {
+  let (direction, up) = FACES[face as usize];
+  let view = glm::look_at(
+    &light_pos,
+    &(light_pos + Vec3::from(direction)),
+    &Vec3::from(up),
+  );
+  let projection = glm::perspective(1., 90f32.to_radians(), near, far);
+  projection * view
+}
+
+#[derive(BindUniform, ShaderTypeDef, Clone)]
+pub struct PointShadowParams {
+  pub map: Texture<TCubemap>,
+  pub light_pos: Vec3,
+  pub near: f32,
+  pub far: f32,
+  pub bias: f32,
+  pub pcf_kernel_size: i32,
+}
+
+impl PointShadowParams {
+  // GLSL for the point shadow lookup, added with ShaderBuilder::with_def. Like
+  // shadow_factor, it returns from 0 (lit) to 1 (fully shadowed).
+  //
+  // Each face stores ordinary perspective depth, which is the distance along the face's
+  // axis. So the fragment is compared by its largest coordinate relative to the light,
+  // after undoing the projection on the stored depth.
+  pub const POINT_SHADOW_FACTOR_DEF: &'static str = r#"
+float point_shadow_factor(PointShadowParams shadow, vec3 world_pos, vec3 normal) {
+  vec3 to_frag = world_pos - shadow.light_pos;
+  vec3 axis_dist = abs(to_frag);
+  float current = max(axis_dist.x, max(axis_dist.y, axis_dist.z));
+
+  // Anything beyond the light's far plane is lit
+  if (current > shadow.far) {
+    return 0.0;
+  }
+
+  // Surfaces at a grazing angle to the light need a bigger bias
+  vec3 dir = normalize(to_frag);
+  float slope_bias = max(shadow.bias * (1.0 - dot(normal, -dir)), shadow.bias * 0.1);
+
+  // Offset samples across the face, perpendicular to the lookup direction. One texel
+  // spans 2 / size at unit distance along the face's axis.
+  vec3 up = abs(dir.y) < 0.99 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+  vec3 tangent = normalize(cross(up, dir));
+  vec3 bitangent = cross(dir, tangent);
+  float texel_size = 2.0 / float(textureSize(shadow.map, 0).x);
+
+  float near = shadow.near;
+  float far = shadow.far;
+  int radius = shadow.pcf_kernel_size / 2;
+  float shadowed = 0.0;
+  for (int x = -radius; x <= radius; x++) {
+    for (int y = -radius; y <= radius; y++) {
+      vec3 sample_dir = dir + (tangent * float(x) + bitangent * float(y)) * texel_size;
+      float z = texture(shadow.map, sample_dir).r * 2.0 - 1.0;
+      float closest = (2.0 * near * far) / (far + near - z * (far - near));
+      shadowed += current - slope_bias > closest ? 1.0 : 0.0;
+    }
+  }
+
+  float width = float(2 * radius + 1);
+  return shadowed / (width * width);
+}
+"#;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn faces_look_along_their_axis() {
+    let light_pos = glm::vec3(1., 2., 3.);
+    for (face, (direction, _)) in FACES.iter().enumerate() {
+      let view_proj = face_view_proj(light_pos, face as u32, 0.1, 25.);
+      let point = light_pos + Vec3::from(*direction) * 5.;
+      let clip = view_proj * glm::vec4(point.x, point.y, point.z, 1.);
+      let ndc = clip.xyz() / clip.w;
+      assert!(ndc.x.abs() < 1e-5 && ndc.y.abs() < 1e-5, "face {}", face);
+      assert!(ndc.z > -1. && ndc.z < 1., "face {}", face);
+    }
+  }
+
+  #[test]
+  fn faces_cover_the_whole_cube() {
+    // A corner direction is on the edge of the three faces it touches
+    let view_proj = face_view_proj(glm::zero(), 0, 0.1, 25.);
+    let clip = view_proj * glm::vec4(1., 1., 1., 1.);
+    let ndc = clip.xyz() / clip.w;
+    assert!((ndc.x.abs() - 1.).abs() < 1e-5);
+    assert!((ndc.y.abs() - 1.).abs() < 1e-5);
+  }
+}
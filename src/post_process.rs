@@ -0,0 +1,125 @@
+use crate::{
+  geometry::Geometry,
+  mesh::Mesh,
+  prelude::*,
+  render_state::RenderState,
+  shader::{ActiveShader, Shader},
+  texture::Texture,
+};
+use std::path::Path;
+
+pub const SCREEN_VERTEX_SHADER: &str = "assets/shaders/screen.vert";
+
+// Quad covering the whole screen, to be drawn with a shader using screen.vert
+pub struct ScreenQuad {
+  mesh: Mesh,
+}
+
+impl ScreenQuad {
+  pub unsafe fn new(gl: &Context) -> Result<Self> {
+    let mesh = Geometry::Plane {
+      length: 2.,
+      width: 2.,
+      normal: glm::zero(),
+    }
+    .to_mesh(gl, None)?;
+    Ok(ScreenQuad { mesh })
+  }
+
+  // Draw into the currently bound framebuffer, without depth testing against it
+  pub unsafe fn draw(&self, gl: &Context, shader: &mut ActiveShader) {
+    let state = RenderState {
+      depth_test: false,
+      ..Default::default()
+    };
+    self.draw_with_state(gl, shader, state);
+  }
+
+  // Like draw, with different render state, e.g. to turn off blending
+  pub unsafe fn draw_with_state(
+    &self,
+    gl: &Context,
+    shader: &mut ActiveShader,
+    state: RenderState,
+  ) {
+    state.apply(gl);
+    self.mesh.draw(gl, shader);
+    RenderState::default().apply(gl);
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.mesh.dispose(gl);
+  }
+}
+
+// A full-screen pass: draws a screen-covering quad with a fragment shader that samples an
+// input texture as `screenTexture`. Effects like FXAA or blur are each one of these.
+pub struct PostProcess {
+  shader: Shader,
+  quad: ScreenQuad,
+  blend: Option<(u32, u32)>,
+}
+
+impl PostProcess {
+  pub async unsafe fn new(gl: &Context, fragment_path: impl AsRef<Path>) -> Result<Self> {
+    Self::with_defs(gl, fragment_path, &[]).await
+  }
+
+  // Like new, for fragment shaders that use GLSL helpers added with ShaderBuilder::with_def
+  pub async unsafe fn with_defs(
+    gl: &Context,
+    fragment_path: impl AsRef<Path>,
+    defs: &[&'static str],
+  ) -> Result<Self> {
+    let quad = ScreenQuad::new(gl)?;
+    let shader =
+      Shader::load_with_defs(gl, SCREEN_VERTEX_SHADER, fragment_path, None, defs).await?;
+
+    Ok(PostProcess {
+      shader,
+      quad,
+      blend: RenderState::default().blend,
+    })
+  }
+
+  // Blend function for the pass's output, or None to overwrite the framebuffer, e.g. for
+  // passes whose alpha channel holds data rather than coverage
+  pub fn with_blend(mut self, blend: Option<(u32, u32)>) -> Self {
+    self.blend = blend;
+    self
+  }
+
+  // Draw into the currently bound framebuffer. init_shader can bind effect-specific uniforms.
+  pub unsafe fn draw(
+    &self,
+    gl: &Context,
+    input: &Texture,
+    init_shader: impl FnOnce(&Context, &mut ActiveShader),
+  ) {
+    self.draw_with(gl, |gl, shader| {
+      shader.bind_uniform(gl, "screenTexture", input);
+      init_shader(gl, shader);
+    });
+  }
+
+  // Like draw, for passes whose inputs aren't a single screen texture (e.g. G-buffer passes)
+  pub unsafe fn draw_with(
+    &self,
+    gl: &Context,
+    init_shader: impl FnOnce(&Context, &mut ActiveShader),
+  ) {
+    let mut shader = self.shader.activate(gl);
+    init_shader(gl, &mut shader);
+    let state = RenderState {
+      depth_test: false,
+      blend: self.blend,
+      ..Default::default()
+    };
+    self.quad.draw_with_state(gl, &mut shader, state);
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.shader.dispose(gl);
+    self.quad.dispose(gl);
+  }
+}
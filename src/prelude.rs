@@ -1,17 +1,21 @@
+pub use crate::context::Context;
 pub use crate::shader::GlmStd140Ext;
 pub use anyhow::{bail, Context as AnyhowContext, Error, Result};
 pub use futures::try_join;
 pub use glm::{Mat4, Vec2, Vec3, Vec4};
-pub use glow::{Context, HasContext};
+pub use glow::HasContext;
 pub use macros::{BindUniform, ShaderBlockDef, ShaderTypeDef};
 pub use maplit::hashmap;
 pub use nalgebra as na;
 pub use nalgebra_glm as glm;
 
-pub type GlShader = <Context as HasContext>::Shader;
-pub type GlProgram = <Context as HasContext>::Program;
-pub type GlVertexArray = <Context as HasContext>::VertexArray;
-pub type GlUniformLocation = <Context as HasContext>::UniformLocation;
-pub type GlTexture = <Context as HasContext>::Texture;
-pub type GlBuffer = <Context as HasContext>::Buffer;
-pub type GlFramebuffer = <Context as HasContext>::Framebuffer;
+pub type GlShader = <glow::Context as HasContext>::Shader;
+pub type GlProgram = <glow::Context as HasContext>::Program;
+pub type GlVertexArray = <glow::Context as HasContext>::VertexArray;
+pub type GlUniformLocation = <glow::Context as HasContext>::UniformLocation;
+pub type GlTexture = <glow::Context as HasContext>::Texture;
+pub type GlBuffer = <glow::Context as HasContext>::Buffer;
+pub type GlFramebuffer = <glow::Context as HasContext>::Framebuffer;
+pub type GlQuery = <glow::Context as HasContext>::Query;
+pub type GlRenderbuffer = <glow::Context as HasContext>::Renderbuffer;
+pub type GlTransformFeedback = <glow::Context as HasContext>::TransformFeedback;
@@ -0,0 +1,163 @@
+use crate::prelude::*;
+
+// Counts how many samples of the draw calls between begin and end pass the depth test,
+// e.g. to skip drawing an object whose bounding box was completely hidden last frame.
+pub struct OcclusionQuery {
+  query: GlQuery,
+  target: u32,
+}
+
+impl OcclusionQuery {
+  // `target` is glow::SAMPLES_PASSED for an exact count, or glow::ANY_SAMPLES_PASSED or
+  // glow::ANY_SAMPLES_PASSED_CONSERVATIVE for a cheaper 0 or 1. WebGL only supports the
+  // latter two.
+  pub unsafe fn new(gl: &Context, target: u32) -> Result<Self> {
+    let query = gl.create_query().map_err(Error::msg)?;
+    Ok(OcclusionQuery { query, target })
+  }
+
+  pub unsafe fn begin(&self, gl: &Context) {
+    gl.begin_query(self.target, self.query);
+  }
+
+  pub unsafe fn end(&self, gl: &Context) {
+    gl.end_query(self.target);
+  }
+
+  // Results arrive a frame or two late. Check this before calling result to avoid
+  // stalling until the GPU catches up.
+  pub unsafe fn result_available(&self, gl: &Context) -> bool {
+    gl.get_query_parameter_u32(self.query, glow::QUERY_RESULT_AVAILABLE) != 0
+  }
+
+  // Samples passed, or 0/1 for the ANY_SAMPLES_PASSED targets
+  pub unsafe fn result(&self, gl: &Context) -> u32 {
+    gl.get_query_parameter_u32(self.query, glow::QUERY_RESULT)
+  }
+
+  pub fn query(&self) -> GlQuery {
+    self.query
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    gl.delete_query(self.query);
+  }
+}
+
+// Measures how long the GPU spends on the commands between begin and end. WebGL2 only
+// has timer queries through an extension, so on the web this does nothing and never
+// returns a result.
+pub struct TimerQuery {
+  query: Option<GlQuery>,
+}
+
+impl TimerQuery {
+  pub unsafe fn new(gl: &Context) -> Result<Self> {
+    let query = if cfg!(target_arch = "wasm32") {
+      None
+    } else {
+      Some(gl.create_query().map_err(Error::msg)?)
+    };
+    Ok(TimerQuery { query })
+  }
+
+  // Only one timer query can be running at a time, so these can't be nested
+  pub unsafe fn begin(&self, gl: &Context) {
+    if let Some(query) = self.query {
+      gl.begin_query(glow::TIME_ELAPSED, query);
+    }
+  }
+
+  pub unsafe fn end(&self, gl: &Context) {
+    if self.query.is_some() {
+      gl.end_query(glow::TIME_ELAPSED);
+    }
+  }
+
+  // Nanoseconds elapsed, or None if the GPU isn't done yet. glow only reads 32-bit query
+  // results, so this wraps around for passes longer than about 4 seconds.
+  pub unsafe fn elapsed_ns(&self, gl: &Context) -> Option<u64> {
+    let query = self.query?;
+    if gl.get_query_parameter_u32(query, glow::QUERY_RESULT_AVAILABLE) == 0 {
+      return None;
+    }
+    Some(gl.get_query_parameter_u32(query, glow::QUERY_RESULT) as u64)
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    if let Some(query) = self.query {
+      gl.delete_query(query);
+    }
+  }
+}
+
+// Times labeled passes each frame. Each label gets its own query that's reused across
+// frames, so results lag a frame or two behind.
+#[derive(Default)]
+pub struct Profiler {
+  passes: Vec<(String, TimerQuery)>,
+  active: Option<usize>,
+}
+
+impl Profiler {
+  pub fn new() -> Self {
+    Profiler::default()
+  }
+
+  pub unsafe fn begin(&mut self, gl: &Context, label: &str) -> Result<()> {
+    if self.active.is_some() {
+      bail!(
+        "Profiler pass {:?} started before the previous pass ended",
+        label
+      );
+    }
+
+    let index = match self.passes.iter().position(|(name, _)| name == label) {
+      Some(index) => index,
+      None => {
+        self.passes.push((label.to_owned(), TimerQuery::new(gl)?));
+        self.passes.len() - 1
+      }
+    };
+
+    self.passes[index].1.begin(gl);
+    self.active = Some(index);
+    Ok(())
+  }
+
+  pub unsafe fn end(&mut self, gl: &Context) {
+    if let Some(index) = self.active.take() {
+      self.passes[index].1.end(gl);
+    }
+  }
+
+  // Latest time for each pass in milliseconds, in the order passes were first seen
+  pub unsafe fn results(&self, gl: &Context) -> Vec<(&str, Option<f64>)> {
+    self
+      .passes
+      .iter()
+      .map(|(label, query)| {
+        (
+          label.as_str(),
+          query.elapsed_ns(gl).map(|ns| ns as f64 / 1e6),
+        )
+      })
+      .collect()
+  }
+
+  // Log the latest time of every pass at info level, one line per pass
+  pub unsafe fn log_summary(&self, gl: &Context) {
+    for (label, ms) in self.results(gl) {
+      match ms {
+        Some(ms) => log::info!("{:>20}: {:.3}ms", label, ms),
+        None => log::info!("{:>20}: pending", label),
+      }
+    }
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    for (_, query) in self.passes {
+      query.dispose(gl);
+    }
+  }
+}
@@ -0,0 +1,134 @@
+use crate::prelude::*;
+use std::cell::RefCell;
+
+// The subset of fixed-function GL state that changes between draw calls. Applying a
+// RenderState only issues GL calls for the fields that differ from the last applied state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderState {
+  pub depth_test: bool,
+  pub depth_write: bool,
+  // (source factor, destination factor) for glBlendFunc, or None to disable blending
+  pub blend: Option<(u32, u32)>,
+  // Which faces to cull (e.g. glow::BACK), or None to disable culling
+  pub cull_face: Option<u32>,
+}
+
+impl Default for RenderState {
+  fn default() -> Self {
+    RenderState {
+      depth_test: true,
+      depth_write: true,
+      blend: Some((glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA)),
+      cull_face: Some(glow::BACK),
+    }
+  }
+}
+
+// GL state is per-context and our context lives on a single thread
+thread_local! {
+  static CURRENT: RefCell<Option<RenderState>> = RefCell::new(None);
+}
+
+unsafe fn set_enabled(gl: &Context, cap: u32, enabled: bool) {
+  if enabled {
+    gl.enable(cap);
+  } else {
+    gl.disable(cap);
+  }
+}
+
+impl RenderState {
+  pub unsafe fn apply(&self, gl: &Context) {
+    CURRENT.with(|current| {
+      let mut current = current.borrow_mut();
+
+      // If nothing has been applied yet, we don't know the GL state so set everything
+      let force = current.is_none();
+      let prev = current.unwrap_or(*self);
+
+      if force || prev.depth_test != self.depth_test {
+        set_enabled(gl, glow::DEPTH_TEST, self.depth_test);
+      }
+
+      if force || prev.depth_write != self.depth_write {
+        gl.depth_mask(self.depth_write);
+      }
+
+      if force || prev.blend != self.blend {
+        set_enabled(gl, glow::BLEND, self.blend.is_some());
+        if let Some((src, dst)) = self.blend {
+          gl.blend_func(src, dst);
+        }
+      }
+
+      if force || prev.cull_face != self.cull_face {
+        set_enabled(gl, glow::CULL_FACE, self.cull_face.is_some());
+        if let Some(face) = self.cull_face {
+          gl.cull_face(face);
+        }
+      }
+
+      *current = Some(*self);
+    });
+  }
+
+  // Forget the cached state, e.g. after changing GL state directly rather than through
+  // a RenderState. The next apply will set every field.
+  pub fn invalidate() {
+    CURRENT.with(|current| *current.borrow_mut() = None);
+  }
+}
+
+// Which buffers of the bound framebuffer to clear. Combine with |, e.g.
+// ClearFlags::COLOR | ClearFlags::DEPTH.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClearFlags(u32);
+
+impl ClearFlags {
+  pub const COLOR: ClearFlags = ClearFlags(glow::COLOR_BUFFER_BIT);
+  pub const DEPTH: ClearFlags = ClearFlags(glow::DEPTH_BUFFER_BIT);
+  pub const STENCIL: ClearFlags = ClearFlags(glow::STENCIL_BUFFER_BIT);
+  pub const ALL: ClearFlags =
+    ClearFlags(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT | glow::STENCIL_BUFFER_BIT);
+
+  pub fn contains(&self, other: ClearFlags) -> bool {
+    self.0 & other.0 == other.0
+  }
+}
+
+impl std::ops::BitOr for ClearFlags {
+  type Output = ClearFlags;
+
+  fn bitor(self, other: ClearFlags) -> ClearFlags {
+    ClearFlags(self.0 | other.0)
+  }
+}
+
+// Clear the bound framebuffer, with color as the clear color. glClear respects the write
+// masks, so depth and stencil writes are enabled for the clear and then put back.
+pub unsafe fn clear(gl: &Context, color: Vec4, flags: ClearFlags) {
+  if flags.contains(ClearFlags::COLOR) {
+    gl.clear_color(color.x, color.y, color.z, color.w);
+  }
+
+  let depth_write = CURRENT.with(|current| current.borrow().map_or(true, |s| s.depth_write));
+  if flags.contains(ClearFlags::DEPTH) && !depth_write {
+    gl.depth_mask(true);
+  }
+  let stencil_mask = if flags.contains(ClearFlags::STENCIL) {
+    let mask = gl.get_parameter_i32(glow::STENCIL_WRITEMASK) as u32;
+    gl.stencil_mask(0xFF);
+    Some(mask)
+  } else {
+    None
+  };
+
+  gl.clear(flags.0);
+
+  if flags.contains(ClearFlags::DEPTH) && !depth_write {
+    gl.depth_mask(false);
+  }
+  if let Some(mask) = stencil_mask {
+    gl.stencil_mask(mask);
+  }
+}
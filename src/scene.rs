@@ -1,3 +1,4 @@
+use futures::TryFutureExt;
 use std::{collections::HashMap, path::Path};
 
 use crate::{
@@ -8,9 +9,11 @@ use crate::{
   mesh::Mesh,
   model::Model,
   prelude::*,
+  render_state::RenderState,
   shader::{ActiveShader, Shader, UniformBlock},
   text::{Font, Text},
   texture::{TCubemap, Texture, TextureBuilder},
+  transform::Transform,
 };
 
 struct Entity {
@@ -25,6 +28,58 @@ impl Entity {
   }
 }
 
+// A node of a scene graph. Each node is placed relative to its parent, so moving a node
+// moves everything below it.
+pub struct SceneNode {
+  pub transform: Transform,
+  pub children: Vec<SceneNode>,
+  pub mesh: Option<Mesh>,
+}
+
+impl SceneNode {
+  pub fn new(transform: Transform, mesh: Option<Mesh>) -> Self {
+    SceneNode {
+      transform,
+      children: vec![],
+      mesh,
+    }
+  }
+
+  pub fn with_child(mut self, child: SceneNode) -> Self {
+    self.children.push(child);
+    self
+  }
+
+  pub fn world_matrix(&self, parent_world: Mat4) -> Mat4 {
+    parent_world * self.transform.matrix(None)
+  }
+
+  // Draw the whole tree below this node, binding each node's world matrix as `model`
+  pub unsafe fn draw(&self, gl: &Context, shader: &mut ActiveShader) {
+    self.draw_with_parent(gl, shader, Mat4::identity());
+  }
+
+  unsafe fn draw_with_parent(&self, gl: &Context, shader: &mut ActiveShader, parent_world: Mat4) {
+    let world = self.world_matrix(parent_world);
+    if let Some(mesh) = &self.mesh {
+      shader.bind_uniform(gl, "model", &world);
+      mesh.draw(gl, shader);
+    }
+    for child in &self.children {
+      child.draw_with_parent(gl, shader, world);
+    }
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    if let Some(mesh) = self.mesh {
+      mesh.dispose(gl);
+    }
+    for child in self.children {
+      child.dispose(gl);
+    }
+  }
+}
+
 pub struct Scene {
   floor: Entity,
   cubes: Vec<Entity>,
@@ -70,19 +125,22 @@ impl Scene {
         } else {
           Some(Path::new("assets/shaders/explode.geom"))
         }
-      ),
+      )
+      .err_into::<Error>(),
       Shader::load(
         gl,
         "assets/shaders/text.vert",
         "assets/shaders/text.frag",
         None
-      ),
+      )
+      .err_into::<Error>(),
       Shader::load(
         gl,
         "assets/shaders/skybox.vert",
         "assets/shaders/skybox.frag",
         None
-      ),
+      )
+      .err_into::<Error>(),
       TextureBuilder::new(gl).load("assets/textures/metal.png"),
       TextureBuilder::new(gl).load("assets/textures/marble.jpg"),
       TextureBuilder::new(gl)
@@ -288,9 +346,13 @@ impl Scene {
     shader.bind_uniform(gl, "skybox", &self.skybox_texture);
 
     // Have to disable face culling because we're viewing the inside of a cube
-    gl.disable(glow::CULL_FACE);
+    RenderState {
+      cull_face: None,
+      ..Default::default()
+    }
+    .apply(gl);
     self.skybox.draw(gl, &mut shader);
-    gl.enable(glow::CULL_FACE);
+    RenderState::default().apply(gl);
 
     // Draw text, which queues draw commands on the individual fonts
     self.text.draw(&mut self.fonts);
@@ -3,13 +3,35 @@ use na::{
   dimension::{U1, U3, U4},
   storage::Storage,
 };
-use std::{marker::PhantomData, mem::size_of, path::Path, slice};
+use std::{
+  cell::RefCell,
+  collections::{BTreeMap, HashMap, HashSet},
+  fmt,
+  marker::PhantomData,
+  mem::size_of,
+  path::{Path, PathBuf},
+  rc::Rc,
+  slice,
+};
 use std140::ReprStd140;
 
-use crate::{io, prelude::*};
+use crate::{capabilities::GlVariant, io, prelude::*, texture::Texture};
 
+// A linked shader program. The program is owned by this struct, but glow needs a &Context
+// to delete it, so like every other GL resource in the crate the owner is responsible for
+// calling Shader::dispose when it's done. Dropping a Shader leaks the program on the GPU.
+// SafeShader holds an Rc<Context> so that it can delete its program when dropped.
 pub struct Shader {
   id: GlProgram,
+
+  // Declared lengths of uniform arrays, looked up once per name
+  array_capacities: RefCell<HashMap<String, Option<usize>>>,
+
+  // Missing uniforms that have already been warned about by ActiveShader::warn_on_missing
+  warned_missing: RefCell<HashSet<String>>,
+
+  // Uniform arrays that have already been warned about for being bound too many elements
+  warned_truncated: RefCell<HashSet<String>>,
 }
 
 impl Shader {
@@ -18,88 +40,104 @@ impl Shader {
     vertex_path: impl AsRef<Path>,
     fragment_path: impl AsRef<Path>,
     geometry_path: Option<&Path>,
-  ) -> Result<Self> {
-    let vertex_path = vertex_path.as_ref();
+  ) -> Result<Self, ShaderError> {
+    Self::load_with_defs(gl, vertex_path, fragment_path, geometry_path, &[]).await
+  }
+
+  // Like load, adding each of defs with ShaderBuilder::with_def
+  pub async unsafe fn load_with_defs(
+    gl: &Context,
+    vertex_path: impl AsRef<Path>,
+    fragment_path: impl AsRef<Path>,
+    geometry_path: Option<&Path>,
+    defs: &[&'static str],
+  ) -> Result<Self, ShaderError> {
     let (vertex_source, fragment_source, geometry_source) = try_join!(
-      io::load_string(vertex_path),
-      io::load_string(fragment_path),
+      Self::load_source(vertex_path.as_ref()),
+      Self::load_source(fragment_path.as_ref()),
       async {
         match geometry_path {
-          Some(path) => {
-            let bytes = io::load_string(path).await;
-            bytes.map(|bytes| Some(bytes))
-          }
+          Some(path) => Self::load_source(path).await.map(Some),
           None => Ok(None),
         }
       }
     )?;
-    Self::new(gl, vertex_source, fragment_source, geometry_source)
-      .context(format!("With shader path {:?}", vertex_path))
+
+    // Name the program after its vertex shader so errors say which shader failed
+    let mut builder = ShaderBuilder::new()
+      .name(vertex_path.as_ref().display().to_string())
+      .vertex_source(vertex_source)
+      .fragment_source(fragment_source);
+    if let Some(geometry_source) = geometry_source {
+      builder = builder.geometry_source(geometry_source);
+    }
+    for def in defs {
+      builder = builder.with_def(*def);
+    }
+    builder.build(gl)
+  }
+
+  async fn load_source(path: &Path) -> Result<String, ShaderError> {
+    // Include the path in I/O errors, otherwise there's no telling which file failed
+    let with_path =
+      |err: std::io::Error| std::io::Error::new(err.kind(), format!("{}: {}", path.display(), err));
+    let bytes = io::load_file(path).await.map_err(with_path)?;
+    String::from_utf8(bytes)
+      .map_err(|err| with_path(std::io::Error::new(std::io::ErrorKind::InvalidData, err)).into())
   }
 
   pub unsafe fn new(
     gl: &Context,
-    mut vertex_source: String,
-    mut fragment_source: String,
-    mut geometry_source: Option<String>,
-  ) -> Result<Self> {
-    // Add directives needed for each platform
-    let header = if cfg!(target_arch = "wasm32") {
-      "#version 300 es\nprecision highp float;\n#define WASM\n"
-    } else {
-      "#version 330 core"
-    };
-
-    // Add struct definitions for all types in the crate
-    let defs = [
-      crate::camera::CameraBlock::BLOCK_DEF,
-      crate::material::Material::TYPE_DEF,
-      crate::light::PointLight::TYPE_DEF,
-      crate::light::DirLight::TYPE_DEF,
-      crate::light::SpotLight::TYPE_DEF,
-    ]
-    .join("\n");
-
-    let preprocess = |source| format!("{}\n{}\n{}", header, defs, source);
-
-    vertex_source = preprocess(vertex_source);
-    fragment_source = preprocess(fragment_source);
-    geometry_source = geometry_source.map(|s| preprocess(s));
-
-    // Compile individual shaders into OpenGL objects
-    let vertex_shader = Self::build_shader(&gl, glow::VERTEX_SHADER, &vertex_source)?;
-    let fragment_shader = Self::build_shader(&gl, glow::FRAGMENT_SHADER, &fragment_source)?;
-    let geometry_shader = geometry_source
-      .map(|s| Self::build_shader(&gl, glow::GEOMETRY_SHADER, &s))
-      .transpose()?;
-
-    // Link shaders into a single program
-    let shader_program = gl.create_program().unwrap();
-    gl.attach_shader(shader_program, vertex_shader);
-    gl.attach_shader(shader_program, fragment_shader);
-    if let Some(geometry_shader) = geometry_shader {
-      gl.attach_shader(shader_program, geometry_shader);
-    }
-
-    gl.link_program(shader_program);
-    if !gl.get_program_link_status(shader_program) {
-      bail!(
-        "Shader program failed to link with error: {}",
-        gl.get_program_info_log(shader_program)
-      );
-    }
+    vertex_source: String,
+    fragment_source: String,
+    geometry_source: Option<String>,
+  ) -> Result<Self, ShaderError> {
+    Self::new_with_defines(
+      gl,
+      vertex_source,
+      fragment_source,
+      geometry_source,
+      &ShaderDefines::new(),
+    )
+  }
 
-    // Cleanup shaders after linking
-    gl.delete_shader(vertex_shader);
-    gl.delete_shader(fragment_shader);
-    if let Some(geometry_shader) = geometry_shader {
-      gl.delete_shader(geometry_shader);
+  pub unsafe fn new_with_defines(
+    gl: &Context,
+    vertex_source: String,
+    fragment_source: String,
+    geometry_source: Option<String>,
+    defines: &ShaderDefines,
+  ) -> Result<Self, ShaderError> {
+    let mut builder = ShaderBuilder::new()
+      .vertex_source(vertex_source)
+      .fragment_source(fragment_source)
+      .defines(defines.clone());
+    if let Some(geometry_source) = geometry_source {
+      builder = builder.geometry_source(geometry_source);
     }
+    builder.build(gl)
+  }
 
-    Ok(Shader { id: shader_program })
+  // Build a shader from sources compiled into the binary, e.g. with include_str!, so no
+  // files need to be fetched at runtime. See the shader! macro.
+  pub unsafe fn from_static(
+    gl: &Context,
+    vertex_source: &'static str,
+    fragment_source: &'static str,
+  ) -> Result<Self, ShaderError> {
+    ShaderBuilder::new()
+      .vertex_source(vertex_source.to_owned())
+      .fragment_source(fragment_source.to_owned())
+      .build(gl)
   }
 
-  unsafe fn build_shader(gl: &Context, shader_type: u32, source: &str) -> Result<GlShader> {
+  unsafe fn build_shader(
+    gl: &Context,
+    shader_type: u32,
+    source: &str,
+  ) -> Result<GlShader, ShaderError> {
+    let stage = ShaderStage::from_gl(shader_type).ok_or(ShaderError::UnknownStage(shader_type))?;
+
     // Create a new OpenGL shader object
     let shader = gl.create_shader(shader_type).unwrap();
 
@@ -109,15 +147,11 @@ impl Shader {
     // Call the OpenGL shader compiler
     gl.compile_shader(shader);
     if !gl.get_shader_compile_status(shader) {
-      bail!(
-        "{} shader failed to compile with error: {}",
-        match shader_type {
-          glow::VERTEX_SHADER => "Vertex",
-          glow::FRAGMENT_SHADER => "Fragment",
-          _ => "???",
-        },
-        gl.get_shader_info_log(shader)
-      );
+      return Err(ShaderError::Compile {
+        name: None,
+        stage,
+        log: gl.get_shader_info_log(shader),
+      });
     }
 
     Ok(shader)
@@ -135,11 +169,635 @@ impl Shader {
     self.id
   }
 
+  // Declared length of a uniform array, e.g. 8 for `uniform PointLight point_lights[8]`.
+  // Arrays of basic types are listed once with their length, but arrays of structs are
+  // listed per member, so their length is one past the highest index seen. None if the
+  // array doesn't exist or was optimized out.
+  pub unsafe fn array_capacity(&self, gl: &Context, name: &str) -> Option<usize> {
+    if let Some(capacity) = self.array_capacities.borrow().get(name) {
+      return *capacity;
+    }
+
+    let capacity = (0..gl.get_active_uniforms(self.id))
+      .filter_map(|index| gl.get_active_uniform(self.id, index))
+      .filter_map(|uniform| array_element_capacity(name, &uniform.name, uniform.size))
+      .max();
+
+    self
+      .array_capacities
+      .borrow_mut()
+      .insert(name.to_owned(), capacity);
+    capacity
+  }
+
+  // List every uniform that survived linking, e.g. for debugging a shader's interface.
+  // Uniforms that the compiler optimized out won't appear here.
+  pub unsafe fn active_uniforms(&self, gl: &Context) -> Vec<(String, UniformType)> {
+    (0..gl.get_active_uniforms(self.id))
+      .filter_map(|index| gl.get_active_uniform(self.id, index))
+      .map(|uniform| (uniform.name, UniformType::from_gl(uniform.utype)))
+      .collect()
+  }
+
+  // Point the named uniform block in this program at a binding slot, i.e. the index a
+  // UniformBlock was bound to with UniformBlock::new or UniformBlock::bind_base
+  pub unsafe fn bind_uniform_block(
+    &self,
+    gl: &Context,
+    block_name: &str,
+    index: u32,
+  ) -> Result<()> {
+    let block = self
+      .block_location(gl, block_name)
+      .with_context(|| format!("Uniform block {:?} does not exist", block_name))?;
+    gl.uniform_block_binding(self.id, block, index);
+    Ok(())
+  }
+
+  // Like bind_uniform_block, but for a shader storage block bound with
+  // StorageBuffer::bind_base
+  #[cfg(not(target_arch = "wasm32"))]
+  pub unsafe fn bind_storage_block(
+    &self,
+    gl: &Context,
+    block_name: &str,
+    index: u32,
+  ) -> Result<()> {
+    let block = gl
+      .get_shader_storage_block_index(self.id, block_name)
+      .with_context(|| format!("Storage block {:?} does not exist", block_name))?;
+    gl.shader_storage_block_binding(self.id, block, index);
+    Ok(())
+  }
+
   // I wanted to call this "use" but that's a Rust keyword :'(
   pub unsafe fn activate(&self, gl: &Context) -> ActiveShader {
     gl.use_program(Some(self.id));
     ActiveShader::new(self)
   }
+
+  // Free the GL program. Consumes the shader so it can't be activated afterwards.
+  pub unsafe fn dispose(self, gl: &Context) {
+    gl.delete_program(self.id);
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+  Vertex,
+  TessControl,
+  TessEvaluation,
+  Geometry,
+  Fragment,
+  Compute,
+}
+
+impl ShaderStage {
+  fn from_gl(shader_type: u32) -> Option<Self> {
+    Some(match shader_type {
+      glow::VERTEX_SHADER => ShaderStage::Vertex,
+      glow::TESS_CONTROL_SHADER => ShaderStage::TessControl,
+      glow::TESS_EVALUATION_SHADER => ShaderStage::TessEvaluation,
+      glow::GEOMETRY_SHADER => ShaderStage::Geometry,
+      glow::FRAGMENT_SHADER => ShaderStage::Fragment,
+      glow::COMPUTE_SHADER => ShaderStage::Compute,
+      _ => return None,
+    })
+  }
+}
+
+// Everything that can go wrong while building a Shader, so callers can tell a typo in
+// GLSL apart from a missing file.
+//
+// `name` identifies which logical shader failed, as set by ShaderBuilder::name.
+#[derive(Debug)]
+pub enum ShaderError {
+  Compile {
+    name: Option<String>,
+    stage: ShaderStage,
+    log: String,
+  },
+  Link {
+    name: Option<String>,
+    log: String,
+  },
+  MissingStage(ShaderStage),
+  UnknownStage(u32),
+  Io(std::io::Error),
+}
+
+impl ShaderError {
+  fn with_name(mut self, new_name: Option<String>) -> Self {
+    match &mut self {
+      ShaderError::Compile { name, .. } | ShaderError::Link { name, .. } => {
+        *name = name.take().or(new_name);
+      }
+      _ => {}
+    }
+    self
+  }
+}
+
+impl fmt::Display for ShaderError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let named = |name: &Option<String>| match name {
+      Some(name) => format!(" ({})", name),
+      None => String::new(),
+    };
+
+    match self {
+      ShaderError::Compile { name, stage, log } => write!(
+        f,
+        "{:?} shader{} failed to compile with error: {}",
+        stage,
+        named(name),
+        log
+      ),
+      ShaderError::Link { name, log } => write!(
+        f,
+        "Shader program{} failed to link with error: {}",
+        named(name),
+        log
+      ),
+      ShaderError::MissingStage(stage) => write!(f, "Shader program has no {:?} stage", stage),
+      ShaderError::UnknownStage(shader_type) => {
+        write!(f, "Unknown shader type {:#x}", shader_type)
+      }
+      ShaderError::Io(err) => write!(f, "Failed to load shader source: {}", err),
+    }
+  }
+}
+
+impl std::error::Error for ShaderError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      ShaderError::Io(err) => Some(err),
+      _ => None,
+    }
+  }
+}
+
+impl From<std::io::Error> for ShaderError {
+  fn from(err: std::io::Error) -> Self {
+    ShaderError::Io(err)
+  }
+}
+
+// GLSL type of an active uniform, as reported by glGetActiveUniform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UniformType {
+  Float,
+  Vec2,
+  Vec3,
+  Vec4,
+  Int,
+  UInt,
+  Bool,
+  Mat3,
+  Mat4,
+  Sampler2D,
+  SamplerCube,
+  Other(u32),
+}
+
+impl UniformType {
+  fn from_gl(utype: u32) -> Self {
+    match utype {
+      glow::FLOAT => UniformType::Float,
+      glow::FLOAT_VEC2 => UniformType::Vec2,
+      glow::FLOAT_VEC3 => UniformType::Vec3,
+      glow::FLOAT_VEC4 => UniformType::Vec4,
+      glow::INT => UniformType::Int,
+      glow::UNSIGNED_INT => UniformType::UInt,
+      glow::BOOL => UniformType::Bool,
+      glow::FLOAT_MAT3 => UniformType::Mat3,
+      glow::FLOAT_MAT4 => UniformType::Mat4,
+      glow::SAMPLER_2D => UniformType::Sampler2D,
+      glow::SAMPLER_CUBE => UniformType::SamplerCube,
+      other => UniformType::Other(other),
+    }
+  }
+}
+
+// Collects the stages and preprocessor options for a shader program, then compiles and
+// links them all at once with ShaderBuilder::build.
+#[derive(Default)]
+pub struct ShaderBuilder {
+  name: Option<String>,
+  vertex_source: Option<String>,
+  fragment_source: Option<String>,
+  geometry_source: Option<String>,
+  tess_control_source: Option<String>,
+  tess_evaluation_source: Option<String>,
+  transform_feedback: Option<(Vec<String>, u32)>,
+  defines: ShaderDefines,
+  include_dirs: Vec<PathBuf>,
+  // GLSL added after the built-in struct definitions, see with_def
+  defs: Vec<&'static str>,
+  builtins: bool,
+}
+
+impl ShaderBuilder {
+  pub fn new() -> Self {
+    ShaderBuilder::default()
+  }
+
+  // Name to report in compile and link errors, e.g. a file path or a variant description
+  pub fn name(mut self, name: impl Into<String>) -> Self {
+    self.name = Some(name.into());
+    self
+  }
+
+  pub fn vertex_source(mut self, source: String) -> Self {
+    self.vertex_source = Some(source);
+    self
+  }
+
+  pub fn fragment_source(mut self, source: String) -> Self {
+    self.fragment_source = Some(source);
+    self
+  }
+
+  pub fn geometry_source(mut self, source: String) -> Self {
+    self.geometry_source = Some(source);
+    self
+  }
+
+  // Tessellation stages must be provided together
+  pub fn tess_control_source(mut self, source: String) -> Self {
+    self.tess_control_source = Some(source);
+    self
+  }
+
+  pub fn tess_evaluation_source(mut self, source: String) -> Self {
+    self.tess_evaluation_source = Some(source);
+    self
+  }
+
+  // Capture the named vertex shader outputs with transform feedback. `mode` is either
+  // glow::INTERLEAVED_ATTRIBS or glow::SEPARATE_ATTRIBS.
+  pub fn transform_feedback_varyings(mut self, names: &[&str], mode: u32) -> Self {
+    let names = names.iter().map(|name| name.to_string()).collect();
+    self.transform_feedback = Some((names, mode));
+    self
+  }
+
+  // Declare Shadertoy-style uniforms (iTime, iResolution, ...) in every stage, to be bound
+  // with BuiltinUniforms::bind. Fragment shaders may define mainImage instead of main.
+  pub fn with_builtins(mut self) -> Self {
+    self.builtins = true;
+    self
+  }
+
+  pub fn defines(mut self, defines: ShaderDefines) -> Self {
+    self.defines = defines;
+    self
+  }
+
+  // Directory to search for files named in `#include "file"` directives. Directories are
+  // searched in the order they were added.
+  pub fn include_dir(mut self, dir: PathBuf) -> Self {
+    self.include_dirs.push(dir);
+    self
+  }
+
+  // Declare a uniform block that isn't built in to every shader, e.g. BoneBlock for
+  // skinning.vert, which is too large to give to every shader
+  pub fn with_block_def<T: ShaderBlockDef>(self) -> Self {
+    self.with_def(T::BLOCK_DEF)
+  }
+
+  // Add GLSL helpers that aren't built in to every shader, e.g. PbrMaterial::PBR_DEF. They
+  // go after the built-in structs in the order they were added, so a def that calls into
+  // another one has to come after it.
+  pub fn with_def(mut self, def: &'static str) -> Self {
+    if !self.defs.contains(&def) {
+      self.defs.push(def);
+    }
+    self
+  }
+
+  pub unsafe fn build(self, gl: &Context) -> Result<Shader, ShaderError> {
+    self
+      .build_program(gl)
+      .map_err(|err| err.with_name(self.name.clone()))
+  }
+
+  unsafe fn build_program(&self, gl: &Context) -> Result<Shader, ShaderError> {
+    let variant = gl.capabilities().variant;
+    if self.vertex_source.is_none() {
+      return Err(ShaderError::MissingStage(ShaderStage::Vertex));
+    }
+    if self.fragment_source.is_none() {
+      return Err(ShaderError::MissingStage(ShaderStage::Fragment));
+    }
+
+    // Tessellation needs both stages, otherwise the program fails to link
+    if self.tess_control_source.is_some() != self.tess_evaluation_source.is_some() {
+      return Err(ShaderError::Link {
+        name: None,
+        log: "tessellation control and evaluation stages must be provided together".to_owned(),
+      });
+    }
+
+    let stages = [
+      (glow::VERTEX_SHADER, self.vertex_source.as_ref()),
+      (glow::TESS_CONTROL_SHADER, self.tess_control_source.as_ref()),
+      (
+        glow::TESS_EVALUATION_SHADER,
+        self.tess_evaluation_source.as_ref(),
+      ),
+      (glow::GEOMETRY_SHADER, self.geometry_source.as_ref()),
+      (glow::FRAGMENT_SHADER, self.fragment_source.as_ref()),
+    ];
+
+    // Compile individual shaders into OpenGL objects
+    let shaders = stages
+      .iter()
+      .filter_map(|(shader_type, source)| source.map(|source| (*shader_type, source)))
+      .map(|(shader_type, source)| {
+        let source = self.preprocess(variant, shader_type, source)?;
+        Shader::build_shader(gl, shader_type, &source)
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    self.link(gl, shaders)
+  }
+
+  unsafe fn link(&self, gl: &Context, shaders: Vec<GlShader>) -> Result<Shader, ShaderError> {
+    // Link shaders into a single program
+    let shader_program = gl.create_program().unwrap();
+    for shader in &shaders {
+      gl.attach_shader(shader_program, *shader);
+    }
+
+    // Transform feedback outputs have to be declared before linking
+    if let Some((names, mode)) = &self.transform_feedback {
+      let names = names.iter().map(|name| name.as_str()).collect::<Vec<_>>();
+      gl.transform_feedback_varyings(shader_program, &names, *mode);
+    }
+
+    gl.link_program(shader_program);
+    if !gl.get_program_link_status(shader_program) {
+      return Err(ShaderError::Link {
+        name: None,
+        log: gl.get_program_info_log(shader_program),
+      });
+    }
+
+    // Cleanup shaders after linking
+    for shader in shaders {
+      gl.delete_shader(shader);
+    }
+
+    Ok(Shader {
+      id: shader_program,
+      array_capacities: RefCell::default(),
+      warned_missing: RefCell::default(),
+      warned_truncated: RefCell::default(),
+    })
+  }
+
+  fn preprocess(
+    &self,
+    variant: GlVariant,
+    shader_type: u32,
+    source: &str,
+  ) -> Result<String, ShaderError> {
+    // Add directives needed for each platform. GLES is defined for both ES dialects, e.g.
+    // to avoid interface blocks, and WASM only in the browser.
+    let header = match variant {
+      GlVariant::Desktop330 => "#version 330 core",
+      GlVariant::Es300 => "#version 300 es\nprecision highp float;\n#define GLES\n",
+      GlVariant::WebGl2 => "#version 300 es\nprecision highp float;\n#define GLES\n#define WASM\n",
+    };
+
+    // Tessellation is core in GL 4.0, so on 3.3 it has to be enabled as an extension
+    let header = match shader_type {
+      glow::TESS_CONTROL_SHADER | glow::TESS_EVALUATION_SHADER => {
+        format!(
+          "{}\n#extension GL_ARB_tessellation_shader : require",
+          header
+        )
+      }
+      _ => header.to_owned(),
+    };
+
+    // User defines go right after the header so type defs and sources can #ifdef on them
+    let defines = self.defines.to_glsl();
+
+    // Add struct definitions for all types in the crate, plus any registered by the user
+    let defs = [
+      crate::camera::CameraBlock::BLOCK_DEF,
+      crate::camera::DepthParams::TYPE_DEF,
+      crate::material::Material::TYPE_DEF,
+      crate::material::PbrMaterial::TYPE_DEF,
+      crate::material::ParallaxMaterial::TYPE_DEF,
+      crate::ibl::IblMaps::TYPE_DEF,
+      crate::light::PointLight::TYPE_DEF,
+      crate::light::DirLight::TYPE_DEF,
+      crate::light::SpotLight::TYPE_DEF,
+      crate::shadow_map::ShadowParams::TYPE_DEF,
+      crate::point_shadow::PointShadowParams::TYPE_DEF,
+      crate::cascaded_shadow_map::CascadeParams::TYPE_DEF,
+      crate::depth_peeling::PeelParams::TYPE_DEF,
+    ]
+    .iter()
+    .chain(&self.defs)
+    .copied()
+    .chain(REGISTERED_DEFS.with(|defs| defs.borrow().clone()))
+    .collect::<Vec<_>>()
+    .join("\n");
+
+    let builtins = if self.builtins { BUILTINS_DEF } else { "" };
+
+    let mut source = self.resolve_includes(source, 0)?;
+
+    // Shadertoy fragment shaders define mainImage instead of main, so call it from one
+    if self.builtins
+      && shader_type == glow::FRAGMENT_SHADER
+      && source.contains("mainImage")
+      && !source.contains("void main")
+    {
+      source.push_str(SHADERTOY_MAIN);
+    }
+
+    Ok(format!(
+      "{}\n{}\n{}\n{}\n{}",
+      header, defines, defs, builtins, source
+    ))
+  }
+
+  // Replace each `#include "file"` line with the contents of file, recursively
+  fn resolve_includes(&self, source: &str, depth: usize) -> Result<String, ShaderError> {
+    const MAX_INCLUDE_DEPTH: usize = 16;
+
+    let mut output = String::with_capacity(source.len());
+    for line in source.lines() {
+      match parse_include(line)? {
+        Some(name) => {
+          if depth >= MAX_INCLUDE_DEPTH {
+            return Err(ShaderError::Io(std::io::Error::new(
+              std::io::ErrorKind::Other,
+              format!(
+                "#include \"{}\" is nested too deeply (cyclic include?)",
+                name
+              ),
+            )));
+          }
+
+          let contents = self.read_include(name)?;
+          output.push_str(&self.resolve_includes(&contents, depth + 1)?);
+        }
+        None => output.push_str(line),
+      }
+      output.push('\n');
+    }
+
+    Ok(output)
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  fn read_include(&self, name: &str) -> Result<String, ShaderError> {
+    self
+      .include_dirs
+      .iter()
+      .map(|dir| dir.join(name))
+      .find(|path| path.exists())
+      .map(std::fs::read_to_string)
+      .unwrap_or_else(|| {
+        Err(std::io::Error::new(
+          std::io::ErrorKind::NotFound,
+          format!("#include \"{}\" not found in {:?}", name, self.include_dirs),
+        ))
+      })
+      .map_err(ShaderError::Io)
+  }
+
+  // Files can only be fetched asynchronously on the web, so includes aren't supported
+  #[cfg(target_arch = "wasm32")]
+  fn read_include(&self, name: &str) -> Result<String, ShaderError> {
+    Err(ShaderError::Io(std::io::Error::new(
+      std::io::ErrorKind::Other,
+      format!(
+        "#include \"{}\": includes are not supported on the web",
+        name
+      ),
+    )))
+  }
+}
+
+// Parses an `#include "file"` directive, returning the file name. Lines that don't start with
+// `#include` are None, and anything else starting with it (e.g. `#includefoo`, `#include foo`)
+// is an error rather than being passed through to the GLSL compiler.
+fn parse_include(line: &str) -> Result<Option<&str>, ShaderError> {
+  let rest = match line.trim().strip_prefix("#include") {
+    Some(rest) => rest,
+    None => return Ok(None),
+  };
+
+  let name = if rest.starts_with(char::is_whitespace) {
+    rest
+      .trim()
+      .strip_prefix('"')
+      .and_then(|rest| rest.strip_suffix('"'))
+      .filter(|name| !name.is_empty() && !name.contains('"'))
+  } else {
+    None
+  };
+
+  name.map(Some).ok_or_else(|| {
+    ShaderError::Io(std::io::Error::new(
+      std::io::ErrorKind::InvalidData,
+      format!(
+        "malformed include, expected `#include \"file\"`: {}",
+        line.trim()
+      ),
+    ))
+  })
+}
+
+// Preprocessor #defines injected into a shader, e.g. for compile-time feature toggles.
+// Kept sorted so the same set of defines always produces the same source.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShaderDefines(BTreeMap<String, String>);
+
+impl ShaderDefines {
+  pub fn new() -> Self {
+    ShaderDefines::default()
+  }
+
+  // Adds `#define name value`
+  pub fn with(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+    self.0.insert(name.into(), value.to_string());
+    self
+  }
+
+  // Adds a bare `#define name` for use with #ifdef
+  pub fn with_flag(mut self, name: impl Into<String>) -> Self {
+    self.0.insert(name.into(), String::new());
+    self
+  }
+
+  fn to_glsl(&self) -> String {
+    self
+      .0
+      .iter()
+      .map(|(name, value)| format!("#define {} {}\n", name, value))
+      .collect()
+  }
+}
+
+const BUILTINS_DEF: &str = r#"
+uniform float iTime;
+uniform float iTimeDelta;
+uniform int iFrame;
+uniform vec3 iResolution;
+uniform vec4 iMouse;
+"#;
+
+const SHADERTOY_MAIN: &str = r#"
+out vec4 shadertoy_FragColor;
+
+void main() {
+  mainImage(shadertoy_FragColor, gl_FragCoord.xy);
+}
+"#;
+
+// Values for the uniforms declared by ShaderBuilder::with_builtins, named as in Shadertoy
+#[derive(Debug, Clone, Default)]
+pub struct BuiltinUniforms {
+  // Seconds since the first tick
+  pub time: f32,
+  pub time_delta: f32,
+  pub frame: i32,
+  // Viewport size in pixels
+  pub resolution: Vec2,
+  // Pixel position while the button is held in xy, and where it was pressed in zw
+  pub mouse: Vec4,
+}
+
+impl BuiltinUniforms {
+  // Advance the clock by one frame, e.g. with the dt passed to the draw callback
+  pub fn tick(&mut self, dt: f32, width: u32, height: u32) {
+    self.time += dt;
+    self.time_delta = dt;
+    self.frame += 1;
+    self.resolution = glm::vec2(width as f32, height as f32);
+  }
+
+  pub unsafe fn bind(&self, gl: &Context, shader: &mut ActiveShader) {
+    shader.bind_uniform(gl, "iTime", &self.time);
+    shader.bind_uniform(gl, "iTimeDelta", &self.time_delta);
+    shader.bind_uniform(gl, "iFrame", &self.frame);
+    // The third component is the pixel aspect ratio, which is always square here
+    shader.bind_uniform(
+      gl,
+      "iResolution",
+      &glm::vec3(self.resolution.x, self.resolution.y, 1.),
+    );
+    shader.bind_uniform(gl, "iMouse", &self.mouse);
+  }
 }
 
 // Trait for custom shader structs that contains a GLSL type definition
@@ -151,9 +809,35 @@ pub trait ShaderBlockDef {
   const BLOCK_DEF: &'static str;
 }
 
+// Definitions registered outside the crate's built-in list, prepended to every shader
+// compiled afterwards. GL is single-threaded, so a thread-local is enough.
+thread_local! {
+  static REGISTERED_DEFS: RefCell<Vec<&'static str>> = RefCell::new(vec![]);
+}
+
+// Make a custom struct available to all subsequently compiled shaders
+pub fn register_type_def<T: ShaderTypeDef>() {
+  register_def(T::TYPE_DEF);
+}
+
+// Make a custom uniform block available to all subsequently compiled shaders
+pub fn register_block_def<T: ShaderBlockDef>() {
+  register_def(T::BLOCK_DEF);
+}
+
+fn register_def(def: &'static str) {
+  REGISTERED_DEFS.with(|defs| {
+    let mut defs = defs.borrow_mut();
+    if !defs.contains(&def) {
+      defs.push(def);
+    }
+  });
+}
+
 pub struct ActiveShader<'a> {
   shader: &'a Shader,
   num_textures: u32,
+  warn_on_missing: bool,
 }
 
 // TODO: this API still doesn't feel quite right wrt handling texture slots
@@ -162,9 +846,16 @@ impl<'a> ActiveShader<'a> {
     ActiveShader {
       shader,
       num_textures: 0,
+      warn_on_missing: false,
     }
   }
 
+  // Print a warning the first time a uniform is bound to a name that doesn't exist in the
+  // program. Off by default since the compiler legitimately strips out unused uniforms.
+  pub fn warn_on_missing(&mut self, warn: bool) {
+    self.warn_on_missing = warn;
+  }
+
   pub fn new_texture_slot(&mut self) -> u32 {
     let slot = self.num_textures;
     self.num_textures += 1;
@@ -180,16 +871,181 @@ impl<'a> ActiveShader<'a> {
   }
 
   pub unsafe fn location(&self, gl: &Context, name: &str) -> Option<GlUniformLocation> {
-    self.shader.location(gl, name)
+    let location = self.shader.location(gl, name);
+    if location.is_none()
+      && self.warn_on_missing
+      && self
+        .shader
+        .warned_missing
+        .borrow_mut()
+        .insert(name.to_owned())
+    {
+      log::warn!("Uniform {:?} does not exist in the active shader", name);
+    }
+    location
   }
 
   pub unsafe fn block_location(&self, gl: &Context, name: &str) -> Option<u32> {
     self.shader.block_location(gl, name)
   }
 
+  pub unsafe fn array_capacity(&self, gl: &Context, name: &str) -> Option<usize> {
+    self.shader.array_capacity(gl, name)
+  }
+
+  // How many of len elements fit in the uniform array `name`. Elements past the end of the
+  // shader's array would be silently dropped by the driver, so callers leave them out. This
+  // is warned about the first time it happens for each array of a shader.
+  unsafe fn array_len(&self, gl: &Context, name: &str, len: usize) -> usize {
+    match self.array_capacity(gl, name) {
+      Some(capacity) if len > capacity => {
+        let first = self
+          .shader
+          .warned_truncated
+          .borrow_mut()
+          .insert(name.to_owned());
+        if first {
+          log::warn!(
+            "{} elements bound to uniform array {:?}, which only holds {}",
+            len,
+            name,
+            capacity
+          );
+        }
+        capacity
+      }
+      _ => len,
+    }
+  }
+
   pub fn reset_textures(&mut self) {
     self.num_textures = 0;
   }
+
+  // Bind a set of uniforms whose names and types are only known at runtime
+  pub unsafe fn bind_uniforms(&mut self, gl: &Context, uniforms: &HashMap<String, UniformValue>) {
+    for (name, value) in uniforms {
+      self.bind_uniform(gl, name, value);
+    }
+  }
+}
+
+// Dynamically-typed uniform value, e.g. for materials edited at runtime
+#[derive(Clone)]
+pub enum UniformValue {
+  Bool(bool),
+  Int(i32),
+  UInt(u32),
+  Float(f32),
+  Vec3(Vec3),
+  Vec4([f32; 4]),
+  Mat3(Mat3),
+  Mat4(Mat4),
+  Texture(Texture),
+}
+
+impl BindUniform for UniformValue {
+  unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
+    match self {
+      UniformValue::Bool(value) => value.bind_uniform(gl, shader, name),
+      UniformValue::Int(value) => value.bind_uniform(gl, shader, name),
+      UniformValue::UInt(value) => value.bind_uniform(gl, shader, name),
+      UniformValue::Float(value) => value.bind_uniform(gl, shader, name),
+      UniformValue::Vec3(value) => value.bind_uniform(gl, shader, name),
+      UniformValue::Vec4(value) => value.bind_uniform(gl, shader, name),
+      UniformValue::Mat3(value) => value.bind_uniform(gl, shader, name),
+      UniformValue::Mat4(value) => value.bind_uniform(gl, shader, name),
+      UniformValue::Texture(value) => value.bind_uniform(gl, shader, name),
+    }
+  }
+}
+
+// Safe layer over Shader for callers that don't want to write `unsafe` everywhere. Holding
+// an Rc<Context> means the program can be deleted automatically when the shader is dropped.
+pub struct SafeShader {
+  gl: Rc<Context>,
+  shader: Option<Shader>,
+}
+
+impl SafeShader {
+  pub async fn load(
+    gl: Rc<Context>,
+    vertex_path: impl AsRef<Path>,
+    fragment_path: impl AsRef<Path>,
+    geometry_path: Option<&Path>,
+  ) -> Result<Self> {
+    let shader = unsafe { Shader::load(&gl, vertex_path, fragment_path, geometry_path).await? };
+    Ok(SafeShader {
+      gl,
+      shader: Some(shader),
+    })
+  }
+
+  pub fn new(
+    gl: Rc<Context>,
+    vertex_source: String,
+    fragment_source: String,
+    geometry_source: Option<String>,
+  ) -> Result<Self> {
+    let shader = unsafe { Shader::new(&gl, vertex_source, fragment_source, geometry_source)? };
+    Ok(SafeShader {
+      gl,
+      shader: Some(shader),
+    })
+  }
+
+  pub fn activate(&self) -> SafeActiveShader {
+    let shader = self.shader.as_ref().unwrap();
+    SafeActiveShader {
+      gl: &self.gl,
+      shader: unsafe { shader.activate(&self.gl) },
+    }
+  }
+
+  pub fn active_uniforms(&self) -> Vec<(String, UniformType)> {
+    unsafe { self.shader.as_ref().unwrap().active_uniforms(&self.gl) }
+  }
+}
+
+impl Drop for SafeShader {
+  fn drop(&mut self) {
+    if let Some(shader) = self.shader.take() {
+      unsafe { shader.dispose(&self.gl) };
+    }
+  }
+}
+
+pub struct SafeActiveShader<'a> {
+  gl: &'a Context,
+  shader: ActiveShader<'a>,
+}
+
+impl<'a> SafeActiveShader<'a> {
+  pub fn bind_uniform<T: BindUniform>(&mut self, name: &str, value: &T) {
+    unsafe { self.shader.bind_uniform(self.gl, name, value) };
+  }
+
+  pub fn warn_on_missing(&mut self, warn: bool) {
+    self.shader.warn_on_missing(warn);
+  }
+
+  // Escape hatch for passing the shader to the unsafe draw methods
+  pub fn inner(&mut self) -> &mut ActiveShader<'a> {
+    &mut self.shader
+  }
+}
+
+// How many elements of the array `name` an active uniform implies, given the uniform's
+// reflected name and size. `lights[0]` (a basic type) carries the whole length as its
+// size, while `lights[3].color` (a struct member) only shows that index 3 exists.
+fn array_element_capacity(name: &str, uniform_name: &str, size: i32) -> Option<usize> {
+  let rest = uniform_name.strip_prefix(name)?.strip_prefix('[')?;
+  if rest == "0]" {
+    Some(size as usize)
+  } else {
+    let index = rest[..rest.find(']')?].parse::<usize>().ok()?;
+    Some(index + 1)
+  }
 }
 
 // A Rustic way to expose the uniform_* methods is to have a single trait which
@@ -198,15 +1054,57 @@ pub trait BindUniform {
   unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str);
 }
 
+// Elements that don't fit in the shader's array are left out (see ActiveShader::array_len),
+// and the _len reflects what was actually bound
 impl<T: BindUniform> BindUniform for Vec<T> {
   unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
-    shader.bind_uniform(gl, &format!("{}_len", name), &(self.len() as i32));
-    for (i, value) in self.iter().enumerate() {
+    let len = shader.array_len(gl, name, self.len());
+
+    shader.bind_uniform(gl, &format!("{}_len", name), &(len as i32));
+    for (i, value) in self.iter().take(len).enumerate() {
       shader.bind_uniform(gl, &format!("{}[{}]", name, i), value);
     }
   }
 }
 
+// Unlike the Vec impl, which binds each element (and a _len) separately, this uploads the
+// whole array in a single call. Use it for large `uniform vec3 name[N]` arrays that are
+// always filled. Like the Vec impl, elements that don't fit in the array are left out.
+impl BindUniform for &[Vec3] {
+  unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
+    let len = shader.array_len(gl, name, self.len());
+    let values = self[..len]
+      .iter()
+      .flat_map(|v| v.iter().copied())
+      .collect::<Vec<_>>();
+    gl.uniform_3_f32_slice(shader.location(gl, name).as_ref(), &values);
+  }
+}
+
+// Single-call upload for `uniform mat4 name[N]`, e.g. bone or instance matrices. Each
+// matrix is already column-major, so they're just laid end to end. Matrices that don't fit
+// in the array are left out.
+impl BindUniform for &[Mat4] {
+  unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
+    let len = shader.array_len(gl, name, self.len());
+    let values = self[..len]
+      .iter()
+      .flat_map(|m| m.iter().copied())
+      .collect::<Vec<_>>();
+    gl.uniform_matrix_4_f32_slice(shader.location(gl, name).as_ref(), false, &values);
+  }
+}
+
+// Binding None leaves the uniform untouched, so optional fields don't need a branch at
+// every call site
+impl<T: BindUniform> BindUniform for Option<T> {
+  unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
+    if let Some(value) = self {
+      value.bind_uniform(gl, shader, name);
+    }
+  }
+}
+
 impl<T: BindUniform> BindUniform for &T {
   unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
     (*self).bind_uniform(gl, shader, name);
@@ -252,12 +1150,54 @@ impl BindUniform for u32 {
   }
 }
 
+impl BindUniform for Vec2 {
+  unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
+    gl.uniform_2_f32(shader.location(gl, name).as_ref(), self.x, self.y);
+  }
+}
+
 impl BindUniform for Vec3 {
   unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
     gl.uniform_3_f32(shader.location(gl, name).as_ref(), self.x, self.y, self.z);
   }
 }
 
+impl BindUniform for Vec4 {
+  unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
+    gl.uniform_4_f32(
+      shader.location(gl, name).as_ref(),
+      self.x,
+      self.y,
+      self.z,
+      self.w,
+    );
+  }
+}
+
+impl BindUniform for glm::IVec2 {
+  unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
+    gl.uniform_2_i32(shader.location(gl, name).as_ref(), self.x, self.y);
+  }
+}
+
+impl BindUniform for glm::IVec3 {
+  unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
+    gl.uniform_3_i32(shader.location(gl, name).as_ref(), self.x, self.y, self.z);
+  }
+}
+
+impl BindUniform for glm::IVec4 {
+  unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
+    gl.uniform_4_i32(
+      shader.location(gl, name).as_ref(),
+      self.x,
+      self.y,
+      self.z,
+      self.w,
+    );
+  }
+}
+
 impl BindUniform for Mat3 {
   unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
     gl.uniform_matrix_3_f32_slice(shader.location(gl, name).as_ref(), false, self.as_slice());
@@ -312,15 +1252,30 @@ impl<T: ReprStd140> UniformBlock<T> {
     gl.buffer_sub_data_u8_slice(glow::UNIFORM_BUFFER, 0, data);
     gl.bind_buffer(glow::UNIFORM_BUFFER, None);
   }
+
+  // Move the buffer to a different binding slot. Shaders bound to the old slot need to be
+  // rebound with Shader::bind_uniform_block.
+  pub unsafe fn bind_base(&mut self, gl: &Context, index: u32) {
+    self.binding = index;
+    gl.bind_buffer_base(glow::UNIFORM_BUFFER, index, Some(self.ubo));
+  }
+
+  pub fn binding(&self) -> u32 {
+    self.binding
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    gl.delete_buffer(self.ubo);
+  }
 }
 
 impl<T: ReprStd140> BindUniform for UniformBlock<T> {
+  // Blocks that the shader doesn't use are optimized out, so skip them like any other
+  // missing uniform instead of failing
   unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
-    gl.uniform_block_binding(
-      shader.program(),
-      shader.block_location(gl, name).unwrap(),
-      self.binding,
-    );
+    if let Some(block) = shader.block_location(gl, name) {
+      gl.uniform_block_binding(shader.program(), block, self.binding);
+    }
   }
 }
 
@@ -357,3 +1312,113 @@ impl GlmStd140Ext<std140::mat4x4> for Mat4 {
     )
   }
 }
+
+// Embed a shader's sources in the binary. Paths are relative to the file using the macro,
+// like include_str!.
+#[macro_export]
+macro_rules! shader {
+  ($gl:expr, $vertex_path:literal, $fragment_path:literal) => {
+    $crate::shader::Shader::from_static(
+      $gl,
+      include_str!($vertex_path),
+      include_str!($fragment_path),
+    )
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn array_element_capacity_parses_reflected_names() {
+    // Arrays of basic types are reflected once with their length
+    assert_eq!(array_element_capacity("weights", "weights[0]", 8), Some(8));
+    // Struct arrays are reflected per member, so each one shows an index
+    assert_eq!(
+      array_element_capacity("point_lights", "point_lights[3].color", 1),
+      Some(4)
+    );
+    assert_eq!(
+      array_element_capacity("point_lights", "point_lights[0].color", 1),
+      Some(1)
+    );
+    // Other uniforms, including ones that share a prefix, don't count
+    assert_eq!(array_element_capacity("lights", "lights_len", 1), None);
+    assert_eq!(array_element_capacity("lights", "lightsx[0]", 4), None);
+    assert_eq!(array_element_capacity("lights", "lights", 1), None);
+    assert_eq!(array_element_capacity("lights", "lights[x]", 1), None);
+  }
+
+  fn include_dir(files: &[(&str, &str)]) -> PathBuf {
+    static NEXT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+      "learn-opengl-includes-{}-{}",
+      std::process::id(),
+      NEXT.fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, contents) in files {
+      std::fs::write(dir.join(name), contents).unwrap();
+    }
+    dir
+  }
+
+  fn resolve(files: &[(&str, &str)], source: &str) -> Result<String, ShaderError> {
+    let builder = ShaderBuilder::new().include_dir(include_dir(files));
+    builder.resolve_includes(source, 0)
+  }
+
+  #[test]
+  fn parse_include_accepts_quoted_names() {
+    assert_eq!(
+      parse_include("#include \"a.glsl\"").unwrap(),
+      Some("a.glsl")
+    );
+    assert_eq!(
+      parse_include("  #include\t\"b.glsl\"  ").unwrap(),
+      Some("b.glsl")
+    );
+    assert_eq!(parse_include("void main() {}").unwrap(), None);
+  }
+
+  #[test]
+  fn parse_include_rejects_malformed_directives() {
+    assert!(parse_include("#includefoo").is_err());
+    assert!(parse_include("#include\"a.glsl\"").is_err());
+    assert!(parse_include("#include a.glsl").is_err());
+    assert!(parse_include("#include \"a.glsl").is_err());
+    assert!(parse_include("#include \"\"").is_err());
+    assert!(parse_include("#include").is_err());
+  }
+
+  #[test]
+  fn resolve_includes_inlines_nested_files() {
+    let output = resolve(
+      &[
+        ("a.glsl", "#include \"b.glsl\"\nfloat a;"),
+        ("b.glsl", "float b;"),
+      ],
+      "#include \"a.glsl\"\nvoid main() {}",
+    )
+    .unwrap();
+    let lines: Vec<_> = output.lines().filter(|line| !line.is_empty()).collect();
+    assert_eq!(lines, ["float b;", "float a;", "void main() {}"]);
+  }
+
+  #[test]
+  fn resolve_includes_reports_missing_files() {
+    assert!(resolve(&[], "#include \"missing.glsl\"").is_err());
+  }
+
+  #[test]
+  fn resolve_includes_stops_cycles() {
+    let result = resolve(&[("a.glsl", "#include \"a.glsl\"")], "#include \"a.glsl\"");
+    assert!(result.is_err());
+  }
+
+  #[test]
+  fn resolve_includes_rejects_malformed_directives() {
+    assert!(resolve(&[("foo", "float foo;")], "#includefoo").is_err());
+  }
+}
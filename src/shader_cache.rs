@@ -0,0 +1,58 @@
+use std::{
+  collections::{hash_map::DefaultHasher, HashMap},
+  hash::{Hash, Hasher},
+};
+
+use crate::{
+  prelude::*,
+  shader::{Shader, ShaderDefines},
+};
+
+// Compiled variants of the same shader sources under different sets of defines, so an
+// uber-shader only gets compiled once per feature combination.
+#[derive(Default)]
+pub struct ShaderCache {
+  shaders: HashMap<(u64, ShaderDefines), Shader>,
+}
+
+impl ShaderCache {
+  pub fn new() -> Self {
+    ShaderCache::default()
+  }
+
+  pub unsafe fn get_or_compile(
+    &mut self,
+    gl: &Context,
+    vertex_source: &str,
+    fragment_source: &str,
+    geometry_source: Option<&str>,
+    defines: &ShaderDefines,
+  ) -> Result<&Shader> {
+    let mut hasher = DefaultHasher::new();
+    (vertex_source, fragment_source, geometry_source).hash(&mut hasher);
+    let key = (hasher.finish(), defines.clone());
+
+    if !self.shaders.contains_key(&key) {
+      let shader = Shader::new_with_defines(
+        gl,
+        vertex_source.to_owned(),
+        fragment_source.to_owned(),
+        geometry_source.map(|s| s.to_owned()),
+        defines,
+      )?;
+      self.shaders.insert(key.clone(), shader);
+    }
+
+    Ok(&self.shaders[&key])
+  }
+
+  pub fn len(&self) -> usize {
+    self.shaders.len()
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    for (_, shader) in self.shaders {
+      shader.dispose(gl);
+    }
+  }
+}
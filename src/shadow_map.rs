@@ -0,0 +1,141 @@
+use crate::{
+  framebuffer::Framebuffer,
+  light::DirLight,
+  prelude::*,
+  texture::{Texture, TextureBuilder},
+};
+
+// Depth-only render target for a directional light. Render the scene from the light's
+// point of view into the shadow map, then sample the depth texture in the lighting pass.
+pub struct ShadowMap {
+  framebuffer: Framebuffer,
+  texture: Texture,
+  pub resolution: u32,
+
+  // Depth offset to keep surfaces from shadowing themselves ("shadow acne")
+  pub bias: f32,
+  // Width of the NxN grid of texels averaged by percentage-closer filtering. 1 gives hard
+  // shadow edges, larger values give softer edges.
+  pub pcf_kernel_size: u32,
+}
+
+impl ShadowMap {
+  pub unsafe fn new(gl: &Context, resolution: u32) -> Result<Self> {
+    let framebuffer = Framebuffer::empty(gl, resolution, resolution)?;
+
+    let texture = TextureBuilder::new(gl)
+      .with_format(glow::DEPTH_COMPONENT)
+      .with_tex_parameter(glow::TEXTURE_MIN_FILTER, glow::NEAREST)
+      .with_tex_parameter(glow::TEXTURE_MAG_FILTER, glow::NEAREST)
+      .with_tex_parameter(glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE)
+      .with_tex_parameter(glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE)
+      .render_texture(resolution, resolution)?;
+    framebuffer.attach_texture(gl, glow::DEPTH_ATTACHMENT, &texture);
+
+    // There's no color attachment, so tell OpenGL not to read or write color data
+    gl.draw_buffer(glow::NONE);
+    gl.read_buffer(glow::NONE);
+
+    framebuffer.check_completeness(gl)?;
+    framebuffer.unbind(gl);
+
+    Ok(ShadowMap {
+      framebuffer,
+      texture,
+      resolution,
+      bias: 0.005,
+      pcf_kernel_size: 3,
+    })
+  }
+
+  pub fn texture(&self) -> &Texture {
+    &self.texture
+  }
+
+  // Bind the shadow map and resize the viewport to match it. The caller is responsible
+  // for restoring the viewport afterwards.
+  pub unsafe fn bind(&self, gl: &Context) {
+    self.framebuffer.bind(gl);
+    gl.viewport(0, 0, self.resolution as i32, self.resolution as i32);
+  }
+
+  pub unsafe fn unbind(&self, gl: &Context) {
+    self.framebuffer.unbind(gl);
+  }
+
+  pub fn light_space_matrix(
+    &self,
+    light: &DirLight,
+    center: Vec3,
+    extent: f32,
+    near: f32,
+    far: f32,
+  ) -> Mat4 {
+    light.shadow_view_proj(center, extent, near, far)
+  }
+
+  // Uniforms for looking up this shadow map with shadow_factor in the lighting shader
+  pub fn params(&self, light_space: Mat4) -> ShadowParams {
+    ShadowParams {
+      map: self.texture.clone(),
+      light_space,
+      bias: self.bias,
+      pcf_kernel_size: self.pcf_kernel_size as i32,
+    }
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.framebuffer.dispose(gl);
+    self.texture.dispose(gl);
+  }
+}
+
+#[derive(BindUniform, ShaderTypeDef, Clone)]
+pub struct ShadowParams {
+  pub map: Texture,
+  pub light_space: Mat4,
+  pub bias: f32,
+  pub pcf_kernel_size: i32,
+}
+
+impl ShadowParams {
+  // GLSL for the shadow lookup, added with ShaderBuilder::with_def. shadow_factor returns
+  // how much of the fragment is in shadow, from 0 (lit) to 1 (fully shadowed). pcf_shadow
+  // takes the parameters separately so cascades can share it.
+  pub const SHADOW_FACTOR_DEF: &'static str = r#"
+float pcf_shadow(
+  sampler2D map, mat4 light_space, float bias, int pcf_kernel_size,
+  vec3 world_pos, vec3 normal, vec3 light_dir
+) {
+  vec4 light_pos = light_space * vec4(world_pos, 1.0);
+  vec3 proj = light_pos.xyz / light_pos.w * 0.5 + 0.5;
+
+  // Anything beyond the light's far plane is lit
+  if (proj.z > 1.0) {
+    return 0.0;
+  }
+
+  // Surfaces at a grazing angle to the light need a bigger bias
+  float slope_bias = max(bias * (1.0 - dot(normal, light_dir)), bias * 0.1);
+
+  vec2 texel_size = 1.0 / vec2(textureSize(map, 0));
+  int radius = pcf_kernel_size / 2;
+  float shadowed = 0.0;
+  for (int x = -radius; x <= radius; x++) {
+    for (int y = -radius; y <= radius; y++) {
+      float depth = texture(map, proj.xy + vec2(float(x), float(y)) * texel_size).r;
+      shadowed += proj.z - slope_bias > depth ? 1.0 : 0.0;
+    }
+  }
+
+  float width = float(2 * radius + 1);
+  return shadowed / (width * width);
+}
+
+float shadow_factor(ShadowParams shadow, vec3 world_pos, vec3 normal, vec3 light_dir) {
+  return pcf_shadow(
+    shadow.map, shadow.light_space, shadow.bias, shadow.pcf_kernel_size,
+    world_pos, normal, light_dir);
+}
+"#;
+}
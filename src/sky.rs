@@ -0,0 +1,217 @@
+use crate::{
+  camera::Camera,
+  geometry::Geometry,
+  ibl::{cube_render_state, cubemap_texture, render_to_cubemap},
+  mesh::Mesh,
+  prelude::*,
+  render_state::RenderState,
+  shader::{Shader, ShaderBuilder},
+  texture::{TCubemap, Texture},
+};
+
+// Sky color from the Preetham et al. analytic daylight model. Luminance and chromaticity
+// are each the zenith value scaled by the Perez distribution, which depends on the angle
+// from the zenith (theta) and from the sun (gamma). Turbidity is the haziness of the
+// atmosphere, from about 2 (clear) to 10 (hazy).
+const PREETHAM_DEF: &str = r#"
+const float PI = 3.14159265359;
+// Angular radius of the sun's disc, in radians
+const float SUN_RADIUS = 0.0047;
+// Converts the model's kcd/m^2 into a range that tonemaps well
+const float SKY_LUMINANCE_SCALE = 0.05;
+
+vec3 perez(float cos_theta, float gamma, float cos_gamma, vec3 A, vec3 B, vec3 C, vec3 D, vec3 E) {
+  return (1.0 + A * exp(B / cos_theta)) * (1.0 + C * exp(D * gamma) + E * cos_gamma * cos_gamma);
+}
+
+vec3 preetham_sky(vec3 direction, vec3 sun_direction, float T) {
+  // Below the horizon, repeat the horizon color
+  direction.y = max(direction.y, 0.001);
+  direction = normalize(direction);
+  vec3 sun = normalize(sun_direction);
+
+  float theta_s = acos(clamp(sun.y, 0.0, 1.0));
+  float cos_gamma = clamp(dot(direction, sun), -1.0, 1.0);
+  float gamma = acos(cos_gamma);
+
+  // Perez coefficients for (Y, x, y)
+  vec3 A = vec3(0.1787 * T - 1.4630, -0.0193 * T - 0.2592, -0.0167 * T - 0.2608);
+  vec3 B = vec3(-0.3554 * T + 0.4275, -0.0665 * T + 0.0008, -0.0950 * T + 0.0092);
+  vec3 C = vec3(-0.0227 * T + 5.3251, -0.0004 * T + 0.2125, -0.0079 * T + 0.2102);
+  vec3 D = vec3(0.1206 * T - 2.5771, -0.0641 * T - 0.8989, -0.0441 * T - 1.6537);
+  vec3 E = vec3(-0.0670 * T + 0.3703, -0.0033 * T + 0.0452, -0.0109 * T + 0.0529);
+
+  // Zenith luminance and chromaticity
+  float chi = (4.0 / 9.0 - T / 120.0) * (PI - 2.0 * theta_s);
+  float Yz = (4.0453 * T - 4.9710) * tan(chi) - 0.2155 * T + 2.4192;
+  vec3 t = vec3(theta_s * theta_s * theta_s, theta_s * theta_s, theta_s);
+  float xz = T * T * dot(vec4(0.00166, -0.00375, 0.00209, 0.0), vec4(t, 1.0))
+    + T * dot(vec4(-0.02903, 0.06377, -0.03202, 0.00394), vec4(t, 1.0))
+    + dot(vec4(0.11693, -0.21196, 0.06052, 0.25886), vec4(t, 1.0));
+  float yz = T * T * dot(vec4(0.00275, -0.00610, 0.00317, 0.0), vec4(t, 1.0))
+    + T * dot(vec4(-0.04214, 0.08970, -0.04153, 0.00516), vec4(t, 1.0))
+    + dot(vec4(0.15346, -0.26756, 0.06670, 0.26688), vec4(t, 1.0));
+
+  vec3 Yxy = vec3(Yz, xz, yz)
+    * perez(direction.y, gamma, cos_gamma, A, B, C, D, E)
+    / perez(1.0, theta_s, cos(theta_s), A, B, C, D, E);
+  Yxy.x *= SKY_LUMINANCE_SCALE;
+
+  // xyY to XYZ to linear sRGB
+  vec3 XYZ = vec3(Yxy.y / Yxy.z * Yxy.x, Yxy.x, (1.0 - Yxy.y - Yxy.z) / Yxy.z * Yxy.x);
+  vec3 color = mat3(
+    3.2406, -0.9689, 0.0557,
+    -1.5372, 1.8758, -0.2040,
+    -0.4986, 0.0415, 1.0570
+  ) * XYZ;
+
+  // Sun disc, only above the horizon
+  if (gamma < SUN_RADIUS && sun.y > 0.0) {
+    color += vec3(Yxy.x * 50.0);
+  }
+  return max(color, vec3(0.0));
+}
+"#;
+
+// Quad on the far plane, so the sky only shows where nothing else was drawn
+const SKY_VERTEX: &str = r#"
+layout (location = 0) in vec3 aPos;
+
+out vec2 ndc;
+
+void main() {
+  ndc = vec2(aPos.x, aPos.z);
+  gl_Position = vec4(ndc, 1.0, 1.0);
+}
+"#;
+
+const SKY_FRAGMENT: &str = r#"
+out vec4 FragColor;
+
+in vec2 ndc;
+
+// Inverse of the projection times the rotation part of the view
+uniform mat4 inverse_view_rotation_projection;
+uniform vec3 sun_direction;
+uniform float turbidity;
+
+void main() {
+  vec4 far = inverse_view_rotation_projection * vec4(ndc, 1.0, 1.0);
+  FragColor = vec4(preetham_sky(far.xyz / far.w, sun_direction, turbidity), 1.0);
+}
+"#;
+
+const CUBEMAP_FRAGMENT: &str = r#"
+out vec4 FragColor;
+
+in vec3 local_pos;
+
+uniform vec3 sun_direction;
+uniform float turbidity;
+
+void main() {
+  FragColor = vec4(preetham_sky(local_pos, sun_direction, turbidity), 1.0);
+}
+"#;
+
+// Procedural daylight sky. The output is linear HDR, so draw it into an HDR framebuffer
+// before tonemapping.
+pub struct ProceduralSky {
+  shader: Shader,
+  cubemap_shader: Shader,
+  quad: Mesh,
+  cube: Mesh,
+}
+
+impl ProceduralSky {
+  pub unsafe fn new(gl: &Context) -> Result<Self> {
+    let shader = ShaderBuilder::new()
+      .name("sky")
+      .vertex_source(SKY_VERTEX.to_owned())
+      .fragment_source(format!("{}{}", PREETHAM_DEF, SKY_FRAGMENT))
+      .build(gl)?;
+    let cubemap_shader = ShaderBuilder::new()
+      .name("sky cubemap")
+      .vertex_source(include_str!("../assets/shaders/cubemap.vert").to_owned())
+      .fragment_source(format!("{}{}", PREETHAM_DEF, CUBEMAP_FRAGMENT))
+      .build(gl)?;
+
+    let quad = Geometry::Plane {
+      length: 2.,
+      width: 2.,
+      normal: glm::zero(),
+    }
+    .to_mesh(gl, None)?;
+    let cube = Geometry::Cube {
+      length: 2.,
+      width: 2.,
+      height: 2.,
+    }
+    .to_mesh(gl, None)?;
+
+    Ok(ProceduralSky {
+      shader,
+      cubemap_shader,
+      quad,
+      cube,
+    })
+  }
+
+  // Draw the sky into the bound framebuffer. Call this after the opaque geometry: the sky
+  // is drawn at the far plane without writing depth, so it only fills the background.
+  pub unsafe fn render(&self, gl: &Context, camera: &Camera, sun_direction: Vec3, turbidity: f32) {
+    let view_rotation = glm::mat3_to_mat4(&glm::mat4_to_mat3(&camera.view_matrix()));
+    let inverse = (camera.projection() * view_rotation)
+      .try_inverse()
+      .unwrap_or_else(Mat4::identity);
+
+    let previous_depth_func = gl.get_parameter_i32(glow::DEPTH_FUNC) as u32;
+    gl.depth_func(glow::LEQUAL);
+    RenderState {
+      depth_write: false,
+      blend: None,
+      cull_face: None,
+      ..Default::default()
+    }
+    .apply(gl);
+
+    let mut shader = self.shader.activate(gl);
+    shader.bind_uniform(gl, "inverse_view_rotation_projection", &inverse);
+    shader.bind_uniform(gl, "sun_direction", &sun_direction);
+    shader.bind_uniform(gl, "turbidity", &turbidity);
+    self.quad.draw(gl, &mut shader);
+
+    RenderState::default().apply(gl);
+    gl.depth_func(previous_depth_func);
+  }
+
+  // Render the sky into the faces of a mipmapped RGBA16F cubemap, e.g. as the environment
+  // for image-based lighting or a regular skybox. The caller is responsible for restoring
+  // the viewport afterwards.
+  pub unsafe fn bake_to_cubemap(
+    &self,
+    gl: &Context,
+    resolution: u32,
+    sun_direction: Vec3,
+    turbidity: f32,
+  ) -> Result<Texture<TCubemap>> {
+    cube_render_state().apply(gl);
+
+    let cubemap = cubemap_texture(gl, resolution, true)?;
+    let mut shader = self.cubemap_shader.activate(gl);
+    shader.bind_uniform(gl, "sun_direction", &sun_direction);
+    shader.bind_uniform(gl, "turbidity", &turbidity);
+    render_to_cubemap(gl, &self.cube, &mut shader, &cubemap, resolution, 0)?;
+    cubemap.generate_mipmaps(gl);
+
+    RenderState::default().apply(gl);
+    Ok(cubemap)
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.shader.dispose(gl);
+    self.cubemap_shader.dispose(gl);
+    self.quad.dispose(gl);
+    self.cube.dispose(gl);
+  }
+}
@@ -0,0 +1,7 @@
+// Uniform structs that build.rs generates from the SPIR-V shaders in assets/shaders/spirv,
+// see build_support::generate_uniform_structs
+#![allow(dead_code, unused_imports)]
+
+use crate::prelude::*;
+
+include!(concat!(env!("OUT_DIR"), "/spirv_uniforms.rs"));
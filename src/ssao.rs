@@ -0,0 +1,113 @@
+use crate::{
+  camera::Camera,
+  framebuffer::Framebuffer,
+  gbuffer::GBuffer,
+  math::Rng,
+  post_process::PostProcess,
+  prelude::*,
+  texture::{Texture, TextureBuilder},
+};
+
+const NOISE_SIZE: u32 = 4;
+// Must match the size of the samples array in ssao.frag
+const MAX_KERNEL_SIZE: u32 = 64;
+
+// Screen-space ambient occlusion. For each pixel, samples points in a hemisphere around its
+// normal and darkens it by how many of them are buried in nearby geometry. The raw result
+// is noisy (each pixel's kernel is randomly rotated), so a blur pass smooths it out.
+pub struct Ssao {
+  kernel: Vec<Vec3>,
+  noise: Texture,
+  ao_framebuffer: Framebuffer,
+  blur_framebuffer: Framebuffer,
+  ao_pass: PostProcess,
+  blur_pass: PostProcess,
+  width: u32,
+  height: u32,
+
+  // World-space radius of the sample hemisphere
+  pub radius: f32,
+  // Depth offset to keep flat surfaces from occluding themselves
+  pub bias: f32,
+}
+
+impl Ssao {
+  pub async unsafe fn new(gl: &Context, width: u32, height: u32, kernel_size: u32) -> Result<Self> {
+    let kernel_size = kernel_size.min(MAX_KERNEL_SIZE);
+    let mut rng = Rng::default();
+
+    // Samples in a unit hemisphere around +Z, clustered toward the origin so that
+    // occluders close to the pixel count for more
+    let kernel = (0..kernel_size)
+      .map(|i| {
+        let sample = glm::normalize(&glm::vec3(
+          rng.range(-1., 1.),
+          rng.range(-1., 1.),
+          rng.next_f32(),
+        )) * rng.next_f32();
+        let scale = i as f32 / kernel_size as f32;
+        sample * glm::lerp_scalar(0.1, 1., scale * scale)
+      })
+      .collect::<Vec<_>>();
+
+    // Random rotations around Z, tiled across the screen
+    let noise_pixels = (0..NOISE_SIZE * NOISE_SIZE)
+      .flat_map(|_| vec![rng.range(-1., 1.), rng.range(-1., 1.), 0.])
+      .collect::<Vec<_>>();
+    let noise = TextureBuilder::new(gl)
+      .with_format(glow::RGB)
+      .with_internal_format(glow::RGB16F)
+      .with_tex_parameter(glow::TEXTURE_MIN_FILTER, glow::NEAREST)
+      .with_tex_parameter(glow::TEXTURE_MAG_FILTER, glow::NEAREST)
+      .render_texture(NOISE_SIZE, NOISE_SIZE)?;
+    noise.sub_image_f32(gl, 0, 0, NOISE_SIZE, NOISE_SIZE, &noise_pixels);
+
+    Ok(Ssao {
+      kernel,
+      noise,
+      ao_framebuffer: Framebuffer::with_color(gl, width, height)?,
+      blur_framebuffer: Framebuffer::with_color(gl, width, height)?,
+      ao_pass: PostProcess::new(gl, "assets/shaders/ssao.frag").await?,
+      blur_pass: PostProcess::new(gl, "assets/shaders/ssao_blur.frag").await?,
+      width,
+      height,
+      radius: 0.5,
+      bias: 0.025,
+    })
+  }
+
+  pub unsafe fn render(&self, gl: &Context, gbuffer: &GBuffer, camera: &Camera) {
+    self.ao_framebuffer.bind(gl);
+    self.ao_pass.draw_with(gl, |gl, shader| {
+      shader.bind_uniform(gl, "gPosition", &gbuffer.position);
+      shader.bind_uniform(gl, "gNormal", &gbuffer.normal);
+      shader.bind_uniform(gl, "texNoise", &self.noise);
+      shader.bind_uniform(gl, "samples", &self.kernel.as_slice());
+      shader.bind_uniform(gl, "kernel_size", &(self.kernel.len() as i32));
+      shader.bind_uniform(gl, "radius", &self.radius);
+      shader.bind_uniform(gl, "bias", &self.bias);
+      shader.bind_uniform(gl, "ssao_view", &camera.view_matrix());
+      shader.bind_uniform(gl, "ssao_projection", &camera.projection());
+      let noise_scale = glm::vec2(self.width as f32, self.height as f32) / NOISE_SIZE as f32;
+      shader.bind_uniform(gl, "noise_scale", &noise_scale);
+    });
+
+    self.blur_framebuffer.bind(gl);
+    let ao = self.ao_framebuffer.color.as_ref().unwrap();
+    self.blur_pass.draw(gl, ao, |_, _| {});
+    self.blur_framebuffer.unbind(gl);
+  }
+
+  // Ambient occlusion factor in the red channel: 1 is fully lit, 0 is fully occluded
+  pub fn texture(&self) -> &Texture {
+    self.blur_framebuffer.color.as_ref().unwrap()
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.noise.dispose(gl);
+    self.ao_framebuffer.dispose(gl);
+    self.blur_framebuffer.dispose(gl);
+    self.ao_pass.dispose(gl);
+    self.blur_pass.dispose(gl);
+  }
+}
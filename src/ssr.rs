@@ -0,0 +1,88 @@
+use crate::{
+  camera::Camera,
+  framebuffer::Framebuffer,
+  gbuffer::GBuffer,
+  post_process::PostProcess,
+  prelude::*,
+  render_state::{self, ClearFlags},
+  texture::{TCubemap, Texture},
+};
+
+// Screen-space reflections: marches each pixel's reflected view ray through the G-buffer
+// looking for a surface to reflect. Rays that leave the screen or hit nothing reflect the
+// environment cubemap instead. The lighting pass can blend texture() in by specularity.
+pub struct Ssr {
+  framebuffer: Framebuffer,
+  pass: PostProcess,
+  composite_pass: PostProcess,
+
+  // Number of steps to march along the ray before giving up
+  pub max_steps: i32,
+  // View-space distance of each step
+  pub step_size: f32,
+  // How far behind a surface the ray can be and still count as hitting it
+  pub thickness: f32,
+}
+
+impl Ssr {
+  pub async unsafe fn new(gl: &Context, width: u32, height: u32) -> Result<Self> {
+    Ok(Ssr {
+      framebuffer: Framebuffer::with_color(gl, width, height)?,
+      // Alpha is the screen vs. environment mix, not coverage, so it's written as-is
+      pass: PostProcess::new(gl, "assets/shaders/ssr.frag")
+        .await?
+        .with_blend(None),
+      composite_pass: PostProcess::new(gl, "assets/shaders/ssr_composite.frag").await?,
+      max_steps: 64,
+      step_size: 0.1,
+      thickness: 0.2,
+    })
+  }
+
+  // Compute reflections of the lit scene_color, using positions and normals from gbuffer
+  pub unsafe fn render(
+    &self,
+    gl: &Context,
+    gbuffer: &GBuffer,
+    scene_color: &Texture,
+    environment: &Texture<TCubemap>,
+    camera: &Camera,
+  ) {
+    self.framebuffer.bind(gl);
+    render_state::clear(gl, glm::zero(), ClearFlags::COLOR);
+    self.pass.draw(gl, scene_color, |gl, shader| {
+      let view = camera.view_matrix();
+      shader.bind_uniform(gl, "gPosition", &gbuffer.position);
+      shader.bind_uniform(gl, "gNormal", &gbuffer.normal);
+      shader.bind_uniform(gl, "environment", environment);
+      shader.bind_uniform(gl, "ssr_view", &view);
+      shader.bind_uniform(gl, "inverse_view", &glm::inverse(&view));
+      shader.bind_uniform(gl, "ssr_projection", &camera.projection());
+      shader.bind_uniform(gl, "max_steps", &self.max_steps);
+      shader.bind_uniform(gl, "step_size", &self.step_size);
+      shader.bind_uniform(gl, "thickness", &self.thickness);
+    });
+    self.framebuffer.unbind(gl);
+  }
+
+  // Reflected color in RGB, and in alpha how much of it came from the screen vs. the
+  // environment
+  pub fn texture(&self) -> &Texture {
+    self.framebuffer.color.as_ref().unwrap()
+  }
+
+  // Draw scene_color with the reflections from the last render added on top, scaled by
+  // each pixel's specular intensity, into the currently bound framebuffer
+  pub unsafe fn composite(&self, gl: &Context, gbuffer: &GBuffer, scene_color: &Texture) {
+    self.composite_pass.draw(gl, scene_color, |gl, shader| {
+      shader.bind_uniform(gl, "reflections", self.texture());
+      shader.bind_uniform(gl, "gAlbedoSpec", &gbuffer.albedo_spec);
+    });
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.framebuffer.dispose(gl);
+    self.pass.dispose(gl);
+    self.composite_pass.dispose(gl);
+  }
+}
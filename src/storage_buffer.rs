@@ -0,0 +1,78 @@
+use crate::prelude::*;
+use std::{marker::PhantomData, mem::size_of, ptr, slice};
+
+// Shader storage buffer: like a UniformBlock, but much larger and writable from shaders.
+// Elements are copied byte-for-byte, so T's layout has to match the GLSL std430 layout of
+// the block's array. SSBOs need GL 4.3, so this is native-only.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct StorageBuffer<T: Copy> {
+  buffer: GlBuffer,
+  capacity: usize,
+  _marker: PhantomData<T>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Copy> StorageBuffer<T> {
+  pub unsafe fn new(gl: &Context, capacity_bytes: usize) -> Result<Self> {
+    let buffer = gl.create_buffer().map_err(Error::msg)?;
+    gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, Some(buffer));
+    gl.buffer_data_size(
+      glow::SHADER_STORAGE_BUFFER,
+      capacity_bytes as i32,
+      glow::DYNAMIC_DRAW,
+    );
+    gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, None);
+
+    Ok(StorageBuffer {
+      buffer,
+      capacity: capacity_bytes,
+      _marker: PhantomData,
+    })
+  }
+
+  // Number of T that fit in the buffer
+  pub fn len(&self) -> usize {
+    self.capacity / size_of::<T>()
+  }
+
+  // Copy data into the start of the buffer
+  pub unsafe fn write(&self, gl: &Context, data: &[T]) -> Result<()> {
+    let bytes = slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data));
+    if bytes.len() > self.capacity {
+      bail!(
+        "Wrote {} bytes into a storage buffer of {} bytes",
+        bytes.len(),
+        self.capacity
+      );
+    }
+
+    gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, Some(self.buffer));
+    gl.buffer_sub_data_u8_slice(glow::SHADER_STORAGE_BUFFER, 0, bytes);
+    gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, None);
+    Ok(())
+  }
+
+  // Copy the whole buffer back to the CPU. This waits for any shaders writing to it.
+  pub unsafe fn read(&self, gl: &Context) -> Vec<T> {
+    let mut bytes = vec![0u8; self.len() * size_of::<T>()];
+
+    gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, Some(self.buffer));
+    gl.get_buffer_sub_data(glow::SHADER_STORAGE_BUFFER, 0, &mut bytes);
+    gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, None);
+
+    // The byte buffer isn't aligned for T, so read each element out unaligned
+    bytes
+      .chunks_exact(size_of::<T>())
+      .map(|chunk| ptr::read_unaligned(chunk.as_ptr() as *const T))
+      .collect()
+  }
+
+  // Put the buffer at a binding slot, to be matched with Shader::bind_storage_block
+  pub unsafe fn bind_base(&self, gl: &Context, index: u32) {
+    gl.bind_buffer_base(glow::SHADER_STORAGE_BUFFER, index, Some(self.buffer));
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    gl.delete_buffer(self.buffer);
+  }
+}
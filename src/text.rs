@@ -6,9 +6,11 @@ use glyph_brush::{
 use crate::{
   io,
   prelude::*,
+  render_state::RenderState,
   shader::Shader,
   texture::{Texture, TextureBuilder},
 };
+use serde::Deserialize;
 use std::{collections::HashMap, mem::size_of, path::Path};
 
 #[repr(C)]
@@ -44,7 +46,7 @@ impl Font {
       .to_string();
 
     // Load font data into glyph_brush
-    let bytes = io::load_file(path).await?;
+    let bytes = io::load_bytes(path).await?;
     let font = FontArc::try_from_vec(bytes)?;
     let glyph_brush = GlyphBrushBuilder::using_font(font).build();
 
@@ -174,6 +176,13 @@ impl Font {
 
     Ok(())
   }
+
+  // Free the glyph buffers and the font's atlas texture
+  pub unsafe fn dispose(self, gl: &Context) {
+    gl.delete_vertex_array(self.vertex_array);
+    gl.delete_buffer(self.vertex_buffer);
+    self.texture.dispose(gl);
+  }
 }
 
 pub struct Text {
@@ -203,3 +212,275 @@ impl Text {
     font.queue(&self.section);
   }
 }
+
+const SDF_VERTEX_SOURCE: &str = r#"
+layout (location = 0) in vec2 left_bottom;
+layout (location = 1) in vec2 right_top;
+layout (location = 2) in vec2 tex_left_bottom;
+layout (location = 3) in vec2 tex_right_top;
+layout (location = 4) in vec4 color;
+
+uniform mat3 model;
+
+out vec2 f_tex_coords;
+out vec4 f_color;
+
+void main() {
+  // Triangle strip corners, in the order (0, 0), (1, 0), (0, 1), (1, 1)
+  vec2 corner = vec2(gl_VertexID & 1, gl_VertexID >> 1);
+  vec2 position = mix(left_bottom, right_top, corner);
+  f_tex_coords = mix(tex_left_bottom, tex_right_top, corner);
+  f_color = color;
+  gl_Position = vec4((model * vec3(position, 1.0)).xy, 0.0, 1.0);
+}
+"#;
+
+const SDF_FRAGMENT_SOURCE: &str = r#"
+out vec4 FragColor;
+
+in vec2 f_tex_coords;
+in vec4 f_color;
+
+uniform sampler2D atlas;
+
+float median(float r, float g, float b) {
+  return max(min(r, g), min(max(r, g), b));
+}
+
+void main() {
+  // Each channel holds a distance to an edge, where 0.5 is on the edge. The median of the
+  // three channels keeps corners sharp, and fwidth makes the edge about one pixel wide at
+  // any scale.
+  vec3 msd = texture(atlas, f_tex_coords).rgb;
+  float dist = median(msd.r, msd.g, msd.b);
+  float width = fwidth(dist);
+  float alpha = smoothstep(0.5 - width, 0.5 + width, dist);
+  if (alpha <= 0.0) {
+    discard;
+  }
+  FragColor = vec4(f_color.rgb, f_color.a * alpha);
+}
+"#;
+
+// Layout of the JSON written by msdf-atlas-gen with -json
+#[derive(Deserialize)]
+struct SdfMetricsFile {
+  atlas: SdfAtlasInfo,
+  metrics: SdfFontMetrics,
+  glyphs: Vec<SdfGlyph>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SdfAtlasInfo {
+  width: f32,
+  height: f32,
+  #[serde(default)]
+  y_origin: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SdfFontMetrics {
+  line_height: f32,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct SdfGlyph {
+  unicode: u32,
+  advance: f32,
+  plane_bounds: Option<SdfBounds>,
+  atlas_bounds: Option<SdfBounds>,
+}
+
+#[derive(Deserialize, Clone)]
+struct SdfBounds {
+  left: f32,
+  bottom: f32,
+  right: f32,
+  top: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Debug)]
+struct SdfGlyphVertex {
+  left_bottom: Vec2,
+  right_top: Vec2,
+  tex_left_bottom: Vec2,
+  tex_right_top: Vec2,
+  color: Vec4,
+}
+
+// A font pre-rendered as a multi-channel signed distance field, e.g. by msdf-atlas-gen. Unlike
+// Font, glyphs stay sharp at any size since edges are reconstructed per pixel from the
+// distance field.
+pub struct SdfFont {
+  glyphs: HashMap<char, SdfGlyph>,
+  line_height: f32,
+  texture: Texture,
+  shader: Shader,
+  vertex_array: GlVertexArray,
+  vertex_buffer: GlBuffer,
+}
+
+impl SdfFont {
+  pub async unsafe fn load(
+    gl: &Context,
+    atlas_image_path: impl AsRef<Path>,
+    metrics_json_path: impl AsRef<Path>,
+  ) -> Result<Self> {
+    let (image, metrics) = try_join!(
+      io::load_image(atlas_image_path),
+      io::load_string(metrics_json_path)
+    )?;
+    let SdfMetricsFile {
+      atlas,
+      metrics,
+      glyphs,
+    } = serde_json::from_str(&metrics)?;
+
+    // Atlas bounds are in pixels. The texture is flipped so v starts at the bottom, so
+    // atlases measured from the top need their y coordinates flipped too.
+    let flip_y = atlas.y_origin.as_deref() == Some("top");
+    let glyphs = glyphs
+      .into_iter()
+      .filter_map(|mut glyph| {
+        if let Some(bounds) = glyph.atlas_bounds.as_mut() {
+          if flip_y {
+            let (top, bottom) = (atlas.height - bounds.bottom, atlas.height - bounds.top);
+            bounds.top = top;
+            bounds.bottom = bottom;
+          }
+          bounds.left /= atlas.width;
+          bounds.right /= atlas.width;
+          bounds.bottom /= atlas.height;
+          bounds.top /= atlas.height;
+        }
+        Some((std::char::from_u32(glyph.unicode)?, glyph))
+      })
+      .collect();
+
+    // Mipmaps would blend distances from neighboring glyphs
+    let texture = TextureBuilder::new(gl)
+      .with_format(glow::RGB)
+      .with_tex_parameter(glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE)
+      .with_tex_parameter(glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE)
+      .with_tex_parameter(glow::TEXTURE_MIN_FILTER, glow::LINEAR)
+      .build(image)?;
+    let shader = Shader::from_static(gl, SDF_VERTEX_SOURCE, SDF_FRAGMENT_SOURCE)?;
+
+    let vertex_array = gl.create_vertex_array().map_err(Error::msg)?;
+    gl.bind_vertex_array(Some(vertex_array));
+
+    let vertex_buffer = gl.create_buffer().map_err(Error::msg)?;
+    gl.bind_buffer(glow::ARRAY_BUFFER, Some(vertex_buffer));
+
+    // One instance per glyph, like Font
+    let size_f32 = size_of::<f32>() as i32;
+    let sizes = [2, 2, 2, 2, 4];
+    let stride = sizes.iter().sum::<i32>() * size_f32;
+
+    let mut offset = 0;
+    for (i, size) in sizes.iter().enumerate() {
+      gl.enable_vertex_attrib_array(i as u32);
+      gl.vertex_attrib_pointer_f32(
+        i as u32,
+        *size,
+        glow::FLOAT,
+        false,
+        stride,
+        offset * size_f32,
+      );
+      gl.vertex_attrib_divisor(i as u32, 1);
+      offset += size;
+    }
+
+    gl.bind_vertex_array(None);
+
+    Ok(SdfFont {
+      glyphs,
+      line_height: metrics.line_height,
+      texture,
+      shader,
+      vertex_array,
+      vertex_buffer,
+    })
+  }
+
+  // Draw text with its first baseline starting at position, in pixels from the bottom-left
+  // of a screen_size screen. scale is the font size in pixels per em. Newlines start a new
+  // line below.
+  pub unsafe fn draw(
+    &self,
+    gl: &Context,
+    text: &str,
+    position: Vec2,
+    scale: f32,
+    color: Vec4,
+    screen_size: Vec2,
+  ) {
+    let mut vertices = vec![];
+    let mut pen = position;
+    for c in text.chars() {
+      if c == '\n' {
+        pen = glm::vec2(position.x, pen.y - self.line_height * scale);
+        continue;
+      }
+
+      let glyph = match self.glyphs.get(&c) {
+        Some(glyph) => glyph,
+        None => continue,
+      };
+
+      // Whitespace has an advance but nothing to draw
+      if let (Some(plane), Some(atlas)) = (&glyph.plane_bounds, &glyph.atlas_bounds) {
+        vertices.push(SdfGlyphVertex {
+          left_bottom: pen + glm::vec2(plane.left, plane.bottom) * scale,
+          right_top: pen + glm::vec2(plane.right, plane.top) * scale,
+          tex_left_bottom: glm::vec2(atlas.left, atlas.bottom),
+          tex_right_top: glm::vec2(atlas.right, atlas.top),
+          color,
+        });
+      }
+      pen.x += glyph.advance * scale;
+    }
+
+    if vertices.is_empty() {
+      return;
+    }
+
+    gl.bind_vertex_array(Some(self.vertex_array));
+    gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vertex_buffer));
+    let (_, vertices_bytes, _) = vertices.align_to::<u8>();
+    gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, vertices_bytes, glow::STREAM_DRAW);
+
+    // Translate text from screen coordinates into normalized device coordinates
+    let model = glm::scale2d(
+      &glm::translation2d(&glm::vec2(-1.0, -1.0)),
+      &glm::vec2(2. / screen_size.x, 2. / screen_size.y),
+    );
+
+    let mut shader = self.shader.activate(gl);
+    shader.bind_uniform(gl, "atlas", &self.texture);
+    shader.bind_uniform(gl, "model", &model);
+
+    // Text goes over everything, blended at its anti-aliased edges
+    RenderState {
+      depth_test: false,
+      cull_face: None,
+      ..Default::default()
+    }
+    .apply(gl);
+    gl.draw_arrays_instanced(glow::TRIANGLE_STRIP, 0, 4, vertices.len() as i32);
+    gl.bind_vertex_array(None);
+    RenderState::default().apply(gl);
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    gl.delete_vertex_array(self.vertex_array);
+    gl.delete_buffer(self.vertex_buffer);
+    self.texture.dispose(gl);
+    self.shader.dispose(gl);
+  }
+}
@@ -1,7 +1,7 @@
 use std::{collections::HashMap, marker::PhantomData, path::Path};
 
 use crate::{
-  io,
+  io::{self, HdrImage},
   prelude::*,
   shader::{ActiveShader, BindUniform},
 };
@@ -28,11 +28,27 @@ impl TextureTarget for TCubemap {
   const TARGET: u32 = glow::TEXTURE_CUBE_MAP;
 }
 
+// Unsized format and texel type that go with a sized internal format. GLES only accepts
+// specific combinations, e.g. RGB16F must be paired with RGB and a float type.
+pub(crate) fn pixel_format(internal_format: u32) -> (u32, u32) {
+  match internal_format {
+    glow::R8 => (glow::RED, glow::UNSIGNED_BYTE),
+    glow::RG8 => (glow::RG, glow::UNSIGNED_BYTE),
+    glow::RGB | glow::RGB8 | glow::SRGB8 => (glow::RGB, glow::UNSIGNED_BYTE),
+    glow::R16F | glow::R32F => (glow::RED, glow::FLOAT),
+    glow::RG16F | glow::RG32F => (glow::RG, glow::FLOAT),
+    glow::RGB16F | glow::RGB32F | glow::R11F_G11F_B10F => (glow::RGB, glow::FLOAT),
+    glow::RGBA16F | glow::RGBA32F => (glow::RGBA, glow::FLOAT),
+    _ => (glow::RGBA, glow::UNSIGNED_BYTE),
+  }
+}
+
 pub struct TextureBuilder<'a, Target> {
   gl: &'a Context,
   tex_parameters: HashMap<u32, u32>,
   flip: bool,
   format: u32,
+  internal_format: Option<u32>,
   alignment: u32,
   _marker: PhantomData<Target>,
 }
@@ -48,6 +64,7 @@ impl<'a> TextureBuilder<'a, T2d> {
       },
       flip: true,
       format: glow::RGBA,
+      internal_format: None,
       alignment: 4,
       _marker: PhantomData,
       gl,
@@ -55,6 +72,7 @@ impl<'a> TextureBuilder<'a, T2d> {
   }
 
   pub unsafe fn build(self, image: DynamicImage) -> Result<Texture<T2d>> {
+    self.check_supported()?;
     let target = Self::target();
     let internal_format = self.internal_format();
     let (image, (width, height)) = self.convert_image(image);
@@ -80,13 +98,14 @@ impl<'a> TextureBuilder<'a, T2d> {
     gl.generate_mipmap(target);
 
     // Set wrapping parameters
-    Self::apply_texture_parameters(gl, self.tex_parameters);
+    Self::apply_texture_parameters(gl, &self.tex_parameters);
 
     gl.bind_texture(target, None);
 
     Ok(Texture {
       texture,
       format: self.format,
+      data_type: self.data_type(),
       _marker: PhantomData,
     })
   }
@@ -95,10 +114,79 @@ impl<'a> TextureBuilder<'a, T2d> {
     let image = io::load_image(path).await?;
     self.build(image)
   }
+
+  // Load an equirectangular .hdr environment as an RGB float texture, by default RGB16F.
+  // Use with_internal_format(glow::RGB32F) to keep full precision.
+  pub async unsafe fn load_hdr(self, path: impl AsRef<Path>) -> Result<Texture<T2d>> {
+    let image = io::load_hdr(path).await?;
+    self.build_hdr(&image)
+  }
+
+  // With the default flip, v = 1 is the top row of the image, i.e. straight up. Longitude
+  // wraps around horizontally but latitude doesn't, so only S repeats.
+  pub unsafe fn build_hdr(self, image: &HdrImage) -> Result<Texture<T2d>> {
+    let pixels = if self.flip {
+      image
+        .pixels
+        .chunks(image.width as usize * 3)
+        .rev()
+        .flatten()
+        .copied()
+        .collect()
+    } else {
+      image.pixels.clone()
+    };
+    let internal_format = self.internal_format.unwrap_or(glow::RGB16F);
+
+    self
+      .with_format(glow::RGB)
+      .with_internal_format(internal_format)
+      .with_tex_parameter(glow::TEXTURE_MIN_FILTER, glow::LINEAR)
+      .with_tex_parameter(glow::TEXTURE_WRAP_S, glow::REPEAT)
+      .with_tex_parameter(glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE)
+      .build_f32(image.width, image.height, &pixels)
+  }
+
+  // Build a texture from floating point pixels, e.g. a decoded HDR image. There should be
+  // one value per channel of the format, with rows starting from the bottom. Mipmaps aren't
+  // generated (not every float format supports it), so set a non-mipmap MIN_FILTER.
+  pub unsafe fn build_f32(self, width: u32, height: u32, pixels: &[f32]) -> Result<Texture<T2d>> {
+    self.check_supported()?;
+    let target = Self::target();
+    let gl = self.gl;
+    let (_, bytes, _) = pixels.align_to::<u8>();
+
+    gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, self.alignment as i32);
+    let texture = gl.create_texture().map_err(Error::msg)?;
+    gl.bind_texture(target, Some(texture));
+    gl.tex_image_2d(
+      target,
+      0,
+      self.internal_format() as i32,
+      width as i32,
+      height as i32,
+      0,
+      self.format,
+      glow::FLOAT,
+      Some(bytes),
+    );
+
+    Self::apply_texture_parameters(gl, &self.tex_parameters);
+
+    gl.bind_texture(target, None);
+
+    Ok(Texture {
+      texture,
+      format: self.format,
+      data_type: glow::FLOAT,
+      _marker: PhantomData,
+    })
+  }
 }
 
 impl<'a> TextureBuilder<'a, TCubemap> {
   pub unsafe fn build(self, images: Vec<DynamicImage>) -> Result<Texture<TCubemap>> {
+    self.check_supported()?;
     let target = Self::target();
     let internal_format = self.internal_format();
 
@@ -127,13 +215,48 @@ impl<'a> TextureBuilder<'a, TCubemap> {
       );
     }
 
-    Self::apply_texture_parameters(gl, self.tex_parameters);
+    Self::apply_texture_parameters(gl, &self.tex_parameters);
+
+    gl.bind_texture(target, None);
+
+    Ok(Texture {
+      texture,
+      format: self.format,
+      data_type: self.data_type(),
+      _marker: PhantomData,
+    })
+  }
+
+  // Empty cubemap with size x size faces, to be rendered into one face at a time
+  pub unsafe fn render_cubemap(self, size: u32) -> Result<Texture<TCubemap>> {
+    self.check_supported()?;
+    let target = Self::target();
+    let gl = self.gl;
+    let texture = gl.create_texture().map_err(Error::msg)?;
+    gl.bind_texture(target, Some(texture));
+
+    for i in 0..6 {
+      gl.tex_image_2d(
+        glow::TEXTURE_CUBE_MAP_POSITIVE_X + i,
+        0,
+        self.internal_format() as i32,
+        size as i32,
+        size as i32,
+        0,
+        self.format,
+        self.data_type(),
+        None,
+      );
+    }
+
+    Self::apply_texture_parameters(gl, &self.tex_parameters);
 
     gl.bind_texture(target, None);
 
     Ok(Texture {
       texture,
       format: self.format,
+      data_type: self.data_type(),
       _marker: PhantomData,
     })
   }
@@ -161,6 +284,18 @@ impl<'a, Target: TextureTarget> TextureBuilder<'a, Target> {
     self
   }
 
+  // Override the sized internal format derived from the format, e.g. to store RGB data as
+  // floating point with glow::RGB16F
+  pub fn with_internal_format(mut self, internal_format: u32) -> Self {
+    self.internal_format = Some(internal_format);
+    self
+  }
+
+  // Anisotropic filtering, checked against Capabilities when the texture is built
+  pub fn with_anisotropy(self, level: u32) -> Self {
+    self.with_tex_parameter(glow::TEXTURE_MAX_ANISOTROPY_EXT, level)
+  }
+
   pub fn with_alignment(mut self, alignment: u32) -> Self {
     self.alignment = alignment;
     self
@@ -183,6 +318,7 @@ impl<'a, Target: TextureTarget> TextureBuilder<'a, Target> {
       tex_parameters,
       flip,
       format,
+      internal_format,
       alignment,
       ..
     } = self;
@@ -191,19 +327,61 @@ impl<'a, Target: TextureTarget> TextureBuilder<'a, Target> {
       tex_parameters,
       flip,
       format,
+      internal_format,
       alignment,
       _marker: PhantomData,
     }
   }
 
   fn internal_format(&self) -> u32 {
+    if let Some(internal_format) = self.internal_format {
+      return internal_format;
+    }
+
     match self.format {
       glow::RGB | glow::RGBA => self.format,
       glow::RED => glow::R8,
+      glow::RG => glow::RG8,
+      glow::DEPTH_COMPONENT => glow::DEPTH_COMPONENT24,
+      glow::DEPTH_STENCIL => glow::DEPTH24_STENCIL8,
       _ => unimplemented!(),
     }
   }
 
+  // Depth and floating point textures store their texels as something other than bytes
+  fn data_type(&self) -> u32 {
+    match self.format {
+      glow::DEPTH_COMPONENT => glow::UNSIGNED_INT,
+      glow::DEPTH_STENCIL => glow::UNSIGNED_INT_24_8,
+      _ => pixel_format(self.internal_format()).1,
+    }
+  }
+
+  // Fail before creating anything if the context lacks a feature this texture needs
+  unsafe fn check_supported(&self) -> Result<()> {
+    let capabilities = self.gl.capabilities();
+    let is_depth = matches!(self.format, glow::DEPTH_COMPONENT | glow::DEPTH_STENCIL);
+    if is_depth && !capabilities.has_depth_texture {
+      bail!("Depth textures are not supported by this context");
+    }
+    if !is_depth && self.data_type() == glow::FLOAT && !capabilities.has_float_textures {
+      bail!("Floating point textures are not supported by this context");
+    }
+    if let Some(level) = self.tex_parameters.get(&glow::TEXTURE_MAX_ANISOTROPY_EXT) {
+      if !capabilities.has_anisotropic_filtering {
+        bail!("Anisotropic filtering is not supported by this context");
+      }
+      if *level as f32 > capabilities.max_anisotropy {
+        bail!(
+          "Anisotropy {} is above the maximum of {}",
+          level,
+          capabilities.max_anisotropy
+        );
+      }
+    }
+    Ok(())
+  }
+
   fn target() -> u32 {
     Target::TARGET
   }
@@ -220,13 +398,14 @@ impl<'a, Target: TextureTarget> TextureBuilder<'a, Target> {
     (bytes, dimensions)
   }
 
-  unsafe fn apply_texture_parameters(gl: &Context, tex_parameters: HashMap<u32, u32>) {
-    for (key, value) in tex_parameters.into_iter() {
-      gl.tex_parameter_i32(Target::TARGET, key, value as i32);
+  unsafe fn apply_texture_parameters(gl: &Context, tex_parameters: &HashMap<u32, u32>) {
+    for (key, value) in tex_parameters.iter() {
+      gl.tex_parameter_i32(Target::TARGET, *key, *value as i32);
     }
   }
 
   pub unsafe fn render_texture(self, width: u32, height: u32) -> Result<Texture<Target>> {
+    self.check_supported()?;
     let target = Self::target();
     let internal_format = self.internal_format();
     let gl = self.gl;
@@ -243,30 +422,39 @@ impl<'a, Target: TextureTarget> TextureBuilder<'a, Target> {
       height as i32,
       0,
       self.format,
-      glow::UNSIGNED_BYTE,
+      self.data_type(),
       None,
     );
 
-    Self::apply_texture_parameters(gl, self.tex_parameters);
+    Self::apply_texture_parameters(gl, &self.tex_parameters);
 
     gl.bind_texture(target, None);
 
     Ok(Texture {
       texture,
       format: self.format,
+      data_type: self.data_type(),
       _marker: PhantomData,
     })
   }
 }
 
+// Cloning a texture only copies the handle, so all clones share the same GL texture.
+// Whoever owns the last clone is responsible for calling Texture::dispose.
 #[derive(Clone)]
 pub struct Texture<Target = T2d> {
   pub texture: GlTexture,
   format: u32,
+  data_type: u32,
   _marker: PhantomData<Target>,
 }
 
 impl<Target: TextureTarget> Texture<Target> {
+  // Type of each texel's components, e.g. glow::FLOAT for floating point textures
+  pub fn data_type(&self) -> u32 {
+    self.data_type
+  }
+
   pub unsafe fn sub_image(
     &self,
     gl: &Context,
@@ -290,6 +478,44 @@ impl<Target: TextureTarget> Texture<Target> {
       glow::PixelUnpackData::Slice(pixels),
     );
   }
+
+  // Like sub_image, for floating point textures
+  pub unsafe fn sub_image_f32(
+    &self,
+    gl: &Context,
+    x_offset: u32,
+    y_offset: u32,
+    width: u32,
+    height: u32,
+    pixels: &[f32],
+  ) {
+    let target = Target::TARGET;
+    let (_, bytes, _) = pixels.align_to::<u8>();
+    gl.bind_texture(target, Some(self.texture));
+    gl.tex_sub_image_2d(
+      target,
+      0,
+      x_offset as i32,
+      y_offset as i32,
+      width as i32,
+      height as i32,
+      self.format,
+      glow::FLOAT,
+      glow::PixelUnpackData::Slice(bytes),
+    );
+  }
+
+  // Regenerate the mip chain from level 0, e.g. after rendering into the texture
+  pub unsafe fn generate_mipmaps(&self, gl: &Context) {
+    gl.bind_texture(Target::TARGET, Some(self.texture));
+    gl.generate_mipmap(Target::TARGET);
+    gl.bind_texture(Target::TARGET, None);
+  }
+
+  // Free the GL texture. Any remaining clones of this texture become dangling.
+  pub unsafe fn dispose(self, gl: &Context) {
+    gl.delete_texture(self.texture);
+  }
 }
 
 impl<Target: TextureTarget> BindUniform for Texture<Target> {
@@ -302,3 +528,47 @@ impl<Target: TextureTarget> BindUniform for Texture<Target> {
     gl.bind_texture(Target::TARGET, Some(self.texture));
   }
 }
+
+// A rectangular region of a texture atlas or sprite sheet. Binding a SubTexture named
+// `name` binds the whole texture to `name` plus `name_uv_offset` and `name_uv_scale`, so
+// the shader can map its texture coordinates into the region with
+// `uv_offset + tex_coords * uv_scale`.
+#[derive(Clone)]
+pub struct SubTexture {
+  pub texture: Texture,
+  pub uv_offset: Vec2,
+  pub uv_scale: Vec2,
+}
+
+impl SubTexture {
+  // Region from pixel coordinates measured from the top-left corner of the atlas image.
+  // Assumes the atlas was loaded with the default vertical flip, so that the top of the
+  // image is at v = 1.
+  pub fn from_pixels(
+    texture: Texture,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    atlas_width: u32,
+    atlas_height: u32,
+  ) -> Self {
+    let (atlas_width, atlas_height) = (atlas_width as f32, atlas_height as f32);
+    SubTexture {
+      texture,
+      uv_offset: glm::vec2(
+        x as f32 / atlas_width,
+        1. - (y + height) as f32 / atlas_height,
+      ),
+      uv_scale: glm::vec2(width as f32 / atlas_width, height as f32 / atlas_height),
+    }
+  }
+}
+
+impl BindUniform for SubTexture {
+  unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
+    shader.bind_uniform(gl, name, &self.texture);
+    shader.bind_uniform(gl, &format!("{}_uv_offset", name), &self.uv_offset);
+    shader.bind_uniform(gl, &format!("{}_uv_scale", name), &self.uv_scale);
+  }
+}
@@ -0,0 +1,85 @@
+use crate::{
+  gamma, io,
+  post_process::{ScreenQuad, SCREEN_VERTEX_SHADER},
+  prelude::*,
+  shader::ShaderDefines,
+  shader_cache::ShaderCache,
+  texture::Texture,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneAlgorithm {
+  Reinhard,
+  Aces,
+  Uncharted2,
+}
+
+impl ToneAlgorithm {
+  fn define(self) -> &'static str {
+    match self {
+      ToneAlgorithm::Reinhard => "TONEMAP_REINHARD",
+      ToneAlgorithm::Aces => "TONEMAP_ACES",
+      ToneAlgorithm::Uncharted2 => "TONEMAP_UNCHARTED2",
+    }
+  }
+}
+
+// Maps an HDR image into displayable [0, 1] colors. Each algorithm is a variant of
+// tonemap.frag selected by a define, and only gets compiled the first time it's used.
+pub struct Tonemapper {
+  vertex_source: String,
+  fragment_source: String,
+  variants: ShaderCache,
+  quad: ScreenQuad,
+}
+
+impl Tonemapper {
+  pub async unsafe fn new(gl: &Context) -> Result<Self> {
+    let (vertex_source, fragment_source) = try_join!(
+      io::load_string(SCREEN_VERTEX_SHADER),
+      io::load_string("assets/shaders/tonemap.frag")
+    )?;
+
+    Ok(Tonemapper {
+      vertex_source,
+      fragment_source,
+      variants: ShaderCache::new(),
+      quad: ScreenQuad::new(gl)?,
+    })
+  }
+
+  // Draw hdr_tex tonemapped into the currently bound framebuffer. With encode, this is the
+  // final pass and encodes for the display according to gamma::shader_gamma. Without it
+  // the output stays linear, for when a later pass draws it to the screen and encodes it,
+  // e.g. ScreenCapture::replay.
+  pub unsafe fn render(
+    &mut self,
+    gl: &Context,
+    hdr_tex: &Texture,
+    exposure: f32,
+    algorithm: ToneAlgorithm,
+    encode: bool,
+  ) -> Result<()> {
+    let defines = ShaderDefines::new().with_flag(algorithm.define());
+    let shader = self.variants.get_or_compile(
+      gl,
+      &self.vertex_source,
+      &self.fragment_source,
+      None,
+      &defines,
+    )?;
+
+    let mut shader = shader.activate(gl);
+    shader.bind_uniform(gl, "screenTexture", hdr_tex);
+    shader.bind_uniform(gl, "exposure", &exposure);
+    let gamma = if encode { gamma::shader_gamma() } else { 1. };
+    shader.bind_uniform(gl, "gamma", &gamma);
+    self.quad.draw(gl, &mut shader);
+    Ok(())
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.variants.dispose(gl);
+    self.quad.dispose(gl);
+  }
+}
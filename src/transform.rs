@@ -0,0 +1,80 @@
+use crate::{
+  prelude::*,
+  shader::{ActiveShader, BindUniform},
+};
+
+// Position, rotation and scale of an object, applied to it in the order scale, rotate,
+// translate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+  pub position: Vec3,
+  pub rotation: glm::Quat,
+  pub scale: Vec3,
+}
+
+impl Default for Transform {
+  fn default() -> Self {
+    Transform {
+      position: glm::zero(),
+      rotation: glm::quat_identity(),
+      scale: glm::vec3(1., 1., 1.),
+    }
+  }
+}
+
+impl Transform {
+  pub fn from_position(position: Vec3) -> Self {
+    Transform {
+      position,
+      ..Default::default()
+    }
+  }
+
+  // An object at eye with its -Z axis pointing at target, like Camera::look_at.
+  // glm::quat_look_at gives the view rotation (target to -Z), so it's inverted.
+  pub fn look_at(eye: &Vec3, target: &Vec3, up: &Vec3) -> Self {
+    let direction = glm::normalize(&(target - eye));
+    Transform {
+      position: *eye,
+      rotation: glm::quat_inverse(&glm::quat_look_at(&direction, up)),
+      ..Default::default()
+    }
+  }
+
+  // The model matrix, relative to parent if this object is attached to one
+  pub fn matrix(&self, parent: Option<&Transform>) -> Mat4 {
+    let local = glm::translation(&self.position)
+      * glm::quat_to_mat4(&self.rotation)
+      * glm::scaling(&self.scale);
+    match parent {
+      Some(parent) => parent.matrix(None) * local,
+      None => local,
+    }
+  }
+}
+
+// Binds the matrix, e.g. `shader.bind_uniform(gl, "model", &transform)`
+impl BindUniform for Transform {
+  unsafe fn bind_uniform(&self, gl: &Context, shader: &mut ActiveShader, name: &str) {
+    shader.bind_uniform(gl, name, &self.matrix(None));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn look_at_points_negative_z_at_target() {
+    let eye = glm::vec3(1., 2., 3.);
+    let target = glm::vec3(4., -1., 0.);
+    let transform = Transform::look_at(&eye, &target, &glm::vec3(0., 1., 0.));
+    let forward = glm::quat_rotate_vec3(&transform.rotation, &glm::vec3(0., 0., -1.));
+    let expected = glm::normalize(&(target - eye));
+    assert!((forward - expected).norm() < 1e-5, "{:?}", forward);
+
+    // The object's origin ends up at eye
+    let origin = transform.matrix(None) * glm::vec4(0., 0., 0., 1.);
+    assert!((origin.xyz() - eye).norm() < 1e-5);
+  }
+}
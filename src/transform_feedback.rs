@@ -0,0 +1,131 @@
+use crate::prelude::*;
+use std::mem::size_of;
+
+// Buffer that captures vertex shader outputs, as declared with
+// ShaderBuilder::transform_feedback_varyings. Draw calls between begin and end write their
+// outputs into the buffer instead of (or as well as) rasterizing.
+pub struct TransformFeedback {
+  buffer: GlBuffer,
+  feedback: GlTransformFeedback,
+  size: usize,
+}
+
+impl TransformFeedback {
+  // Make a feedback object whose buffer holds `size` bytes of captured output
+  pub unsafe fn new(gl: &Context, size: usize) -> Result<Self> {
+    let buffer = gl.create_buffer().map_err(Error::msg)?;
+    gl.bind_buffer(glow::TRANSFORM_FEEDBACK_BUFFER, Some(buffer));
+    gl.buffer_data_size(
+      glow::TRANSFORM_FEEDBACK_BUFFER,
+      size as i32,
+      glow::DYNAMIC_READ,
+    );
+    gl.bind_buffer(glow::TRANSFORM_FEEDBACK_BUFFER, None);
+
+    // The feedback object remembers which buffer it writes into
+    let feedback = gl.create_transform_feedback().map_err(Error::msg)?;
+    gl.bind_transform_feedback(glow::TRANSFORM_FEEDBACK, Some(feedback));
+    gl.bind_buffer_base(glow::TRANSFORM_FEEDBACK_BUFFER, 0, Some(buffer));
+    gl.bind_transform_feedback(glow::TRANSFORM_FEEDBACK, None);
+
+    Ok(TransformFeedback {
+      buffer,
+      feedback,
+      size,
+    })
+  }
+
+  pub fn buffer(&self) -> GlBuffer {
+    self.buffer
+  }
+
+  // Start capturing. `primitive_mode` must match the draw calls: glow::POINTS,
+  // glow::LINES, or glow::TRIANGLES.
+  pub unsafe fn begin(&self, gl: &Context, primitive_mode: u32) {
+    gl.bind_transform_feedback(glow::TRANSFORM_FEEDBACK, Some(self.feedback));
+    gl.begin_transform_feedback(primitive_mode);
+  }
+
+  pub unsafe fn end(&self, gl: &Context) {
+    gl.end_transform_feedback();
+    gl.bind_transform_feedback(glow::TRANSFORM_FEEDBACK, None);
+  }
+
+  // Run draw with its outputs captured and nothing rasterized, e.g. for a simulation step
+  // that only updates vertex data
+  pub unsafe fn capture(&self, gl: &Context, primitive_mode: u32, draw: impl FnOnce(&Context)) {
+    gl.enable(glow::RASTERIZER_DISCARD);
+    self.begin(gl, primitive_mode);
+    draw(gl);
+    self.end(gl);
+    gl.disable(glow::RASTERIZER_DISCARD);
+  }
+
+  // Copy the captured outputs back to the CPU. This stalls until the GPU is done, so it's
+  // meant for debugging rather than every frame.
+  pub unsafe fn read_back(&self, gl: &Context) -> Vec<f32> {
+    let mut bytes = vec![0u8; self.size];
+    gl.bind_buffer(glow::TRANSFORM_FEEDBACK_BUFFER, Some(self.buffer));
+    gl.get_buffer_sub_data(glow::TRANSFORM_FEEDBACK_BUFFER, 0, &mut bytes);
+    gl.bind_buffer(glow::TRANSFORM_FEEDBACK_BUFFER, None);
+
+    bytes
+      .chunks_exact(size_of::<f32>())
+      .map(|chunk| f32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+      .collect()
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    gl.delete_transform_feedback(self.feedback);
+    gl.delete_buffer(self.buffer);
+  }
+}
+
+// Pair of feedback buffers for simulations that read last frame's output as this frame's
+// input, like framebuffer::PingPong does for textures
+pub struct TransformFeedbackPingPong {
+  feedbacks: [TransformFeedback; 2],
+  current: usize,
+}
+
+impl TransformFeedbackPingPong {
+  // Both buffers hold `size` bytes. Fill the initial state with initial_data, which is
+  // what the first call to next returns as its input.
+  pub unsafe fn new(gl: &Context, size: usize, initial_data: &[u8]) -> Result<Self> {
+    let feedbacks = [
+      TransformFeedback::new(gl, size)?,
+      TransformFeedback::new(gl, size)?,
+    ];
+    gl.bind_buffer(glow::ARRAY_BUFFER, Some(feedbacks[0].buffer));
+    gl.buffer_sub_data_u8_slice(glow::ARRAY_BUFFER, 0, initial_data);
+    gl.bind_buffer(glow::ARRAY_BUFFER, None);
+    Ok(TransformFeedbackPingPong {
+      feedbacks,
+      current: 0,
+    })
+  }
+
+  // Swap the buffers. Returns the buffer holding the latest state, to bind as vertex input,
+  // and the feedback to capture the next state into.
+  pub fn next(&mut self) -> (GlBuffer, &TransformFeedback) {
+    let read = self.current;
+    self.current = 1 - self.current;
+    (self.feedbacks[read].buffer, &self.feedbacks[self.current])
+  }
+
+  // Buffer written by the most recent capture
+  pub fn result(&self) -> GlBuffer {
+    self.feedbacks[self.current].buffer
+  }
+
+  // Both buffers, e.g. to set up a vertex array for each ahead of time
+  pub fn buffers(&self) -> [GlBuffer; 2] {
+    [self.feedbacks[0].buffer, self.feedbacks[1].buffer]
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    let [a, b] = self.feedbacks;
+    a.dispose(gl);
+    b.dispose(gl);
+  }
+}
@@ -0,0 +1,103 @@
+use crate::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+  pub x: i32,
+  pub y: i32,
+  pub width: i32,
+  pub height: i32,
+}
+
+impl Viewport {
+  pub unsafe fn apply(&self, gl: &Context) {
+    gl.viewport(self.x, self.y, self.width, self.height);
+  }
+
+  pub fn aspect(&self) -> f32 {
+    self.width as f32 / self.height as f32
+  }
+
+  // Divide the viewport into `count` equal columns, left to right, e.g. for split-screen
+  pub fn split_horizontal(&self, count: i32) -> Vec<Viewport> {
+    let width = self.width / count;
+    (0..count)
+      .map(|i| Viewport {
+        x: self.x + i * width,
+        width,
+        ..*self
+      })
+      .collect()
+  }
+
+  // Run draw with rendering restricted to this viewport. The scissor test keeps clears from
+  // touching the rest of the screen. Afterwards the viewport is popped off viewports and the
+  // scissor test is turned back off.
+  pub unsafe fn render(
+    &self,
+    gl: &Context,
+    viewports: &mut ViewportStack,
+    draw: impl FnOnce(&Context),
+  ) {
+    viewports.push(gl, self.x, self.y, self.width, self.height);
+    gl.enable(glow::SCISSOR_TEST);
+    gl.scissor(self.x, self.y, self.width, self.height);
+
+    draw(gl);
+
+    gl.disable(glow::SCISSOR_TEST);
+    viewports.pop(gl);
+  }
+}
+
+// Saves and restores the GL viewport around nested passes, e.g. rendering a shadow map
+// at its own resolution in the middle of drawing to the screen. Reading the viewport back
+// from GL is slow (and not possible through glow), so the stack remembers the last one it
+// set instead. Viewport changes made behind its back aren't seen.
+pub struct ViewportStack {
+  current: Viewport,
+  stack: Vec<Viewport>,
+}
+
+impl ViewportStack {
+  // Start from the viewport in use, usually the whole window
+  pub unsafe fn new(gl: &Context, viewport: Viewport) -> Self {
+    viewport.apply(gl);
+    ViewportStack {
+      current: viewport,
+      stack: Vec::new(),
+    }
+  }
+
+  pub fn current(&self) -> Viewport {
+    self.current
+  }
+
+  // Switch viewports without saving the old one, e.g. when the window is resized
+  pub unsafe fn set(&mut self, gl: &Context, viewport: Viewport) {
+    self.current = viewport;
+    viewport.apply(gl);
+  }
+
+  // Remember the current viewport and switch to a new one
+  pub unsafe fn push(&mut self, gl: &Context, x: i32, y: i32, width: i32, height: i32) {
+    let viewport = Viewport {
+      x,
+      y,
+      width,
+      height,
+    };
+    self.stack.push(self.current);
+    self.current = viewport;
+    viewport.apply(gl);
+  }
+
+  // Restore the viewport from before the matching push
+  pub unsafe fn pop(&mut self, gl: &Context) {
+    let viewport = self
+      .stack
+      .pop()
+      .expect("ViewportStack::pop called without a matching push");
+    self.current = viewport;
+    viewport.apply(gl);
+  }
+}
@@ -0,0 +1,79 @@
+use crate::{
+  camera::Camera, framebuffer::Framebuffer, post_process::PostProcess, prelude::*, texture::Texture,
+};
+
+// Height fog found by ray marching from the camera to the scene depth of each pixel. The
+// result holds the fog color in RGB and its opacity in alpha, so blending it over the lit
+// scene with regular alpha blending fades distant and low-lying geometry into the fog.
+pub struct VolumetricFog {
+  framebuffer: Framebuffer,
+  march_pass: PostProcess,
+  composite_pass: PostProcess,
+
+  // Samples along each ray. More steps resolve thin layers of fog better but cost more.
+  pub steps: u32,
+  // How far rays through the sky (depth 1) march before stopping
+  pub max_distance: f32,
+  // Fog is at full density below this height, and thins out above it
+  pub base_height: f32,
+  pub height_falloff: f32,
+}
+
+impl VolumetricFog {
+  pub async unsafe fn new(gl: &Context, width: u32, height: u32) -> Result<Self> {
+    Ok(VolumetricFog {
+      framebuffer: Framebuffer::with_color_format(gl, width, height, glow::RGBA16F)?,
+      march_pass: PostProcess::new(gl, "assets/shaders/volumetric_fog.frag").await?,
+      composite_pass: PostProcess::new(gl, "assets/shaders/fog_composite.frag").await?,
+      steps: 32,
+      max_distance: 100.,
+      base_height: 0.,
+      height_falloff: 0.2,
+    })
+  }
+
+  // March the fog for the scene whose depth buffer is depth_tex. density is the fraction
+  // of light absorbed per unit of distance at full density.
+  pub unsafe fn render(
+    &self,
+    gl: &Context,
+    depth_tex: &Texture,
+    camera: &Camera,
+    fog_color: Vec3,
+    density: f32,
+  ) {
+    let inverse_view_projection = (camera.projection() * camera.view_matrix())
+      .try_inverse()
+      .unwrap_or_else(Mat4::identity);
+
+    self.framebuffer.bind(gl);
+    self.march_pass.draw_with(gl, |gl, shader| {
+      shader.bind_uniform(gl, "depth_texture", depth_tex);
+      shader.bind_uniform(gl, "inverse_view_projection", &inverse_view_projection);
+      shader.bind_uniform(gl, "camera_pos", &camera.pos);
+      shader.bind_uniform(gl, "fog_color", &fog_color);
+      shader.bind_uniform(gl, "density", &density);
+      shader.bind_uniform(gl, "steps", &(self.steps.max(1) as i32));
+      shader.bind_uniform(gl, "max_distance", &self.max_distance);
+      shader.bind_uniform(gl, "base_height", &self.base_height);
+      shader.bind_uniform(gl, "height_falloff", &self.height_falloff);
+    });
+    self.framebuffer.unbind(gl);
+  }
+
+  // Fog color and opacity from the last render
+  pub fn result(&self) -> &Texture {
+    self.framebuffer.color.as_ref().unwrap()
+  }
+
+  // Blend the fog over the lit scene in the currently bound framebuffer
+  pub unsafe fn composite(&self, gl: &Context) {
+    self.composite_pass.draw(gl, self.result(), |_, _| {});
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    self.framebuffer.dispose(gl);
+    self.march_pass.dispose(gl);
+    self.composite_pass.dispose(gl);
+  }
+}
@@ -1,4 +1,5 @@
-use glow::*;
+use crate::context::Context;
+use instant::Instant;
 use winit::{
   event_loop::EventLoop,
   window::{self as winit_window, WindowBuilder},
@@ -7,33 +8,111 @@ use winit::{
 #[cfg(not(target_arch = "wasm32"))]
 mod platform {
   use super::*;
+  use winit::dpi::PhysicalSize;
 
-  pub struct Window(glutin::ContextWrapper<glutin::PossiblyCurrent, winit_window::Window>);
+  pub struct Window {
+    context: glutin::ContextWrapper<glutin::PossiblyCurrent, winit_window::Window>,
+    vsync: bool,
+  }
 
   impl Window {
-    // Use glutin to get an OpenGL context from winit via glutin::ContextWrapper
-    pub fn build(wb: WindowBuilder, event_loop: &EventLoop<()>) -> (Self, Context) {
+    // Use glutin to get an OpenGL context from winit via glutin::ContextWrapper.
+    // With vsync, swap_buffers blocks until the next vertical blank.
+    pub fn build(wb: WindowBuilder, event_loop: &EventLoop<()>, vsync: bool) -> (Self, Context) {
       unsafe {
         let windowed_context = glutin::ContextBuilder::new()
-          .with_vsync(true)
+          .with_vsync(vsync)
           .build_windowed(wb, event_loop)
           .unwrap();
         let windowed_context = windowed_context.make_current().unwrap();
         let gl =
           glow::Context::from_loader_function(|s| windowed_context.get_proc_address(s) as *const _);
+        let gl = Context::new(gl);
 
-        (Window(windowed_context), gl)
+        (
+          Window {
+            context: windowed_context,
+            vsync,
+          },
+          gl,
+        )
       }
     }
 
     pub fn winit(&self) -> &winit_window::Window {
-      self.0.window()
+      self.context.window()
+    }
+
+    pub fn vsync(&self) -> bool {
+      self.vsync
     }
 
     pub fn swap_buffers(&self) {
-      self.0.swap_buffers().unwrap();
+      self.context.swap_buffers().unwrap();
     }
   }
+
+  // An OpenGL context without a window, e.g. for rendering tests in CI. Not every platform
+  // gives headless contexts a default framebuffer, so draw into a Framebuffer and read it
+  // back with framebuffer::read_pixels_to_image.
+  pub struct HeadlessContext {
+    _context: glutin::Context<glutin::PossiblyCurrent>,
+    // The context uses the event loop's display connection, so it's dropped after the
+    // context
+    _event_loop: EventLoop<()>,
+  }
+
+  impl HeadlessContext {
+    // Unlike Window::build, this makes its own event loop, which is allowed off the main
+    // thread on Linux and Windows. So it works in `cargo test`, where each test runs on
+    // its own thread.
+    pub fn build(width: u32, height: u32) -> anyhow::Result<(Self, Context)> {
+      unsafe {
+        let event_loop = any_thread_event_loop();
+        let context = glutin::ContextBuilder::new()
+          .build_headless(&event_loop, PhysicalSize::new(width, height))?;
+        let context = context.make_current().map_err(|(_, err)| err)?;
+        let gl = glow::Context::from_loader_function(|s| context.get_proc_address(s) as *const _);
+        let gl = Context::new(gl);
+        let headless = HeadlessContext {
+          _context: context,
+          _event_loop: event_loop,
+        };
+        Ok((headless, gl))
+      }
+    }
+  }
+
+  #[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+  ))]
+  fn any_thread_event_loop() -> EventLoop<()> {
+    use winit::platform::unix::EventLoopExtUnix;
+    EventLoop::new_any_thread()
+  }
+
+  #[cfg(target_os = "windows")]
+  fn any_thread_event_loop() -> EventLoop<()> {
+    use winit::platform::windows::EventLoopExtWindows;
+    EventLoop::new_any_thread()
+  }
+
+  // macOS only allows event loops on the main thread
+  #[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "windows"
+  )))]
+  fn any_thread_event_loop() -> EventLoop<()> {
+    EventLoop::new()
+  }
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -43,12 +122,16 @@ mod platform {
   use wasm_bindgen::{JsCast, JsValue};
   use winit::platform::web::WindowBuilderExtWebSys;
 
-  pub struct Window(winit_window::Window);
+  pub struct Window {
+    window: winit_window::Window,
+    vsync: bool,
+  }
 
   impl Window {
-    // Use winit::WindowBuilder::with_canvas and glow::Context::from_webgl2_context
-    // to set up the web window
-    pub fn build(wb: WindowBuilder, event_loop: &EventLoop<()>) -> (Self, Context) {
+    // Use winit::WindowBuilder::with_canvas and Context::from_webgl2_context
+    // to set up the web window. Browsers don't let us change the swap interval, so vsync
+    // here means drawing from requestAnimationFrame (which winit uses for redraw requests).
+    pub fn build(wb: WindowBuilder, event_loop: &EventLoop<()>, vsync: bool) -> (Self, Context) {
       let canvas = web_sys::window()
         .unwrap()
         .document()
@@ -73,11 +156,15 @@ mod platform {
 
       let window = wb.with_canvas(Some(canvas)).build(event_loop).unwrap();
       let gl = Context::from_webgl2_context(webgl2_context);
-      (Window(window), gl)
+      (Window { window, vsync }, gl)
     }
 
     pub fn winit(&self) -> &winit_window::Window {
-      &self.0
+      &self.window
+    }
+
+    pub fn vsync(&self) -> bool {
+      self.vsync
     }
 
     pub fn swap_buffers(&self) {}
@@ -85,3 +172,84 @@ mod platform {
 }
 
 pub use platform::Window;
+
+// Measures the time between consecutive frames, so animation can run at the same speed
+// regardless of frame rate
+pub struct FrameTimer {
+  last_frame: Instant,
+  dt: f32,
+  frame_count: u64,
+}
+
+impl FrameTimer {
+  pub fn new() -> Self {
+    FrameTimer {
+      last_frame: Instant::now(),
+      dt: 0.,
+      frame_count: 0,
+    }
+  }
+
+  // Call once per frame. Returns the seconds elapsed since the previous call.
+  pub fn tick(&mut self) -> f32 {
+    let now = Instant::now();
+    self.dt = (now - self.last_frame).as_nanos() as f32 / 1e9;
+    self.last_frame = now;
+    self.frame_count += 1;
+    self.dt
+  }
+
+  pub fn dt(&self) -> f32 {
+    self.dt
+  }
+
+  pub fn fps(&self) -> f32 {
+    if self.dt > 0. {
+      1. / self.dt
+    } else {
+      0.
+    }
+  }
+
+  pub fn frame_count(&self) -> u64 {
+    self.frame_count
+  }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+  use super::*;
+  use crate::framebuffer::{self, Framebuffer};
+  use glow::HasContext;
+
+  // winit needs a display server to connect to, even for a headless context, so this only
+  // runs when asked for with `cargo test -- --ignored`
+  #[test]
+  #[ignore]
+  fn headless_renders_to_framebuffer() {
+    let (headless, gl) = platform::HeadlessContext::build(4, 4).unwrap();
+    unsafe {
+      let target = Framebuffer::with_color(&gl, 4, 4).unwrap();
+      target.bind(&gl);
+      gl.viewport(0, 0, 4, 4);
+      gl.clear_color(0., 0., 0., 1.);
+      gl.clear(glow::COLOR_BUFFER_BIT);
+
+      // Fill the bottom-left quarter (in GL's coordinates) with red
+      gl.enable(glow::SCISSOR_TEST);
+      gl.scissor(0, 0, 2, 2);
+      gl.clear_color(1., 0., 0., 1.);
+      gl.clear(glow::COLOR_BUFFER_BIT);
+      gl.disable(glow::SCISSOR_TEST);
+
+      // Images start at the top, so the red ends up in the bottom-left of the image
+      let image = framebuffer::read_pixels_to_image(&gl, 4, 4);
+      assert_eq!(image.get_pixel(0, 3).0, [255, 0, 0, 255]);
+      assert_eq!(image.get_pixel(3, 3).0, [0, 0, 0, 255]);
+      assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 255]);
+
+      target.dispose(&gl);
+    }
+    drop(headless);
+  }
+}
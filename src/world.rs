@@ -0,0 +1,60 @@
+use crate::{
+  camera::{Camera, CameraBlock},
+  light::{DirLight, PointLight, SpotLight},
+  model::Model,
+  prelude::*,
+  shader::{Shader, UniformBlock},
+};
+
+// A camera, some lights, and a list of models to draw with them. Rendering binds the
+// camera and lights once and then draws every model, which covers the common case of
+// drawing a bunch of lit objects with a single shader.
+pub struct World {
+  pub camera: Camera,
+  pub dir_lights: Vec<DirLight>,
+  pub point_lights: Vec<PointLight>,
+  pub spot_lights: Vec<SpotLight>,
+  pub entities: Vec<(Model, Mat4)>,
+  camera_ubo: UniformBlock<CameraBlock>,
+}
+
+impl World {
+  // The camera uniform block is put at binding 0, so don't share that slot with another
+  // camera block at the same time
+  pub unsafe fn new(gl: &Context, camera: Camera) -> Result<Self> {
+    Ok(World {
+      camera,
+      dir_lights: vec![],
+      point_lights: vec![],
+      spot_lights: vec![],
+      entities: vec![],
+      camera_ubo: UniformBlock::new(gl, 0)?,
+    })
+  }
+
+  pub fn add(&mut self, model: Model, transform: Mat4) {
+    self.entities.push((model, transform));
+  }
+
+  pub unsafe fn render(&self, gl: &Context, shader: &Shader) {
+    self.camera_ubo.upload(gl, &self.camera.uniform_block());
+
+    let mut shader = shader.activate(gl);
+    shader.bind_uniform(gl, "CameraBlock", &self.camera_ubo);
+    shader.bind_uniform(gl, "dir_lights", &self.dir_lights);
+    shader.bind_uniform(gl, "point_lights", &self.point_lights);
+    shader.bind_uniform(gl, "spot_lights", &self.spot_lights);
+
+    for (model, transform) in &self.entities {
+      shader.bind_uniform(gl, "model", transform);
+      model.draw(gl, &mut shader);
+    }
+  }
+
+  pub unsafe fn dispose(self, gl: &Context) {
+    for (model, _) in self.entities {
+      model.dispose(gl);
+    }
+    self.camera_ubo.dispose(gl);
+  }
+}